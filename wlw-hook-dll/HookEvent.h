@@ -1,15 +1,35 @@
+// Generated by wlw-server/build.rs from wlw-server/protocol.schema; edit
+// that instead. Every message is a FrameHeader followed by the body of its
+// kind: the fixed fields below, then any strings, each a uint16_t byte count
+// followed by that much UTF-8. Receivers ignore bytes past the fields they
+// know.
 #pragma once
 
+#include <stddef.h>
 #include <stdint.h>
 #include <windows.h>
 
+// Hook to server. An empty frame is never sent this way.
 enum Kind {
-    KIND_CWP_SHOW_WINDOW,
-    KIND_CBT_ACTIVATE,
-    KIND_CBT_CREATE_WINDOW,
-    KIND_CBT_DESTROY_WINDOW,
-    KIND_CBT_MIN_MAX,
-    KIND_CBT_MOVE_SIZE,
+    KIND_EMPTY = 0,
+    KIND_HELLO = 1,
+    KIND_CWP_SHOW_WINDOW = 2,
+    KIND_CBT_ACTIVATE = 3,
+    KIND_CBT_CREATE_WINDOW = 4,
+    KIND_CBT_DESTROY_WINDOW = 5,
+    KIND_CBT_MIN_MAX = 6,
+    KIND_CBT_MOVE_SIZE = 7,
+    KIND_CWP_SET_TEXT = 8,
+    KIND_CBT_SET_FOCUS = 9,
+    KIND_CBT_SYS_COMMAND = 10,
+    KIND_CWP_WINDOW_POS_CHANGED = 11,
+};
+
+// Server to hook. An empty frame leaves everything as it was.
+enum ResponseKind {
+    RESPONSE_KIND_EMPTY = 0,
+    RESPONSE_KIND_POS_AND_SIZE = 1,
+    RESPONSE_KIND_VERDICT = 2,
 };
 
 typedef uint8_t PortableBOOL;
@@ -18,6 +38,12 @@ typedef uint32_t PortableHWND;
 typedef int32_t PortableLONG;
 typedef int32_t PortableInt;
 
+// Bump whenever a message changes shape other than by appending fields
+#define PROTOCOL_VERSION 6
+// Set on frames whose sender waits for the server's answer
+#define FRAME_FLAG_WANTS_REPLY 1
+#define MAX_FRAME_SIZE (64 * 1024)
+
 #pragma pack(push, 1)
 struct _PortableRECT {
     PortableLONG left;
@@ -27,43 +53,158 @@ struct _PortableRECT {
 };
 typedef struct _PortableRECT PortableRECT;
 
-struct _HookEvent {
-    uint8_t kind;
-    union {
-        struct {
-            PortableHWND hwnd;
-            PortableBOOL shown;
-        } cwp_show_window_data;
-        struct {
-            PortableHWND hwnd;
-            PortableBOOL caused_by_mouse;
-        } cbt_activate_data;
-        struct {
-            PortableHWND hwnd;
-            PortableRECT rect;
-        } cbt_create_window_data;
-        struct {
-            PortableHWND hwnd;
-        } cbt_destroy_window_data;
-        struct {
-            PortableHWND hwnd;
-            PortableInt show_command;
-        } cbt_min_max_data;
-        struct {
-            PortableHWND hwnd;
-            PortableRECT rect;
-        } cbt_move_size_data;
-    };
-};
-typedef struct _HookEvent HookEvent;
-
-struct _HookResponse {
-    union {
-        struct {
-            PortableRECT rect;
-        } pos_and_size_data;
-    };
-};
-typedef struct _HookResponse HookResponse;
+// Starts every message, in both directions. `length` counts the bytes of the
+// body, which follows straight after. The sender picks `id`; an answer carries
+// the id of the frame it answers. This hook has one request in flight at a
+// time and only checks the id against the request it is waiting on.
+struct _FrameHeader {
+    uint32_t length;
+    uint16_t kind;
+    uint16_t flags;
+    uint32_t id;
+};
+typedef struct _FrameHeader FrameHeader;
+
+// What a hook says about itself when it connects, before sending any
+// events.
+struct _HelloBody {
+    uint16_t protocol_version;
+    // 32 or 64; hooks of either width speak the same portable protocol.
+    uint8_t pointer_width;
+    uint32_t pid;
+    // The file name of the hooked process's executable.
+    // Then the string image_name
+};
+typedef struct _HelloBody HelloBody;
+
+// Who a window belongs to, as the hook saw it when an event happened.
+struct _WindowInfoBody {
+    uint32_t pid;
+    uint32_t thread_id;
+    // Then the string class_name
+    // The file name of the owning process's executable.
+    // Then the string exe
+};
+typedef struct _WindowInfoBody WindowInfoBody;
+
+// What happens to windows, sent as it happens: a u16 byte count, the
+// event's fields, then the window's WindowInfo. Hooks wait for a response
+// to every CBT event but CbtDestroyWindow, CbtSetFocus and CbtSysCommand,
+// which they may veto.
+struct _CwpShowWindowBody {
+    PortableHWND hwnd;
+    PortableBOOL shown;
+};
+typedef struct _CwpShowWindowBody CwpShowWindowBody;
+
+struct _CbtActivateBody {
+    PortableHWND hwnd;
+    PortableBOOL caused_by_mouse;
+};
+typedef struct _CbtActivateBody CbtActivateBody;
+
+struct _CbtCreateWindowBody {
+    PortableHWND hwnd;
+    PortableRECT rect;
+};
+typedef struct _CbtCreateWindowBody CbtCreateWindowBody;
+
+struct _CbtDestroyWindowBody {
+    PortableHWND hwnd;
+};
+typedef struct _CbtDestroyWindowBody CbtDestroyWindowBody;
+
+struct _CbtMinMaxBody {
+    PortableHWND hwnd;
+    PortableInt show_command;
+};
+typedef struct _CbtMinMaxBody CbtMinMaxBody;
+
+struct _CbtMoveSizeBody {
+    PortableHWND hwnd;
+    PortableRECT rect;
+};
+typedef struct _CbtMoveSizeBody CbtMoveSizeBody;
+
+// The window's title is about to change to `title`.
+struct _CwpSetTextBody {
+    PortableHWND hwnd;
+    // Then the string title
+};
+typedef struct _CwpSetTextBody CwpSetTextBody;
+
+// Keyboard focus is about to move into the window.
+struct _CbtSetFocusBody {
+    PortableHWND hwnd;
+};
+typedef struct _CbtSetFocusBody CbtSetFocusBody;
+
+// A window menu or title bar command, one of the `SC_*` values, is about
+// to be carried out on the window.
+struct _CbtSysCommandBody {
+    PortableHWND hwnd;
+    PortableInt command;
+};
+typedef struct _CbtSysCommandBody CbtSysCommandBody;
+
+// Where the window ended up after being moved, resized or shown.
+struct _CwpWindowPosChangedBody {
+    PortableHWND hwnd;
+    PortableRECT rect;
+};
+typedef struct _CwpWindowPosChangedBody CwpWindowPosChangedBody;
+
+// How the server wants the event the hook waits on changed.
+struct _PosAndSizeBody {
+    PortableRECT rect;
+};
+typedef struct _PosAndSizeBody PosAndSizeBody;
+
+// Lets the event go ahead unchanged, or stops it.
+struct _VerdictBody {
+    PortableBOOL allow;
+};
+typedef struct _VerdictBody VerdictBody;
 
 #pragma pack(pop)
+
+C_ASSERT(sizeof(PortableRECT) == 16);
+C_ASSERT(sizeof(FrameHeader) == 12);
+C_ASSERT(sizeof(HelloBody) == 7);
+C_ASSERT(offsetof(HelloBody, protocol_version) == 0);
+C_ASSERT(offsetof(HelloBody, pointer_width) == 2);
+C_ASSERT(offsetof(HelloBody, pid) == 3);
+C_ASSERT(sizeof(WindowInfoBody) == 8);
+C_ASSERT(offsetof(WindowInfoBody, pid) == 0);
+C_ASSERT(offsetof(WindowInfoBody, thread_id) == 4);
+C_ASSERT(sizeof(CwpShowWindowBody) == 5);
+C_ASSERT(offsetof(CwpShowWindowBody, hwnd) == 0);
+C_ASSERT(offsetof(CwpShowWindowBody, shown) == 4);
+C_ASSERT(sizeof(CbtActivateBody) == 5);
+C_ASSERT(offsetof(CbtActivateBody, hwnd) == 0);
+C_ASSERT(offsetof(CbtActivateBody, caused_by_mouse) == 4);
+C_ASSERT(sizeof(CbtCreateWindowBody) == 20);
+C_ASSERT(offsetof(CbtCreateWindowBody, hwnd) == 0);
+C_ASSERT(offsetof(CbtCreateWindowBody, rect) == 4);
+C_ASSERT(sizeof(CbtDestroyWindowBody) == 4);
+C_ASSERT(offsetof(CbtDestroyWindowBody, hwnd) == 0);
+C_ASSERT(sizeof(CbtMinMaxBody) == 8);
+C_ASSERT(offsetof(CbtMinMaxBody, hwnd) == 0);
+C_ASSERT(offsetof(CbtMinMaxBody, show_command) == 4);
+C_ASSERT(sizeof(CbtMoveSizeBody) == 20);
+C_ASSERT(offsetof(CbtMoveSizeBody, hwnd) == 0);
+C_ASSERT(offsetof(CbtMoveSizeBody, rect) == 4);
+C_ASSERT(sizeof(CwpSetTextBody) == 4);
+C_ASSERT(offsetof(CwpSetTextBody, hwnd) == 0);
+C_ASSERT(sizeof(CbtSetFocusBody) == 4);
+C_ASSERT(offsetof(CbtSetFocusBody, hwnd) == 0);
+C_ASSERT(sizeof(CbtSysCommandBody) == 8);
+C_ASSERT(offsetof(CbtSysCommandBody, hwnd) == 0);
+C_ASSERT(offsetof(CbtSysCommandBody, command) == 4);
+C_ASSERT(sizeof(CwpWindowPosChangedBody) == 20);
+C_ASSERT(offsetof(CwpWindowPosChangedBody, hwnd) == 0);
+C_ASSERT(offsetof(CwpWindowPosChangedBody, rect) == 4);
+C_ASSERT(sizeof(PosAndSizeBody) == 16);
+C_ASSERT(offsetof(PosAndSizeBody, rect) == 0);
+C_ASSERT(sizeof(VerdictBody) == 1);
+C_ASSERT(offsetof(VerdictBody, allow) == 0);
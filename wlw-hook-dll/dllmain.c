@@ -8,11 +8,154 @@ uint32_t server_pid = 0;
 #pragma comment(linker, "/section:shared,RWS")
 
 volatile LONG ready = FALSE;
+// Ids for frames the server answers; the answer carries the id back
+volatile LONG last_id = 0;
 HANDLE pipe = INVALID_HANDLE_VALUE;
 CRITICAL_SECTION mutex;
 
 #define PIPE_NAME_BUF_LEN 256
 
+// Room for the executable's name as UTF-8, which takes up to three bytes per
+// UTF-16 unit
+#define IMAGE_NAME_BUF_LEN (MAX_PATH * 3)
+// Window class names are at most 256 UTF-16 units
+#define CLASS_NAME_LEN 256
+// Titles are cut short after this many UTF-16 units
+#define TITLE_LEN 256
+// The largest event body, which is a title change
+#define EVENT_BODY_BUF_LEN                                                   \
+    (sizeof(CwpSetTextBody) + sizeof(uint16_t) + TITLE_LEN * 3)
+// A counted event body, then the window's WindowInfo and its two strings
+#define EVENT_BUF_LEN                                                        \
+    (sizeof(uint16_t) + EVENT_BODY_BUF_LEN + sizeof(WindowInfoBody)          \
+     + 2 * sizeof(uint16_t) + CLASS_NAME_LEN * 3 + IMAGE_NAME_BUF_LEN)
+// Enough for any response this hook understands; anything longer fails the
+// transaction
+#define RESPONSE_BUF_LEN 256
+
+// This process's executable's file name as UTF-8, found once at attach
+static uint8_t image_name[IMAGE_NAME_BUF_LEN];
+static uint16_t image_name_len = 0;
+
+static void find_image_name(void) {
+    WCHAR path[MAX_PATH];
+    DWORD path_len = GetModuleFileNameW(NULL, path, MAX_PATH);
+    if (path_len == 0 || path_len >= MAX_PATH) {
+        return;
+    }
+    const WCHAR *name = path;
+    for (const WCHAR *c = path; *c; ++c) {
+        if (*c == L'\\' || *c == L'/') {
+            name = c + 1;
+        }
+    }
+    // Counted rather than terminated, so leave the NUL behind
+    int converted = WideCharToMultiByte(CP_UTF8, 0, name, -1, (LPSTR)image_name,
+                                        IMAGE_NAME_BUF_LEN, NULL, NULL);
+    if (converted > 0) {
+        image_name_len = (uint16_t)(converted - 1);
+    }
+}
+
+// Writes a counted string to `dest` and gives the first byte after it.
+static uint8_t *put_str(uint8_t *dest, const uint8_t *str, uint16_t len) {
+    CopyMemory(dest, &len, sizeof(uint16_t));
+    CopyMemory(dest + sizeof(uint16_t), str, len);
+    return dest + sizeof(uint16_t) + len;
+}
+
+// Writes up to `max_len` UTF-16 units of `str` to `dest` as a counted UTF-8
+// string, which takes up to three bytes per unit, and gives the first byte
+// after it.
+static uint8_t *put_wide_str(uint8_t *dest,
+                             const WCHAR *str,
+                             int len,
+                             int max_len) {
+    if (len > max_len) {
+        len = max_len;
+        // Rather than split a surrogate pair
+        if (IS_HIGH_SURROGATE(str[len - 1])) {
+            --len;
+        }
+    }
+    int converted = 0;
+    if (len > 0) {
+        converted = WideCharToMultiByte(
+            CP_UTF8, 0, str, len, (LPSTR)(dest + sizeof(uint16_t)),
+            max_len * 3, NULL, NULL);
+    }
+    uint16_t converted_len = (uint16_t)converted;
+    CopyMemory(dest, &converted_len, sizeof(uint16_t));
+    return dest + sizeof(uint16_t) + converted_len;
+}
+
+// Writes the WindowInfo which follows every event to `dest`, which must have
+// room for it, and gives the first byte after it.
+static uint8_t *put_window_info(uint8_t *dest, HWND hwnd) {
+    WindowInfoBody info;
+    DWORD pid = 0;
+    info.thread_id = (uint32_t)GetWindowThreadProcessId(hwnd, &pid);
+    info.pid = (uint32_t)pid;
+    CopyMemory(dest, &info, sizeof(info));
+    dest += sizeof(info);
+
+    WCHAR class_name[CLASS_NAME_LEN + 1];
+    int class_name_len = GetClassNameW(hwnd, class_name, CLASS_NAME_LEN + 1);
+    dest = put_wide_str(dest, class_name, class_name_len, CLASS_NAME_LEN);
+    // Hooks run in the window's own process
+    return put_str(dest, image_name, image_name_len);
+}
+
+// Writes a header and `body` to `frame`, which must have room for both, and
+// gives the size of the whole frame.
+static DWORD make_frame(uint8_t *frame,
+                        uint16_t kind,
+                        uint16_t flags,
+                        uint32_t id,
+                        const void *body,
+                        uint32_t body_len) {
+    FrameHeader *header = (FrameHeader *)frame;
+    header->length = body_len;
+    header->kind = kind;
+    header->flags = flags;
+    header->id = id;
+    CopyMemory(frame + sizeof(FrameHeader), body, body_len);
+    return (DWORD)(sizeof(FrameHeader) + body_len);
+}
+
+// Writes an event's frame, with `body` behind its byte count and the window's
+// WindowInfo after it, and gives the size of the whole frame. `frame` must
+// have room for a header and EVENT_BUF_LEN bytes.
+static DWORD make_event_frame(uint8_t *frame,
+                              uint16_t kind,
+                              uint16_t flags,
+                              uint32_t id,
+                              HWND hwnd,
+                              const void *body,
+                              uint32_t body_len) {
+    uint8_t event[EVENT_BUF_LEN];
+    uint16_t counted_len = (uint16_t)body_len;
+    CopyMemory(event, &counted_len, sizeof(uint16_t));
+    CopyMemory(event + sizeof(uint16_t), body, body_len);
+    uint8_t *end = put_window_info(event + sizeof(uint16_t) + body_len, hwnd);
+    return make_frame(frame, kind, flags, id, event, (uint32_t)(end - event));
+}
+
+static BOOL send_hello(void) {
+    uint8_t body[sizeof(HelloBody) + sizeof(uint16_t) + IMAGE_NAME_BUF_LEN];
+    HelloBody *hello = (HelloBody *)body;
+    hello->protocol_version = PROTOCOL_VERSION;
+    hello->pointer_width = (uint8_t)(sizeof(void *) * 8);
+    hello->pid = (PortableDWORD)GetCurrentProcessId();
+    uint8_t *end = put_str(body + sizeof(HelloBody), image_name, image_name_len);
+
+    uint8_t frame[sizeof(FrameHeader) + sizeof(body)];
+    DWORD frame_len
+        = make_frame(frame, KIND_HELLO, 0, 0, body, (uint32_t)(end - body));
+    DWORD num_sent;
+    return WriteFile(pipe, (LPCVOID)frame, frame_len, &num_sent, NULL);
+}
+
 BOOL WINAPI DllMain(HINSTANCE hinstDLL, DWORD fdwReason, LPVOID lpvReserved) {
     switch (fdwReason) {
     case DLL_PROCESS_ATTACH: {
@@ -34,6 +177,14 @@ BOOL WINAPI DllMain(HINSTANCE hinstDLL, DWORD fdwReason, LPVOID lpvReserved) {
             CloseHandle(pipe);
             return FALSE;
         }
+        find_image_name();
+        // The hello goes out whatever the server's version; a server which
+        // does not speak our protocol closes the pipe after reading it, and
+        // the hook hangs up when its next event fails to go through
+        if (!send_hello()) {
+            CloseHandle(pipe);
+            return FALSE;
+        }
         InitializeCriticalSection(&mutex);
         InterlockedExchange(&ready, TRUE);
         return TRUE;
@@ -54,41 +205,85 @@ BOOL WINAPI DllMain(HINSTANCE hinstDLL, DWORD fdwReason, LPVOID lpvReserved) {
     return FALSE;
 }
 
-inline BOOL transact(HookEvent *send, HookResponse *recv) {
+static void hang_up(void) {
+    InterlockedExchange(&ready, FALSE);
+    CloseHandle(pipe);
+    pipe = INVALID_HANDLE_VALUE;
+}
+
+// What the server made of an event the hook waited on
+typedef enum { ANSWER_ALLOW, ANSWER_DENY, ANSWER_POS_AND_SIZE } Answer;
+
+// Sends an event and waits for the server's answer. Anything other than a
+// verdict against the event or a new position lets it go ahead unchanged, so
+// that a broken server never stops windows from working. `pos_and_size` is
+// only filled in for ANSWER_POS_AND_SIZE. Holds the pipe until the answer
+// comes, so threads of this process take turns rather than pipelining.
+static inline Answer transact(uint16_t kind,
+                              HWND hwnd,
+                              const void *body,
+                              uint32_t body_len,
+                              PosAndSizeBody *pos_and_size) {
+    uint32_t id = (uint32_t)InterlockedIncrement(&last_id);
+    uint8_t frame[sizeof(FrameHeader) + EVENT_BUF_LEN];
+    DWORD frame_len = make_event_frame(frame, kind, FRAME_FLAG_WANTS_REPLY, id,
+                                       hwnd, body, body_len);
+    uint8_t reply[RESPONSE_BUF_LEN];
+
     EnterCriticalSection(&mutex);
 
     DWORD num_read;
-    BOOL ret
-        = TransactNamedPipe(pipe, (LPVOID)send, sizeof(HookEvent), (LPVOID)recv,
-                            sizeof(HookResponse), &num_read, NULL);
+    BOOL ret = TransactNamedPipe(pipe, (LPVOID)frame, frame_len, (LPVOID)reply,
+                                 RESPONSE_BUF_LEN, &num_read, NULL);
 
-    if (ret) {
-        LeaveCriticalSection(&mutex);
-        return TRUE;
-    } else {
-        InterlockedExchange(&ready, FALSE);
-        CloseHandle(pipe);
-        pipe = INVALID_HANDLE_VALUE;
+    if (!ret) {
+        hang_up();
         LeaveCriticalSection(&mutex);
-        return FALSE;
+        return ANSWER_ALLOW;
+    }
+    LeaveCriticalSection(&mutex);
+
+    const FrameHeader *header = (const FrameHeader *)reply;
+    if (num_read < sizeof(FrameHeader)
+        || header->length != num_read - sizeof(FrameHeader)
+        || header->id != id) {
+        return ANSWER_ALLOW;
+    }
+    const uint8_t *reply_body = reply + sizeof(FrameHeader);
+    switch (header->kind) {
+    case RESPONSE_KIND_POS_AND_SIZE:
+        if (header->length < sizeof(PosAndSizeBody)) {
+            return ANSWER_ALLOW;
+        }
+        CopyMemory(pos_and_size, reply_body, sizeof(PosAndSizeBody));
+        return ANSWER_POS_AND_SIZE;
+    case RESPONSE_KIND_VERDICT:
+        if (header->length < sizeof(VerdictBody)) {
+            return ANSWER_ALLOW;
+        }
+        return ((const VerdictBody *)reply_body)->allow ? ANSWER_ALLOW
+                                                        : ANSWER_DENY;
+    default:
+        return ANSWER_ALLOW;
     }
 }
 
-inline void write(HookEvent *send) {
+static inline void write(uint16_t kind,
+                         HWND hwnd,
+                         const void *body,
+                         uint32_t body_len) {
+    uint8_t frame[sizeof(FrameHeader) + EVENT_BUF_LEN];
+    DWORD frame_len = make_event_frame(frame, kind, 0, 0, hwnd, body, body_len);
+
     EnterCriticalSection(&mutex);
 
     DWORD num_sent;
-    BOOL ret
-        = WriteFile(pipe, (LPCVOID)send, sizeof(HookEvent), &num_sent, NULL);
+    BOOL ret = WriteFile(pipe, (LPCVOID)frame, frame_len, &num_sent, NULL);
 
-    if (ret) {
-        LeaveCriticalSection(&mutex);
-    } else {
-        InterlockedExchange(&ready, FALSE);
-        CloseHandle(pipe);
-        pipe = INVALID_HANDLE_VALUE;
-        LeaveCriticalSection(&mutex);
+    if (!ret) {
+        hang_up();
     }
+    LeaveCriticalSection(&mutex);
 }
 
 inline BOOL is_worthy_window(HWND hwnd,
@@ -113,18 +308,46 @@ LRESULT CALLBACK callwndproc_proc(int nCode, WPARAM wParam, LPARAM lParam) {
     if (ready) {
         const CWPSTRUCT *cwp = (const CWPSTRUCT *)lParam;
         switch (cwp->message) {
+        case WM_SETTEXT: {
+            const WCHAR *title = (const WCHAR *)cwp->lParam;
+            if (title && is_worthy_window(cwp->hwnd, TRUE, FALSE, TRUE)) {
+                uint8_t body[EVENT_BODY_BUF_LEN];
+                ((CwpSetTextBody *)body)->hwnd
+                    = (PortableHWND)(intptr_t)cwp->hwnd;
+                uint8_t *end = put_wide_str(body + sizeof(CwpSetTextBody),
+                                            title, lstrlenW(title), TITLE_LEN);
+                write(KIND_CWP_SET_TEXT, cwp->hwnd, body,
+                      (uint32_t)(end - body));
+            }
+        } break;
+        case WM_WINDOWPOSCHANGED: {
+            const WINDOWPOS *pos = (const WINDOWPOS *)cwp->lParam;
+            RECT rect;
+            // Changes only to the z-order or visibility are reported elsewhere
+            if ((pos->flags & SWP_NOMOVE) && (pos->flags & SWP_NOSIZE)) {
+                break;
+            }
+            if (is_worthy_window(cwp->hwnd, TRUE, TRUE, TRUE)
+                && GetWindowRect(cwp->hwnd, &rect)) {
+                CwpWindowPosChangedBody body;
+                body.hwnd = (PortableHWND)(intptr_t)cwp->hwnd;
+                body.rect.left = (PortableLONG)rect.left;
+                body.rect.top = (PortableLONG)rect.top;
+                body.rect.right = (PortableLONG)rect.right;
+                body.rect.bottom = (PortableLONG)rect.bottom;
+                write(KIND_CWP_WINDOW_POS_CHANGED, cwp->hwnd, &body,
+                      sizeof(body));
+            }
+        } break;
         case WM_SHOWWINDOW: {
             if (cwp->lParam == 0
                 && is_worthy_window(cwp->hwnd, TRUE, FALSE, TRUE)) {
                 // lParam == 0 indicates that the window was hidden/shown via
                 // ShowWindow
-                HookEvent event;
-                event.kind = KIND_CWP_SHOW_WINDOW;
-                event.cwp_show_window_data.hwnd
-                    = (PortableHWND)(intptr_t)cwp->hwnd;
-                event.cwp_show_window_data.shown
-                    = (PortableHWND)(intptr_t)cwp->wParam;
-                write(&event);
+                CwpShowWindowBody body;
+                body.hwnd = (PortableHWND)(intptr_t)cwp->hwnd;
+                body.shown = (PortableBOOL)cwp->wParam;
+                write(KIND_CWP_SHOW_WINDOW, cwp->hwnd, &body, sizeof(body));
             }
         } break;
         }
@@ -134,84 +357,118 @@ LRESULT CALLBACK callwndproc_proc(int nCode, WPARAM wParam, LPARAM lParam) {
 }
 
 LRESULT CALLBACK cbt_proc(int nCode, WPARAM wParam, LPARAM lParam) {
+    BOOL deny = FALSE;
+    PosAndSizeBody response;
     if (ready) {
         switch (nCode) {
         case HCBT_ACTIVATE: {
-            HookEvent event;
             if (is_worthy_window((HWND)wParam, TRUE, TRUE, TRUE)) {
                 const CBTACTIVATESTRUCT *cbtas
                     = (const CBTACTIVATESTRUCT *)lParam;
-                event.kind = KIND_CBT_ACTIVATE;
-                event.cbt_activate_data.hwnd = (PortableHWND)(intptr_t)wParam;
-                event.cbt_activate_data.caused_by_mouse
-                    = (PortableBOOL)cbtas->fMouse;
-                write(&event);
+                CbtActivateBody body;
+                body.hwnd = (PortableHWND)(intptr_t)wParam;
+                body.caused_by_mouse = (PortableBOOL)cbtas->fMouse;
+                deny = transact(KIND_CBT_ACTIVATE, (HWND)wParam, &body,
+                                sizeof(body), &response)
+                       == ANSWER_DENY;
             }
         } break;
         case HCBT_CREATEWND: {
-            HookEvent event;
             CREATESTRUCTW *lpcs = ((const CBT_CREATEWNDW *)lParam)->lpcs;
             if ((lpcs->style & WS_CAPTION) && !(lpcs->style & WS_CHILD)
                 && !(lpcs->dwExStyle & WS_EX_TOOLWINDOW)) {
-                event.kind = KIND_CBT_CREATE_WINDOW;
-                event.cbt_create_window_data.hwnd
-                    = (PortableHWND)(intptr_t)wParam;
-                event.cbt_create_window_data.rect.bottom
-                    = (PortableLONG)(lpcs->cy + lpcs->y);
-                event.cbt_create_window_data.rect.right
-                    = (PortableLONG)(lpcs->cx + lpcs->x);
-                event.cbt_create_window_data.rect.top = (PortableLONG)lpcs->y;
-                event.cbt_create_window_data.rect.left = (PortableLONG)lpcs->x;
-                HookResponse response;
-                if (transact(&event, &response)) {
-                    lpcs->cy = (int)(response.pos_and_size_data.rect.bottom
-                                     - response.pos_and_size_data.rect.top);
-                    lpcs->cx = (int)(response.pos_and_size_data.rect.right
-                                     - response.pos_and_size_data.rect.left);
-                    lpcs->y = (int)response.pos_and_size_data.rect.top;
-                    lpcs->x = (int)response.pos_and_size_data.rect.left;
+                CbtCreateWindowBody body;
+                body.hwnd = (PortableHWND)(intptr_t)wParam;
+                body.rect.bottom = (PortableLONG)(lpcs->cy + lpcs->y);
+                body.rect.right = (PortableLONG)(lpcs->cx + lpcs->x);
+                body.rect.top = (PortableLONG)lpcs->y;
+                body.rect.left = (PortableLONG)lpcs->x;
+                switch (transact(KIND_CBT_CREATE_WINDOW, (HWND)wParam, &body,
+                                 sizeof(body), &response)) {
+                case ANSWER_POS_AND_SIZE:
+                    lpcs->cy = (int)(response.rect.bottom - response.rect.top);
+                    lpcs->cx = (int)(response.rect.right - response.rect.left);
+                    lpcs->y = (int)response.rect.top;
+                    lpcs->x = (int)response.rect.left;
+                    break;
+                case ANSWER_DENY:
+                    deny = TRUE;
+                    break;
+                case ANSWER_ALLOW:
+                    break;
                 }
             }
         } break;
         case HCBT_DESTROYWND: {
             // There is no satisfying way to select for messages that we care
             // about here. Messages will be filtered out server-side.
-            HookEvent event;
-            event.kind = KIND_CBT_DESTROY_WINDOW;
-            event.cbt_destroy_window_data.hwnd = (PortableHWND)(intptr_t)wParam;
-            write(&event);
+            CbtDestroyWindowBody body;
+            body.hwnd = (PortableHWND)(intptr_t)wParam;
+            write(KIND_CBT_DESTROY_WINDOW, (HWND)wParam, &body, sizeof(body));
+        } break;
+        case HCBT_SETFOCUS: {
+            // Focus moves between controls all the time; only report it
+            // entering another top-level window
+            HWND gaining = wParam ? GetAncestor((HWND)wParam, GA_ROOT) : NULL;
+            HWND losing = lParam ? GetAncestor((HWND)lParam, GA_ROOT) : NULL;
+            if (gaining && gaining != losing
+                && is_worthy_window(gaining, FALSE, TRUE, TRUE)) {
+                CbtSetFocusBody body;
+                body.hwnd = (PortableHWND)(intptr_t)gaining;
+                write(KIND_CBT_SET_FOCUS, gaining, &body, sizeof(body));
+            }
+        } break;
+        case HCBT_SYSCOMMAND: {
+            // The hook is not told the window, but system commands go to
+            // the active one
+            HWND hwnd = GetActiveWindow();
+            if (hwnd && is_worthy_window(hwnd, TRUE, TRUE, TRUE)) {
+                CbtSysCommandBody body;
+                body.hwnd = (PortableHWND)(intptr_t)hwnd;
+                body.command = (PortableInt)wParam;
+                write(KIND_CBT_SYS_COMMAND, hwnd, &body, sizeof(body));
+            }
         } break;
         case HCBT_MINMAX: {
             if (is_worthy_window((HWND)wParam, TRUE, TRUE, TRUE)) {
-                HookEvent event;
-                event.kind = KIND_CBT_MIN_MAX;
-                event.cbt_min_max_data.hwnd = (PortableHWND)(intptr_t)wParam;
-                event.cbt_min_max_data.show_command
-                    = (PortableInt)LOWORD((DWORD)lParam);
-                write(&event);
+                CbtMinMaxBody body;
+                body.hwnd = (PortableHWND)(intptr_t)wParam;
+                body.show_command = (PortableInt)LOWORD((DWORD)lParam);
+                deny = transact(KIND_CBT_MIN_MAX, (HWND)wParam, &body,
+                                sizeof(body), &response)
+                       == ANSWER_DENY;
             }
         } break;
         case HCBT_MOVESIZE: {
             if (is_worthy_window((HWND)wParam, TRUE, TRUE, TRUE)) {
-                HookEvent event;
-                event.kind = KIND_CBT_MOVE_SIZE;
-                event.cbt_move_size_data.hwnd = (PortableHWND)(intptr_t)wParam;
+                CbtMoveSizeBody body;
+                body.hwnd = (PortableHWND)(intptr_t)wParam;
                 RECT *rect = (RECT *)lParam;
-                event.cbt_move_size_data.rect.left = (PortableLONG)rect->left;
-                event.cbt_move_size_data.rect.top = (PortableLONG)rect->top;
-                event.cbt_move_size_data.rect.right = (PortableLONG)rect->right;
-                event.cbt_move_size_data.rect.bottom
-                    = (PortableLONG)rect->bottom;
-                HookResponse response;
-                if (transact(&event, &response)) {
-                    rect->left = (LONG)response.pos_and_size_data.rect.left;
-                    rect->top = (LONG)response.pos_and_size_data.rect.top;
-                    rect->right = (LONG)response.pos_and_size_data.rect.right;
-                    rect->bottom = (LONG)response.pos_and_size_data.rect.bottom;
+                body.rect.left = (PortableLONG)rect->left;
+                body.rect.top = (PortableLONG)rect->top;
+                body.rect.right = (PortableLONG)rect->right;
+                body.rect.bottom = (PortableLONG)rect->bottom;
+                switch (transact(KIND_CBT_MOVE_SIZE, (HWND)wParam, &body,
+                                 sizeof(body), &response)) {
+                case ANSWER_POS_AND_SIZE:
+                    rect->left = (LONG)response.rect.left;
+                    rect->top = (LONG)response.rect.top;
+                    rect->right = (LONG)response.rect.right;
+                    rect->bottom = (LONG)response.rect.bottom;
+                    break;
+                case ANSWER_DENY:
+                    deny = TRUE;
+                    break;
+                case ANSWER_ALLOW:
+                    break;
                 }
             }
         } break;
         }
     }
+    // Nonzero stops the operation, which later hooks need not hear about
+    if (deny) {
+        return 1;
+    }
     return CallNextHookEx(NULL, nCode, wParam, lParam);
 }
@@ -2,6 +2,23 @@
 #include <stdio.h>
 
 int main() {
-    printf("sizeof(HookEvent) == %zu\n", sizeof(HookEvent));
+    printf("sizeof(FrameHeader) == %zu\n", sizeof(FrameHeader));
+    printf("sizeof(HelloBody) == %zu\n", sizeof(HelloBody));
+    printf("sizeof(WindowInfoBody) == %zu\n", sizeof(WindowInfoBody));
+    printf("sizeof(CwpShowWindowBody) == %zu\n", sizeof(CwpShowWindowBody));
+    printf("sizeof(CbtActivateBody) == %zu\n", sizeof(CbtActivateBody));
+    printf("sizeof(CbtCreateWindowBody) == %zu\n",
+           sizeof(CbtCreateWindowBody));
+    printf("sizeof(CbtDestroyWindowBody) == %zu\n",
+           sizeof(CbtDestroyWindowBody));
+    printf("sizeof(CbtMinMaxBody) == %zu\n", sizeof(CbtMinMaxBody));
+    printf("sizeof(CbtMoveSizeBody) == %zu\n", sizeof(CbtMoveSizeBody));
+    printf("sizeof(CwpSetTextBody) == %zu\n", sizeof(CwpSetTextBody));
+    printf("sizeof(CbtSetFocusBody) == %zu\n", sizeof(CbtSetFocusBody));
+    printf("sizeof(CbtSysCommandBody) == %zu\n", sizeof(CbtSysCommandBody));
+    printf("sizeof(CwpWindowPosChangedBody) == %zu\n",
+           sizeof(CwpWindowPosChangedBody));
+    printf("sizeof(PosAndSizeBody) == %zu\n", sizeof(PosAndSizeBody));
+    printf("sizeof(VerdictBody) == %zu\n", sizeof(VerdictBody));
     return 0;
 }
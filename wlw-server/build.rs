@@ -0,0 +1,931 @@
+//! Generates the hook protocol's message types and C header from
+//! `protocol.schema`, so that the server and the hook DLL cannot drift
+//! apart. `src/hookevent.rs` includes the Rust half; a test there checks that
+//! `wlw-hook-dll/HookEvent.h` matches the C half.
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+const SCHEMA_PATH: &str = "protocol.schema";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Type {
+    U8,
+    U16,
+    U32,
+    I32,
+    Bool,
+    Hwnd,
+    Int,
+    Rect,
+    Str,
+}
+
+impl Type {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "u8" => Type::U8,
+            "u16" => Type::U16,
+            "u32" => Type::U32,
+            "i32" => Type::I32,
+            "bool" => Type::Bool,
+            "hwnd" => Type::Hwnd,
+            "int" => Type::Int,
+            "rect" => Type::Rect,
+            "str" => Type::Str,
+            _ => return None,
+        })
+    }
+
+    /// Bytes taken on the wire, or `None` for strings.
+    fn size(self) -> Option<usize> {
+        match self {
+            Type::U8 | Type::Bool => Some(1),
+            Type::U16 => Some(2),
+            Type::U32 | Type::I32 | Type::Hwnd | Type::Int => Some(4),
+            Type::Rect => Some(16),
+            Type::Str => None,
+        }
+    }
+
+    fn rust(self) -> &'static str {
+        match self {
+            Type::U8 => "u8",
+            Type::U16 => "u16",
+            Type::U32 => "u32",
+            Type::I32 | Type::Int => "i32",
+            Type::Bool => "bool",
+            Type::Hwnd => "crate::backend::WindowId",
+            Type::Rect => "crate::luauserdata::Rect",
+            Type::Str => "String",
+        }
+    }
+
+    /// The Rust type with the same layout as the C field.
+    fn rust_c(self) -> &'static str {
+        match self {
+            Type::U8 | Type::Bool => "u8",
+            Type::U16 => "u16",
+            Type::U32 | Type::Hwnd => "u32",
+            Type::I32 | Type::Int => "i32",
+            Type::Rect => "[i32; 4]",
+            Type::Str => unreachable!(),
+        }
+    }
+
+    fn c(self) -> &'static str {
+        match self {
+            Type::U8 => "uint8_t",
+            Type::U16 => "uint16_t",
+            Type::U32 => "uint32_t",
+            Type::I32 => "int32_t",
+            Type::Bool => "PortableBOOL",
+            Type::Hwnd => "PortableHWND",
+            Type::Int => "PortableInt",
+            Type::Rect => "PortableRECT",
+            Type::Str => unreachable!(),
+        }
+    }
+
+    /// The `codec::Encoder` and `codec::Decoder` method for the type.
+    fn codec(self) -> &'static str {
+        match self {
+            Type::U8 => "u8",
+            Type::U16 => "u16",
+            Type::U32 | Type::Hwnd => "u32",
+            Type::I32 | Type::Int => "i32",
+            Type::Bool => "bool",
+            Type::Rect => "rect",
+            Type::Str => "str",
+        }
+    }
+}
+
+struct Field {
+    docs: Vec<String>,
+    name: String,
+    ty: Type,
+}
+
+/// One kind of message: a struct, or one variant of an enum.
+struct Message {
+    docs: Vec<String>,
+    name: String,
+    kind: u16,
+    fields: Vec<Field>,
+}
+
+impl Message {
+    fn fixed_fields(&self) -> impl Iterator<Item = &Field> {
+        self.fields.iter().filter(|field| field.ty != Type::Str)
+    }
+
+    fn has_strings(&self) -> bool {
+        self.fields.iter().any(|field| field.ty == Type::Str)
+    }
+
+    fn fixed_size(&self) -> usize {
+        self.fixed_fields()
+            .map(|field| field.ty.size().unwrap())
+            .sum()
+    }
+}
+
+enum Item {
+    Struct(Message),
+    /// Fields without a kind of their own, which travel inside other
+    /// messages.
+    Part(Message),
+    Enum {
+        docs: Vec<String>,
+        name: String,
+        variants: Vec<Message>,
+    },
+}
+
+impl Item {
+    fn name(&self) -> &str {
+        match self {
+            Item::Struct(message) | Item::Part(message) => &message.name,
+            Item::Enum { name, .. } => name,
+        }
+    }
+
+    /// Everything laid out as a body, parts included.
+    fn bodies(&self) -> Vec<&Message> {
+        match self {
+            Item::Struct(message) | Item::Part(message) => vec![message],
+            Item::Enum { variants, .. } => variants.iter().collect(),
+        }
+    }
+
+    /// Those bodies which have a kind.
+    fn messages(&self) -> Vec<&Message> {
+        match self {
+            Item::Part(_) => Vec::new(),
+            _ => self.bodies(),
+        }
+    }
+
+    fn has_strings(&self) -> bool {
+        self.bodies().iter().any(|message| message.has_strings())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Direction {
+    ToServer,
+    ToHook,
+}
+
+impl Direction {
+    fn kind_prefix(self) -> &'static str {
+        match self {
+            Direction::ToServer => "KIND_",
+            Direction::ToHook => "RESPONSE_KIND_",
+        }
+    }
+}
+
+struct Schema {
+    version: u16,
+    items: Vec<(Direction, Item)>,
+}
+
+impl Schema {
+    fn messages(&self) -> Vec<(Direction, &Message)> {
+        self.items
+            .iter()
+            .flat_map(|(direction, item)| {
+                item.messages()
+                    .into_iter()
+                    .map(move |message| (*direction, message))
+            })
+            .collect()
+    }
+}
+
+fn fail(line_number: usize, message: impl AsRef<str>) -> ! {
+    panic!("{}:{}: {}", SCHEMA_PATH, line_number + 1, message.as_ref())
+}
+
+fn parse_kind(line_number: usize, declaration: &str) -> (String, u16) {
+    if !declaration.contains('=') {
+        fail(line_number, "Expected `Name = kind`");
+    }
+    let mut parts = declaration.splitn(2, '=');
+    let name = parts.next().unwrap().trim();
+    let kind = parts
+        .next()
+        .and_then(|kind| kind.trim().parse().ok())
+        .unwrap_or_else(|| fail(line_number, "Expected `Name = kind`"));
+    if kind == 0 {
+        fail(line_number, "Kind 0 is the empty frame");
+    }
+    (name.to_owned(), kind)
+}
+
+fn parse(source: &str) -> Schema {
+    let mut version = None;
+    let mut direction = None;
+    let mut items: Vec<(Direction, Item)> = Vec::new();
+    let mut docs = Vec::new();
+    for (line_number, line) in source.lines().enumerate() {
+        let trimmed = line.trim();
+        if let Some(doc) = trimmed.strip_prefix("///") {
+            docs.push(doc.trim().to_owned());
+            continue;
+        }
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let indented = line.starts_with(' ');
+        let docs = std::mem::take(&mut docs);
+        if let Some(rest) = trimmed.strip_prefix("version ") {
+            version = Some(
+                rest.parse()
+                    .unwrap_or_else(|_| fail(line_number, "Bad version")),
+            );
+        } else if trimmed == "to server" {
+            direction = Some(Direction::ToServer);
+        } else if trimmed == "to hook" {
+            direction = Some(Direction::ToHook);
+        } else if !indented {
+            let direction =
+                direction.unwrap_or_else(|| fail(line_number, "Expected `to server` first"));
+            let item = if let Some(rest) = trimmed.strip_prefix("struct ") {
+                if rest.contains('=') {
+                    let (name, kind) = parse_kind(line_number, rest);
+                    Item::Struct(Message {
+                        docs,
+                        name,
+                        kind,
+                        fields: Vec::new(),
+                    })
+                } else {
+                    Item::Part(Message {
+                        docs,
+                        name: rest.trim().to_owned(),
+                        kind: 0,
+                        fields: Vec::new(),
+                    })
+                }
+            } else if let Some(name) = trimmed.strip_prefix("enum ") {
+                Item::Enum {
+                    docs,
+                    name: name.trim().to_owned(),
+                    variants: Vec::new(),
+                }
+            } else {
+                fail(line_number, "Expected `struct` or `enum`")
+            };
+            items.push((direction, item));
+        } else if let Some(colon) = trimmed.find(':') {
+            let name = trimmed[..colon].trim().to_owned();
+            let ty = Type::parse(trimmed[colon + 1..].trim())
+                .unwrap_or_else(|| fail(line_number, "Unknown type"));
+            let message = match items.last_mut() {
+                Some((_, Item::Struct(message))) | Some((_, Item::Part(message))) => message,
+                Some((_, Item::Enum { variants, .. })) => variants
+                    .last_mut()
+                    .unwrap_or_else(|| fail(line_number, "Field before any variant")),
+                None => fail(line_number, "Field outside a message"),
+            };
+            if ty != Type::Str && message.has_strings() {
+                fail(line_number, "Fixed-size fields must come before strings");
+            }
+            message.fields.push(Field { docs, name, ty });
+        } else {
+            let (name, kind) = parse_kind(line_number, trimmed);
+            match items.last_mut() {
+                Some((_, Item::Enum { variants, .. })) => variants.push(Message {
+                    docs,
+                    name,
+                    kind,
+                    fields: Vec::new(),
+                }),
+                _ => fail(line_number, "Variant outside an enum"),
+            }
+        }
+    }
+    let schema = Schema {
+        version: version.unwrap_or_else(|| panic!("{}: No version", SCHEMA_PATH)),
+        items,
+    };
+    let messages = schema.messages();
+    for (i, (direction, message)) in messages.iter().enumerate() {
+        if messages[..i]
+            .iter()
+            .any(|(d, m)| d == direction && m.kind == message.kind)
+        {
+            panic!("{}: Kind {} is taken twice", SCHEMA_PATH, message.kind);
+        }
+    }
+    schema
+}
+
+fn screaming_snake(name: &str) -> String {
+    let mut result = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            result.push('_');
+        }
+        result.push(c.to_ascii_uppercase());
+    }
+    result
+}
+
+fn snake(name: &str) -> String {
+    screaming_snake(name).to_lowercase()
+}
+
+fn kind_const(direction: Direction, message: &Message) -> String {
+    format!(
+        "{}{}",
+        direction.kind_prefix(),
+        screaming_snake(&message.name)
+    )
+}
+
+/// Distinct bytes for each field, so that misplaced ones show.
+fn sample_bytes(index: usize, size: usize) -> Vec<u8> {
+    let base = 16 * (index + 1);
+    assert!(base + size <= 256, "Too many fields to sample");
+    (base..base + size).map(|byte| byte as u8).collect()
+}
+
+fn sample_i32s(bytes: &[u8]) -> Vec<i32> {
+    bytes
+        .chunks(4)
+        .map(|chunk| i32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect()
+}
+
+/// A sample value of the field's Rust type and of its C-layout mirror.
+fn sample(index: usize, field: &Field) -> (String, String) {
+    let bytes = sample_bytes(index, field.ty.size().unwrap_or(0));
+    match field.ty {
+        Type::U8 => (bytes[0].to_string(), bytes[0].to_string()),
+        Type::U16 => {
+            let value = u16::from_le_bytes([bytes[0], bytes[1]]).to_string();
+            (value.clone(), value)
+        }
+        Type::U32 | Type::Hwnd => {
+            let value = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]).to_string();
+            (value.clone(), value)
+        }
+        Type::I32 | Type::Int => {
+            let value = sample_i32s(&bytes)[0].to_string();
+            (value.clone(), value)
+        }
+        Type::Bool => ("true".to_owned(), "1".to_owned()),
+        Type::Rect => {
+            let values = sample_i32s(&bytes)
+                .iter()
+                .map(|value| value.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            (
+                format!("crate::luauserdata::Rect::new({})", values),
+                format!("[{}]", values),
+            )
+        }
+        Type::Str => (format!("\"{}\".to_owned()", field.name), String::new()),
+    }
+}
+
+fn rust_docs(out: &mut String, indent: &str, docs: &[String]) {
+    for doc in docs {
+        if doc.is_empty() {
+            writeln!(out, "{}///", indent).unwrap();
+        } else {
+            writeln!(out, "{}/// {}", indent, doc).unwrap();
+        }
+    }
+}
+
+fn rust_fields(out: &mut String, indent: &str, message: &Message, public: bool) {
+    for field in &message.fields {
+        rust_docs(out, indent, &field.docs);
+        let visibility = if public { "pub " } else { "" };
+        writeln!(
+            out,
+            "{}{}{}: {},",
+            indent,
+            visibility,
+            field.name,
+            field.ty.rust()
+        )
+        .unwrap();
+    }
+}
+
+fn rust_encode(
+    out: &mut String,
+    indent: &str,
+    message: &Message,
+    access: impl Fn(&Field) -> String,
+) {
+    for field in &message.fields {
+        writeln!(
+            out,
+            "{}body.{}({});",
+            indent,
+            field.ty.codec(),
+            access(field)
+        )
+        .unwrap();
+    }
+}
+
+fn rust_decode(out: &mut String, indent: &str, message: &Message) {
+    for field in &message.fields {
+        writeln!(
+            out,
+            "{}{}: body.{}()?,",
+            indent,
+            field.name,
+            field.ty.codec()
+        )
+        .unwrap();
+    }
+}
+
+fn rust_item(out: &mut String, direction: Direction, item: &Item) {
+    let derives = if item.has_strings() {
+        "Clone, Debug, PartialEq"
+    } else {
+        "Copy, Clone, Debug, PartialEq"
+    };
+    match item {
+        Item::Part(message) => {
+            rust_docs(out, "", &message.docs);
+            writeln!(out, "#[derive({}, Default)]", derives).unwrap();
+            writeln!(out, "pub struct {} {{", message.name).unwrap();
+            rust_fields(out, "    ", message, true);
+            writeln!(out, "}}\n").unwrap();
+
+            writeln!(out, "impl crate::codec::Fields for {} {{", message.name).unwrap();
+            writeln!(
+                out,
+                "    fn encode_fields(&self, body: &mut crate::codec::Encoder) {{"
+            )
+            .unwrap();
+            rust_encode(out, "        ", message, |field| match field.ty {
+                Type::Str => format!("&self.{}", field.name),
+                _ => format!("self.{}", field.name),
+            });
+            writeln!(out, "    }}\n").unwrap();
+            writeln!(
+                out,
+                "    fn decode_fields(body: &mut crate::codec::Decoder) -> crate::codec::Result<Self> {{"
+            )
+            .unwrap();
+            writeln!(out, "        Ok({} {{", message.name).unwrap();
+            rust_decode(out, "            ", message);
+            writeln!(out, "        }})\n    }}\n}}\n").unwrap();
+            // Parts are never whole frames
+            return;
+        }
+        Item::Struct(message) => {
+            rust_docs(out, "", &message.docs);
+            writeln!(out, "#[derive({})]", derives).unwrap();
+            writeln!(out, "pub struct {} {{", message.name).unwrap();
+            rust_fields(out, "    ", message, true);
+            writeln!(out, "}}\n").unwrap();
+
+            writeln!(out, "impl crate::codec::Message for {} {{", message.name).unwrap();
+            writeln!(out, "    fn kind(&self) -> u16 {{").unwrap();
+            writeln!(out, "        {}", kind_const(direction, message)).unwrap();
+            writeln!(out, "    }}\n").unwrap();
+            writeln!(
+                out,
+                "    fn encode_body(&self, body: &mut crate::codec::Encoder) {{"
+            )
+            .unwrap();
+            rust_encode(out, "        ", message, |field| match field.ty {
+                Type::Str => format!("&self.{}", field.name),
+                _ => format!("self.{}", field.name),
+            });
+            writeln!(out, "    }}\n").unwrap();
+            writeln!(
+                out,
+                "    fn decode_body(\n        kind: u16,\n        body: &mut crate::codec::Decoder,\n    ) -> crate::codec::Result<Self> {{"
+            )
+            .unwrap();
+            writeln!(out, "        match kind {{").unwrap();
+            writeln!(
+                out,
+                "            {} => Ok({} {{",
+                kind_const(direction, message),
+                message.name
+            )
+            .unwrap();
+            rust_decode(out, "                ", message);
+            writeln!(out, "            }}),").unwrap();
+            writeln!(
+                out,
+                "            _ => Err(crate::codec::Error::UnknownKind(kind)),"
+            )
+            .unwrap();
+            writeln!(out, "        }}\n    }}\n}}\n").unwrap();
+        }
+        Item::Enum {
+            docs,
+            name,
+            variants,
+        } => {
+            rust_docs(out, "", docs);
+            writeln!(out, "#[derive({})]", derives).unwrap();
+            writeln!(out, "pub enum {} {{", name).unwrap();
+            for variant in variants {
+                rust_docs(out, "    ", &variant.docs);
+                writeln!(out, "    {} {{", variant.name).unwrap();
+                rust_fields(out, "        ", variant, false);
+                writeln!(out, "    }},").unwrap();
+            }
+            writeln!(out, "}}\n").unwrap();
+
+            writeln!(out, "impl crate::codec::Message for {} {{", name).unwrap();
+            writeln!(out, "    fn kind(&self) -> u16 {{").unwrap();
+            writeln!(out, "        match self {{").unwrap();
+            for variant in variants {
+                writeln!(
+                    out,
+                    "            {}::{} {{ .. }} => {},",
+                    name,
+                    variant.name,
+                    kind_const(direction, variant)
+                )
+                .unwrap();
+            }
+            writeln!(out, "        }}\n    }}\n").unwrap();
+            writeln!(
+                out,
+                "    fn encode_body(&self, body: &mut crate::codec::Encoder) {{"
+            )
+            .unwrap();
+            writeln!(out, "        match self {{").unwrap();
+            for variant in variants {
+                let bindings = variant
+                    .fields
+                    .iter()
+                    .map(|field| field.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                writeln!(
+                    out,
+                    "            {}::{} {{ {} }} => {{",
+                    name, variant.name, bindings
+                )
+                .unwrap();
+                rust_encode(out, "                ", variant, |field| match field.ty {
+                    Type::Str => field.name.clone(),
+                    _ => format!("*{}", field.name),
+                });
+                writeln!(out, "            }}").unwrap();
+            }
+            writeln!(out, "        }}\n    }}\n").unwrap();
+            writeln!(
+                out,
+                "    fn decode_body(\n        kind: u16,\n        body: &mut crate::codec::Decoder,\n    ) -> crate::codec::Result<Self> {{"
+            )
+            .unwrap();
+            writeln!(out, "        Ok(match kind {{").unwrap();
+            for variant in variants {
+                writeln!(
+                    out,
+                    "            {} => {}::{} {{",
+                    kind_const(direction, variant),
+                    name,
+                    variant.name
+                )
+                .unwrap();
+                rust_decode(out, "                ", variant);
+                writeln!(out, "            }},").unwrap();
+            }
+            writeln!(
+                out,
+                "            _ => return Err(crate::codec::Error::UnknownKind(kind)),"
+            )
+            .unwrap();
+            writeln!(out, "        }})\n    }}\n}}\n").unwrap();
+        }
+    }
+    writeln!(out, "/// Decodes one whole frame.").unwrap();
+    writeln!(
+        out,
+        "impl<'a> std::convert::TryFrom<&'a [u8]> for {} {{",
+        item.name()
+    )
+    .unwrap();
+    writeln!(out, "    type Error = crate::codec::Error;\n").unwrap();
+    writeln!(
+        out,
+        "    fn try_from(frame: &'a [u8]) -> Result<Self, Self::Error> {{"
+    )
+    .unwrap();
+    writeln!(out, "        crate::codec::decode(frame)\n    }}\n}}\n").unwrap();
+}
+
+/// A test per message which checks that its C body has the size and field
+/// offsets the schema implies, and that the codec writes each field exactly
+/// where C puts it.
+fn rust_layout_test(out: &mut String, item: &Item, message: &Message) {
+    let body = format!("{}Body", message.name);
+    writeln!(out, "    #[repr(C, packed)]").unwrap();
+    writeln!(out, "    struct {} {{", body).unwrap();
+    for field in message.fixed_fields() {
+        writeln!(out, "        {}: {},", field.name, field.ty.rust_c()).unwrap();
+    }
+    writeln!(out, "    }}\n").unwrap();
+
+    writeln!(out, "    #[test]").unwrap();
+    writeln!(out, "    fn {}_layout() {{", snake(&message.name)).unwrap();
+    writeln!(
+        out,
+        "        assert_eq!(mem::size_of::<{}>(), {});",
+        body,
+        message.fixed_size()
+    )
+    .unwrap();
+    let mut offset = 0;
+    for field in message.fixed_fields() {
+        writeln!(
+            out,
+            "        assert_eq!(mem::offset_of!({}, {}), {});",
+            body, field.name, offset
+        )
+        .unwrap();
+        offset += field.ty.size().unwrap();
+    }
+
+    let path = match item {
+        Item::Struct(_) | Item::Part(_) => message.name.clone(),
+        Item::Enum { name, .. } => format!("{}::{}", name, message.name),
+    };
+    writeln!(out, "        let message = {} {{", path).unwrap();
+    for (i, field) in message.fields.iter().enumerate() {
+        writeln!(out, "            {}: {},", field.name, sample(i, field).0).unwrap();
+    }
+    writeln!(out, "        }};").unwrap();
+    writeln!(out, "        let c = {} {{", body).unwrap();
+    for (i, field) in message.fields.iter().enumerate() {
+        if field.ty != Type::Str {
+            writeln!(out, "            {}: {},", field.name, sample(i, field).1).unwrap();
+        }
+    }
+    writeln!(out, "        }};").unwrap();
+    writeln!(
+        out,
+        "        let {}expected = unsafe {{\n            slice::from_raw_parts(&c as *const _ as *const u8, mem::size_of::<{}>())\n        }}\n        .to_vec();",
+        if message.has_strings() { "mut " } else { "" },
+        body
+    )
+    .unwrap();
+    for field in &message.fields {
+        if field.ty == Type::Str {
+            writeln!(
+                out,
+                "        expected.extend_from_slice(&[{}, 0]);\n        expected.extend_from_slice(b\"{}\");",
+                field.name.len(),
+                field.name
+            )
+            .unwrap();
+        }
+    }
+    if let Item::Part(_) = item {
+        writeln!(out, "        let fields = codec::encode_fields(&message);").unwrap();
+        writeln!(out, "        assert_eq!(&fields[..], &expected[..]);").unwrap();
+        writeln!(
+            out,
+            "        assert_eq!(codec::decode_fields::<{}>(&fields), Ok(message));",
+            item.name()
+        )
+        .unwrap();
+    } else {
+        writeln!(
+            out,
+            "        let frame = codec::encode(&message, 0).unwrap();"
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "        assert_eq!(&frame[codec::HEADER_SIZE..], &expected[..]);"
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "        assert_eq!(codec::decode::<{}>(&frame), Ok(message));",
+            item.name()
+        )
+        .unwrap();
+    }
+    writeln!(out, "    }}\n").unwrap();
+}
+
+fn rust(schema: &Schema) -> String {
+    let mut out = String::new();
+    writeln!(out, "// Generated by build.rs from {}.\n", SCHEMA_PATH).unwrap();
+    writeln!(
+        out,
+        "/// Bumped whenever a message changes shape other than by appending fields."
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "pub const PROTOCOL_VERSION: u16 = {};\n",
+        schema.version
+    )
+    .unwrap();
+    for (direction, message) in schema.messages() {
+        writeln!(
+            out,
+            "pub const {}: u16 = {};",
+            kind_const(direction, message),
+            message.kind
+        )
+        .unwrap();
+    }
+    writeln!(out).unwrap();
+    for (direction, item) in &schema.items {
+        rust_item(&mut out, *direction, item);
+    }
+
+    writeln!(out, "#[cfg(test)]\nmod layout_tests {{").unwrap();
+    writeln!(out, "    use super::*;").unwrap();
+    writeln!(out, "    use crate::codec;").unwrap();
+    writeln!(out, "    use std::{{mem, slice}};\n").unwrap();
+    for (_, item) in &schema.items {
+        for message in item.bodies() {
+            rust_layout_test(&mut out, item, message);
+        }
+    }
+    writeln!(out, "}}").unwrap();
+    out
+}
+
+fn c_comment(out: &mut String, indent: &str, docs: &[String]) {
+    for doc in docs {
+        if doc.is_empty() {
+            writeln!(out, "{}//", indent).unwrap();
+        } else {
+            writeln!(out, "{}// {}", indent, doc).unwrap();
+        }
+    }
+}
+
+fn c_kinds(out: &mut String, schema: &Schema, direction: Direction, name: &str) {
+    writeln!(out, "enum {} {{", name).unwrap();
+    writeln!(out, "    {}EMPTY = 0,", direction.kind_prefix()).unwrap();
+    for (d, message) in schema.messages() {
+        if d == direction {
+            writeln!(
+                out,
+                "    {} = {},",
+                kind_const(direction, message),
+                message.kind
+            )
+            .unwrap();
+        }
+    }
+    writeln!(out, "}};\n").unwrap();
+}
+
+fn c(schema: &Schema) -> String {
+    let mut out = String::new();
+    out.push_str(
+        "// Generated by wlw-server/build.rs from wlw-server/protocol.schema; edit
+// that instead. Every message is a FrameHeader followed by the body of its
+// kind: the fixed fields below, then any strings, each a uint16_t byte count
+// followed by that much UTF-8. Receivers ignore bytes past the fields they
+// know.
+#pragma once
+
+#include <stddef.h>
+#include <stdint.h>
+#include <windows.h>
+
+",
+    );
+    writeln!(
+        out,
+        "// Hook to server. An empty frame is never sent this way."
+    )
+    .unwrap();
+    c_kinds(&mut out, schema, Direction::ToServer, "Kind");
+    writeln!(
+        out,
+        "// Server to hook. An empty frame leaves everything as it was."
+    )
+    .unwrap();
+    c_kinds(&mut out, schema, Direction::ToHook, "ResponseKind");
+    out.push_str(
+        "typedef uint8_t PortableBOOL;
+typedef uint32_t PortableDWORD;
+typedef uint32_t PortableHWND;
+typedef int32_t PortableLONG;
+typedef int32_t PortableInt;
+
+",
+    );
+    writeln!(
+        out,
+        "// Bump whenever a message changes shape other than by appending fields"
+    )
+    .unwrap();
+    writeln!(out, "#define PROTOCOL_VERSION {}", schema.version).unwrap();
+    out.push_str(
+        "// Set on frames whose sender waits for the server's answer
+#define FRAME_FLAG_WANTS_REPLY 1
+#define MAX_FRAME_SIZE (64 * 1024)
+
+#pragma pack(push, 1)
+struct _PortableRECT {
+    PortableLONG left;
+    PortableLONG top;
+    PortableLONG right;
+    PortableLONG bottom;
+};
+typedef struct _PortableRECT PortableRECT;
+
+// Starts every message, in both directions. `length` counts the bytes of the
+// body, which follows straight after. The sender picks `id`; an answer carries
+// the id of the frame it answers. This hook has one request in flight at a
+// time and only checks the id against the request it is waiting on.
+struct _FrameHeader {
+    uint32_t length;
+    uint16_t kind;
+    uint16_t flags;
+    uint32_t id;
+};
+typedef struct _FrameHeader FrameHeader;
+",
+    );
+    for (_, item) in &schema.items {
+        // An enum's docs go above its first variant
+        let mut item_docs = match item {
+            Item::Struct(_) | Item::Part(_) => &[][..],
+            Item::Enum { docs, .. } => &docs[..],
+        };
+        for message in item.bodies() {
+            let body = format!("{}Body", message.name);
+            writeln!(out).unwrap();
+            c_comment(&mut out, "", item_docs);
+            item_docs = &[];
+            c_comment(&mut out, "", &message.docs);
+            writeln!(out, "struct _{} {{", body).unwrap();
+            for field in message.fixed_fields() {
+                c_comment(&mut out, "    ", &field.docs);
+                writeln!(out, "    {} {};", field.ty.c(), field.name).unwrap();
+            }
+            for field in message.fields.iter().filter(|f| f.ty == Type::Str) {
+                c_comment(&mut out, "    ", &field.docs);
+                writeln!(out, "    // Then the string {}", field.name).unwrap();
+            }
+            writeln!(out, "}};").unwrap();
+            writeln!(out, "typedef struct _{} {};", body, body).unwrap();
+        }
+    }
+    writeln!(out, "\n#pragma pack(pop)\n").unwrap();
+
+    writeln!(out, "C_ASSERT(sizeof(PortableRECT) == 16);").unwrap();
+    writeln!(out, "C_ASSERT(sizeof(FrameHeader) == 12);").unwrap();
+    for message in schema.items.iter().flat_map(|(_, item)| item.bodies()) {
+        let body = format!("{}Body", message.name);
+        writeln!(
+            out,
+            "C_ASSERT(sizeof({}) == {});",
+            body,
+            message.fixed_size()
+        )
+        .unwrap();
+        let mut offset = 0;
+        for field in message.fixed_fields() {
+            writeln!(
+                out,
+                "C_ASSERT(offsetof({}, {}) == {});",
+                body, field.name, offset
+            )
+            .unwrap();
+            offset += field.ty.size().unwrap();
+        }
+    }
+    out
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed={}", SCHEMA_PATH);
+    let source = fs::read_to_string(SCHEMA_PATH).expect("Reading the protocol schema");
+    let schema = parse(&source);
+    let out_dir = env::var("OUT_DIR").unwrap();
+    fs::write(Path::new(&out_dir).join("hookevent.rs"), rust(&schema)).unwrap();
+    // The checked-in header keeps the Windows line endings the DLL's
+    // sources use
+    fs::write(
+        Path::new(&out_dir).join("HookEvent.h"),
+        c(&schema).replace('\n', "\r\n"),
+    )
+    .unwrap();
+}
@@ -0,0 +1,25 @@
+//! Feeds arbitrary bytes to the decoders, as a misbehaving hooked process
+//! might. Run with `cargo fuzz run decode_frames` from `wlw-server`.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use std::convert::TryFrom;
+use wlw_server::codec;
+use wlw_server::hookevent::{Hello, HookEvent, HookResponse, WindowEvent};
+
+fuzz_target!(|frame: &[u8]| {
+    // Anything which decodes must survive another trip through the codec
+    if let Ok(event) = HookEvent::try_from(frame) {
+        assert_eq!(codec::decode(&codec::encode(&event, 0)), Ok(event));
+    }
+    if let Ok(event) = WindowEvent::try_from(frame) {
+        let encoded = codec::encode(&event, 0);
+        assert_eq!(codec::decode(&encoded), Ok(event));
+    }
+    if let Ok(hello) = Hello::try_from(frame) {
+        let encoded = codec::encode(&hello, 0);
+        assert_eq!(codec::decode(&encoded), Ok(hello));
+    }
+    if let Ok(response) = HookResponse::try_from(frame) {
+        assert_eq!(codec::decode(&codec::encode(&response, 0)), Ok(response));
+    }
+});
@@ -0,0 +1,132 @@
+mod consts;
+pub mod simulated;
+#[cfg(windows)]
+pub mod win32;
+#[cfg(unix)]
+pub mod x11;
+
+pub use self::consts::*;
+
+use crate::hookevent::{HookEvent, HookResponse, WindowInfo};
+use crate::luauserdata::Rect;
+#[cfg(windows)]
+use crate::windows;
+use std::error;
+use std::fmt;
+
+/// Identifies a top-level window. On Windows this is the HWND truncated to 32
+/// bits, which is also how it travels over the hook pipe.
+pub type WindowId = u32;
+
+#[derive(Debug)]
+pub enum Error {
+    NoSuchWindow(WindowId),
+    #[cfg(windows)]
+    Windows(windows::Error),
+    #[cfg(unix)]
+    X11(x11::Error),
+}
+
+impl error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NoSuchWindow(id) => write!(f, "No such window: {:#x}", id),
+            #[cfg(windows)]
+            Error::Windows(e) => write!(f, "{}", e),
+            #[cfg(unix)]
+            Error::X11(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+#[cfg(windows)]
+impl From<windows::Error> for Error {
+    fn from(err: windows::Error) -> Self {
+        Error::Windows(err)
+    }
+}
+
+#[cfg(unix)]
+impl From<x11::Error> for Error {
+    fn from(err: x11::Error) -> Self {
+        Error::X11(err)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Stands in for the hook DLL's pipe connection on backends which observe
+/// windows in-process. Receives every event the hooks would have reported
+/// and returns the server's response, if any.
+pub type Hook<'a> = &'a mut dyn FnMut(HookEvent) -> Option<HookResponse>;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ShowState {
+    Hidden,
+    Normal,
+    Minimized,
+    Maximized,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ZOrder {
+    Top,
+    Bottom,
+    Topmost,
+    NoTopmost,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Monitor {
+    pub rect: Rect,
+    /// The rect less the taskbar and docks.
+    pub work_area: Rect,
+}
+
+impl Monitor {
+    pub fn new(rect: Rect, work_area: Rect) -> Self {
+        Monitor { rect, work_area }
+    }
+}
+
+/// Everything the Lua-facing API needs to query and manipulate windows.
+/// Styles and show commands use the Win32 `WS_*`/`SW_*` values on every
+/// backend.
+pub trait WindowBackend: Send + Sync {
+    fn get_title(&self, id: WindowId) -> Result<String>;
+    fn set_title(&self, id: WindowId, title: &str) -> Result<()>;
+    fn get_window_rect(&self, id: WindowId) -> Result<Rect>;
+    /// Moves and resizes the window, bringing it to the top of the z-order
+    /// without activating it.
+    fn set_window_rect(&self, id: WindowId, rect: Rect) -> Result<()>;
+    /// The window less its frame, in screen coordinates.
+    fn get_client_rect(&self, id: WindowId) -> Result<Rect>;
+    fn get_style(&self, id: WindowId) -> Result<u32>;
+    fn set_style(&self, id: WindowId, style: u32) -> Result<()>;
+    fn get_ex_style(&self, id: WindowId) -> Result<u32>;
+    fn set_ex_style(&self, id: WindowId, ex_style: u32) -> Result<()>;
+    fn get_show_state(&self, id: WindowId) -> Result<ShowState>;
+    fn show_window(&self, id: WindowId, show_command: i32) -> Result<()>;
+    fn set_z_order(&self, id: WindowId, z_order: ZOrder) -> Result<()>;
+    /// Brings the window to the front and gives it focus.
+    fn activate(&self, id: WindowId) -> Result<()>;
+    /// Asks the window to close, as its close button would. The application
+    /// may ask the user first, or refuse.
+    fn close(&self, id: WindowId) -> Result<()>;
+    /// From 0, invisible, to 1, opaque.
+    fn set_opacity(&self, id: WindowId, opacity: f64) -> Result<()>;
+    /// Who owns the window. Fields the backend cannot find out are left
+    /// zero or empty.
+    fn get_window_info(&self, id: WindowId) -> Result<WindowInfo>;
+    /// The window this one stays in front of and is minimized along with.
+    fn get_owner(&self, id: WindowId) -> Result<Option<WindowId>>;
+    /// Top-level windows have none.
+    fn get_parent(&self, id: WindowId) -> Result<Option<WindowId>>;
+    /// The monitor the window overlaps the most.
+    fn get_monitor(&self, id: WindowId) -> Result<Monitor>;
+    /// The visible top-level windows the hooks would report on, front to
+    /// back.
+    fn list_windows(&self) -> Result<Vec<WindowId>>;
+}
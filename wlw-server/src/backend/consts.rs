@@ -0,0 +1,82 @@
+// Win32 window styles, show commands and system commands, available on every
+// platform.
+
+pub const WS_OVERLAPPED: u32 = 0x0000_0000;
+pub const WS_POPUP: u32 = 0x8000_0000;
+pub const WS_CHILD: u32 = 0x4000_0000;
+pub const WS_MINIMIZE: u32 = 0x2000_0000;
+pub const WS_VISIBLE: u32 = 0x1000_0000;
+pub const WS_DISABLED: u32 = 0x0800_0000;
+pub const WS_CLIPSIBLINGS: u32 = 0x0400_0000;
+pub const WS_CLIPCHILDREN: u32 = 0x0200_0000;
+pub const WS_MAXIMIZE: u32 = 0x0100_0000;
+pub const WS_CAPTION: u32 = 0x00C0_0000;
+pub const WS_BORDER: u32 = 0x0080_0000;
+pub const WS_DLGFRAME: u32 = 0x0040_0000;
+pub const WS_VSCROLL: u32 = 0x0020_0000;
+pub const WS_HSCROLL: u32 = 0x0010_0000;
+pub const WS_SYSMENU: u32 = 0x0008_0000;
+pub const WS_THICKFRAME: u32 = 0x0004_0000;
+pub const WS_GROUP: u32 = 0x0002_0000;
+pub const WS_TABSTOP: u32 = 0x0001_0000;
+pub const WS_MINIMIZEBOX: u32 = 0x0002_0000;
+pub const WS_MAXIMIZEBOX: u32 = 0x0001_0000;
+pub const WS_ICONIC: u32 = WS_MINIMIZE;
+pub const WS_OVERLAPPEDWINDOW: u32 =
+    WS_OVERLAPPED | WS_CAPTION | WS_SYSMENU | WS_THICKFRAME | WS_MINIMIZEBOX | WS_MAXIMIZEBOX;
+
+pub const WS_EX_DLGMODALFRAME: u32 = 0x0000_0001;
+pub const WS_EX_NOPARENTNOTIFY: u32 = 0x0000_0004;
+pub const WS_EX_TOPMOST: u32 = 0x0000_0008;
+pub const WS_EX_ACCEPTFILES: u32 = 0x0000_0010;
+pub const WS_EX_TRANSPARENT: u32 = 0x0000_0020;
+pub const WS_EX_MDICHILD: u32 = 0x0000_0040;
+pub const WS_EX_TOOLWINDOW: u32 = 0x0000_0080;
+pub const WS_EX_WINDOWEDGE: u32 = 0x0000_0100;
+pub const WS_EX_CLIENTEDGE: u32 = 0x0000_0200;
+pub const WS_EX_CONTEXTHELP: u32 = 0x0000_0400;
+pub const WS_EX_RIGHT: u32 = 0x0000_1000;
+pub const WS_EX_RTLREADING: u32 = 0x0000_2000;
+pub const WS_EX_LEFTSCROLLBAR: u32 = 0x0000_4000;
+pub const WS_EX_CONTROLPARENT: u32 = 0x0001_0000;
+pub const WS_EX_STATICEDGE: u32 = 0x0002_0000;
+pub const WS_EX_APPWINDOW: u32 = 0x0004_0000;
+pub const WS_EX_LAYERED: u32 = 0x0008_0000;
+pub const WS_EX_NOINHERITLAYOUT: u32 = 0x0010_0000;
+pub const WS_EX_NOREDIRECTIONBITMAP: u32 = 0x0020_0000;
+pub const WS_EX_LAYOUTRTL: u32 = 0x0040_0000;
+pub const WS_EX_COMPOSITED: u32 = 0x0200_0000;
+pub const WS_EX_NOACTIVATE: u32 = 0x0800_0000;
+
+pub const SW_HIDE: i32 = 0;
+pub const SW_SHOWNORMAL: i32 = 1;
+pub const SW_SHOWMINIMIZED: i32 = 2;
+pub const SW_MAXIMIZE: i32 = 3;
+pub const SW_SHOWNOACTIVATE: i32 = 4;
+pub const SW_SHOW: i32 = 5;
+pub const SW_MINIMIZE: i32 = 6;
+pub const SW_SHOWMINNOACTIVE: i32 = 7;
+pub const SW_SHOWNA: i32 = 8;
+pub const SW_RESTORE: i32 = 9;
+pub const SW_SHOWDEFAULT: i32 = 10;
+pub const SW_FORCEMINIMIZE: i32 = 11;
+
+pub const SC_SIZE: i32 = 0xF000;
+pub const SC_MOVE: i32 = 0xF010;
+pub const SC_MINIMIZE: i32 = 0xF020;
+pub const SC_MAXIMIZE: i32 = 0xF030;
+pub const SC_NEXTWINDOW: i32 = 0xF040;
+pub const SC_PREVWINDOW: i32 = 0xF050;
+pub const SC_CLOSE: i32 = 0xF060;
+pub const SC_VSCROLL: i32 = 0xF070;
+pub const SC_HSCROLL: i32 = 0xF080;
+pub const SC_MOUSEMENU: i32 = 0xF090;
+pub const SC_KEYMENU: i32 = 0xF100;
+pub const SC_ARRANGE: i32 = 0xF110;
+pub const SC_RESTORE: i32 = 0xF120;
+pub const SC_TASKLIST: i32 = 0xF130;
+pub const SC_SCREENSAVE: i32 = 0xF140;
+pub const SC_HOTKEY: i32 = 0xF150;
+pub const SC_DEFAULT: i32 = 0xF160;
+pub const SC_MONITORPOWER: i32 = 0xF170;
+pub const SC_CONTEXTHELP: i32 = 0xF180;
@@ -0,0 +1,709 @@
+use super::*;
+use crate::hookevent::{self, HookEvent, WindowInfo};
+use std::cmp;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// How thick Win32 draws the resizable frame and the title bar below it.
+const FRAME_THICKNESS: i32 = 8;
+const CAPTION_HEIGHT: i32 = 23;
+
+/// Where Win32 parks minimized top-level windows.
+const MINIMIZED_RECT: Rect = Rect {
+    left: -32000,
+    top: -32000,
+    right: -31840,
+    bottom: -31972,
+};
+
+/// The state of a single window on the simulated desktop.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimulatedWindow {
+    pub class: String,
+    pub title: String,
+    pub rect: Rect,
+    /// Where the window goes when it is restored from minimized or maximized.
+    pub normal_rect: Rect,
+    pub style: u32,
+    pub ex_style: u32,
+    pub pid: u32,
+    pub exe: String,
+    /// From 0, invisible, to 1, opaque.
+    pub opacity: f64,
+    /// Every simulated window is top-level, so it can have an owner but no
+    /// parent.
+    pub owner: Option<WindowId>,
+}
+
+impl SimulatedWindow {
+    /// A visible, captioned top-level window like the ones the hook reports.
+    pub fn new(title: impl Into<String>, rect: Rect) -> Self {
+        SimulatedWindow {
+            class: String::new(),
+            title: title.into(),
+            rect,
+            normal_rect: rect,
+            style: WS_OVERLAPPEDWINDOW | WS_VISIBLE,
+            ex_style: 0,
+            pid: 0,
+            exe: String::new(),
+            opacity: 1.0,
+            owner: None,
+        }
+    }
+
+    fn is_visible(&self) -> bool {
+        self.style & WS_VISIBLE != 0
+    }
+
+    fn is_min_or_max(&self) -> bool {
+        self.style & (WS_MINIMIZE | WS_MAXIMIZE) != 0
+    }
+
+    fn client_rect(&self) -> Rect {
+        let frame = if self.style & WS_THICKFRAME != 0 {
+            FRAME_THICKNESS
+        } else {
+            0
+        };
+        let caption = if self.style & WS_CAPTION == WS_CAPTION {
+            CAPTION_HEIGHT
+        } else {
+            0
+        };
+        let left = self.rect.left + frame;
+        let top = self.rect.top + frame + caption;
+        Rect::new(
+            left,
+            top,
+            cmp::max(left, self.rect.right - frame),
+            cmp::max(top, self.rect.bottom - frame),
+        )
+    }
+
+    // Mirrors is_worthy_window in dllmain.c; every simulated window is
+    // top-level.
+    fn is_worthy(&self, exclude_hidden: bool) -> bool {
+        if exclude_hidden && !self.is_visible() {
+            return false;
+        }
+        self.style & WS_CAPTION != 0 && self.ex_style & WS_EX_TOOLWINDOW == 0
+    }
+}
+
+struct Desktop {
+    windows: HashMap<WindowId, SimulatedWindow>,
+    // Front to back
+    z_order: Vec<WindowId>,
+    focus: Option<WindowId>,
+    monitors: Vec<Monitor>,
+    last_id: WindowId,
+}
+
+impl Desktop {
+    fn get(&self, id: WindowId) -> Result<&SimulatedWindow> {
+        self.windows.get(&id).ok_or(Error::NoSuchWindow(id))
+    }
+
+    fn get_mut(&mut self, id: WindowId) -> Result<&mut SimulatedWindow> {
+        self.windows.get_mut(&id).ok_or(Error::NoSuchWindow(id))
+    }
+
+    fn insert(&mut self, window: SimulatedWindow) -> WindowId {
+        self.last_id += 1;
+        let id = self.last_id;
+        self.windows.insert(id, window);
+        self.raise(id);
+        id
+    }
+
+    fn is_topmost(&self, id: WindowId) -> bool {
+        self.windows[&id].ex_style & WS_EX_TOPMOST != 0
+    }
+
+    fn raise(&mut self, id: WindowId) {
+        self.z_order.retain(|&other| other != id);
+        let index = if self.is_topmost(id) {
+            0
+        } else {
+            self.z_order
+                .iter()
+                .position(|&other| !self.is_topmost(other))
+                .unwrap_or(self.z_order.len())
+        };
+        self.z_order.insert(index, id);
+    }
+
+    fn lower(&mut self, id: WindowId) {
+        self.z_order.retain(|&other| other != id);
+        self.z_order.push(id);
+    }
+
+    fn activate(&mut self, id: WindowId) {
+        self.raise(id);
+        self.focus = Some(id);
+    }
+
+    /// Passes focus to the frontmost remaining window, as Windows does when
+    /// the focused window goes away.
+    fn unfocus(&mut self, id: WindowId) {
+        if self.focus == Some(id) {
+            self.focus = self.z_order.iter().cloned().find(|&other| {
+                let window = &self.windows[&other];
+                other != id && window.is_visible() && window.style & WS_MINIMIZE == 0
+            });
+        }
+    }
+
+    /// The monitor which the rect overlaps the most, or the primary monitor.
+    fn monitor_for(&self, rect: Rect) -> Monitor {
+        let overlap = |monitor: &Monitor| {
+            let width =
+                cmp::min(rect.right, monitor.rect.right) - cmp::max(rect.left, monitor.rect.left);
+            let height =
+                cmp::min(rect.bottom, monitor.rect.bottom) - cmp::max(rect.top, monitor.rect.top);
+            i64::from(cmp::max(width, 0)) * i64::from(cmp::max(height, 0))
+        };
+        let mut best = self.monitors[0];
+        for monitor in &self.monitors[1..] {
+            if overlap(monitor) > overlap(&best) {
+                best = *monitor;
+            }
+        }
+        best
+    }
+
+    fn set_rect(&mut self, id: WindowId, rect: Rect) -> Result<()> {
+        let window = self.get_mut(id)?;
+        window.rect = rect;
+        if !window.is_min_or_max() {
+            window.normal_rect = rect;
+        }
+        Ok(())
+    }
+
+    fn show(&mut self, id: WindowId, show_command: i32) -> Result<()> {
+        let window = self.get(id)?;
+        let style = window.style;
+        let normal_rect = window.normal_rect;
+        let work_area = self.monitor_for(normal_rect).work_area;
+        let (style, rect) = match show_command {
+            SW_HIDE => (style & !WS_VISIBLE, window.rect),
+            SW_MINIMIZE | SW_SHOWMINIMIZED | SW_SHOWMINNOACTIVE | SW_FORCEMINIMIZE => (
+                (style | WS_VISIBLE | WS_MINIMIZE) & !WS_MAXIMIZE,
+                MINIMIZED_RECT,
+            ),
+            SW_MAXIMIZE => ((style | WS_VISIBLE | WS_MAXIMIZE) & !WS_MINIMIZE, work_area),
+            SW_SHOWNORMAL | SW_RESTORE | SW_SHOWDEFAULT => (
+                (style | WS_VISIBLE) & !(WS_MINIMIZE | WS_MAXIMIZE),
+                normal_rect,
+            ),
+            SW_SHOW | SW_SHOWNA | SW_SHOWNOACTIVATE => (style | WS_VISIBLE, window.rect),
+            _ => (style, window.rect),
+        };
+        let window = self.get_mut(id)?;
+        window.style = style;
+        window.rect = rect;
+        if style & WS_VISIBLE == 0 || style & WS_MINIMIZE != 0 {
+            self.unfocus(id);
+        }
+        Ok(())
+    }
+}
+
+/// An in-memory desktop which behaves closely enough to Win32 for scripts to
+/// be tested without it.
+///
+/// The `WindowBackend` methods change the desktop silently, the way a script
+/// sees its own calls take effect. The remaining methods act out what an
+/// application or the user would do, reporting to `hook` exactly what the
+/// hook DLL would and applying its responses.
+pub struct SimulatedBackend {
+    desktop: Mutex<Desktop>,
+}
+
+impl Default for SimulatedBackend {
+    fn default() -> Self {
+        SimulatedBackend::new()
+    }
+}
+
+impl SimulatedBackend {
+    /// A desktop with a single 1920x1080 monitor and a 40 pixel taskbar.
+    pub fn new() -> Self {
+        SimulatedBackend::with_monitors(vec![Monitor::new(
+            Rect::new(0, 0, 1920, 1080),
+            Rect::new(0, 0, 1920, 1040),
+        )])
+    }
+
+    /// The first monitor is the primary one.
+    pub fn with_monitors(monitors: Vec<Monitor>) -> Self {
+        assert!(!monitors.is_empty(), "A desktop needs at least one monitor");
+        SimulatedBackend {
+            desktop: Mutex::new(Desktop {
+                windows: HashMap::new(),
+                z_order: Vec::new(),
+                focus: None,
+                monitors,
+                last_id: 0,
+            }),
+        }
+    }
+
+    /// Adds a window to the top of the z-order without firing any hooks, as
+    /// if it had existed before the server started.
+    pub fn create_window(&self, window: SimulatedWindow) -> WindowId {
+        self.desktop.lock().unwrap().insert(window)
+    }
+
+    /// Adds a window under a known id without firing any hooks, replacing
+    /// any window which already has it. Later windows are numbered after it.
+    pub fn insert_window(&self, id: WindowId, window: SimulatedWindow) {
+        let mut desktop = self.desktop.lock().unwrap();
+        desktop.windows.insert(id, window);
+        desktop.raise(id);
+        desktop.last_id = cmp::max(desktop.last_id, id);
+    }
+
+    /// Removes a window without firing any hooks.
+    pub fn destroy_window(&self, id: WindowId) -> Result<()> {
+        let mut desktop = self.desktop.lock().unwrap();
+        desktop.get(id)?;
+        desktop.unfocus(id);
+        desktop.windows.remove(&id);
+        desktop.z_order.retain(|&other| other != id);
+        Ok(())
+    }
+
+    /// Returns a snapshot of the window's current state.
+    pub fn window(&self, id: WindowId) -> Result<SimulatedWindow> {
+        Ok(self.desktop.lock().unwrap().get(id)?.clone())
+    }
+
+    /// Returns every window id, front to back.
+    pub fn z_order(&self) -> Vec<WindowId> {
+        self.desktop.lock().unwrap().z_order.clone()
+    }
+
+    pub fn focused(&self) -> Option<WindowId> {
+        self.desktop.lock().unwrap().focus
+    }
+
+    pub fn monitors(&self) -> Vec<Monitor> {
+        self.desktop.lock().unwrap().monitors.clone()
+    }
+
+    /// Creates a window the way `CreateWindowEx` followed by `ShowWindow`
+    /// would: the create hook may reposition it before it becomes visible,
+    /// then it is shown and activated. A window whose creation the hook
+    /// denies is gone again by the time this returns.
+    pub fn open_window(&self, window: SimulatedWindow, hook: Hook) -> WindowId {
+        let reported = window.style & WS_CAPTION != 0
+            && window.style & WS_CHILD == 0
+            && window.ex_style & WS_EX_TOOLWINDOW == 0;
+        let visible = window.is_visible();
+        let rect = window.rect;
+        let id = self.desktop.lock().unwrap().insert(SimulatedWindow {
+            style: window.style & !WS_VISIBLE,
+            ..window
+        });
+        if reported {
+            let response = hook(HookEvent::CbtCreateWindow { hwnd: id, rect });
+            if !hookevent::allows(response) {
+                self.destroy_window(id).unwrap();
+                return id;
+            }
+            if let Some(rect) = response.and_then(|response| response.rect()) {
+                self.desktop.lock().unwrap().set_rect(id, rect).unwrap();
+            }
+        }
+        if visible {
+            self.set_window_shown(id, true, hook).unwrap();
+            self.activate_window(id, false, hook).unwrap();
+        }
+        id
+    }
+
+    /// Destroys a window. The destroy hook fires for every window.
+    pub fn close_window(&self, id: WindowId, hook: Hook) -> Result<()> {
+        self.desktop.lock().unwrap().get(id)?;
+        hook(HookEvent::CbtDestroyWindow { hwnd: id });
+        self.destroy_window(id)
+    }
+
+    /// Shows or hides a window, as `ShowWindow(SW_SHOW)` or `ShowWindow(SW_HIDE)`.
+    pub fn set_window_shown(&self, id: WindowId, shown: bool, hook: Hook) -> Result<()> {
+        let window = self.window(id)?;
+        if window.is_visible() == shown {
+            return Ok(());
+        }
+        if window.is_worthy(false) {
+            hook(HookEvent::CwpShowWindow { hwnd: id, shown });
+        }
+        self.desktop
+            .lock()
+            .unwrap()
+            .show(id, if shown { SW_SHOW } else { SW_HIDE })
+    }
+
+    /// Changes a window's title the way `SetWindowText` would, telling the
+    /// hook first.
+    pub fn retitle_window(&self, id: WindowId, title: &str, hook: Hook) -> Result<()> {
+        if self.window(id)?.is_worthy(false) {
+            hook(HookEvent::CwpSetText {
+                hwnd: id,
+                title: title.to_owned(),
+            });
+        }
+        self.set_title(id, title)
+    }
+
+    /// Activates a window, bringing it to the front and giving it focus,
+    /// unless the hook denies it.
+    pub fn activate_window(&self, id: WindowId, caused_by_mouse: bool, hook: Hook) -> Result<()> {
+        let window = self.window(id)?;
+        if window.ex_style & WS_EX_NOACTIVATE != 0 || self.focused() == Some(id) {
+            return Ok(());
+        }
+        if window.is_worthy(true)
+            && !hookevent::allows(hook(HookEvent::CbtActivate {
+                hwnd: id,
+                caused_by_mouse,
+            }))
+        {
+            return Ok(());
+        }
+        self.desktop.lock().unwrap().activate(id);
+        Ok(())
+    }
+
+    /// Minimizes, maximizes or restores a window as `show_command` dictates,
+    /// unless the hook denies it.
+    pub fn min_max_window(&self, id: WindowId, show_command: i32, hook: Hook) -> Result<()> {
+        if self.window(id)?.is_worthy(true)
+            && !hookevent::allows(hook(HookEvent::CbtMinMax {
+                hwnd: id,
+                show_command,
+            }))
+        {
+            return Ok(());
+        }
+        self.desktop.lock().unwrap().show(id, show_command)?;
+        if show_command == SW_MAXIMIZE || show_command == SW_RESTORE {
+            self.activate_window(id, false, hook)?;
+        }
+        Ok(())
+    }
+
+    /// Moves or resizes a window the way dragging its frame would. The hook's
+    /// response replaces the requested rect, or leaves the window where it
+    /// was.
+    pub fn move_size_window(&self, id: WindowId, rect: Rect, hook: Hook) -> Result<()> {
+        let mut rect = rect;
+        if self.window(id)?.is_worthy(true) {
+            let response = hook(HookEvent::CbtMoveSize { hwnd: id, rect });
+            if !hookevent::allows(response) {
+                return Ok(());
+            }
+            if let Some(response_rect) = response.and_then(|response| response.rect()) {
+                rect = response_rect;
+            }
+        }
+        self.desktop.lock().unwrap().set_rect(id, rect)
+    }
+}
+
+impl WindowBackend for SimulatedBackend {
+    fn get_title(&self, id: WindowId) -> Result<String> {
+        Ok(self.window(id)?.title)
+    }
+
+    fn set_title(&self, id: WindowId, title: &str) -> Result<()> {
+        self.desktop.lock().unwrap().get_mut(id)?.title = title.to_owned();
+        Ok(())
+    }
+
+    fn get_window_rect(&self, id: WindowId) -> Result<Rect> {
+        Ok(self.desktop.lock().unwrap().get(id)?.rect)
+    }
+
+    fn set_window_rect(&self, id: WindowId, rect: Rect) -> Result<()> {
+        let mut desktop = self.desktop.lock().unwrap();
+        desktop.set_rect(id, rect)?;
+        desktop.raise(id);
+        Ok(())
+    }
+
+    fn get_client_rect(&self, id: WindowId) -> Result<Rect> {
+        Ok(self.desktop.lock().unwrap().get(id)?.client_rect())
+    }
+
+    fn get_style(&self, id: WindowId) -> Result<u32> {
+        Ok(self.desktop.lock().unwrap().get(id)?.style)
+    }
+
+    fn set_style(&self, id: WindowId, style: u32) -> Result<()> {
+        self.desktop.lock().unwrap().get_mut(id)?.style = style;
+        Ok(())
+    }
+
+    fn get_ex_style(&self, id: WindowId) -> Result<u32> {
+        Ok(self.desktop.lock().unwrap().get(id)?.ex_style)
+    }
+
+    fn set_ex_style(&self, id: WindowId, ex_style: u32) -> Result<()> {
+        self.desktop.lock().unwrap().get_mut(id)?.ex_style = ex_style;
+        Ok(())
+    }
+
+    fn get_show_state(&self, id: WindowId) -> Result<ShowState> {
+        let style = self.desktop.lock().unwrap().get(id)?.style;
+        Ok(if style & WS_VISIBLE == 0 {
+            ShowState::Hidden
+        } else if style & WS_MINIMIZE != 0 {
+            ShowState::Minimized
+        } else if style & WS_MAXIMIZE != 0 {
+            ShowState::Maximized
+        } else {
+            ShowState::Normal
+        })
+    }
+
+    fn show_window(&self, id: WindowId, show_command: i32) -> Result<()> {
+        self.desktop.lock().unwrap().show(id, show_command)
+    }
+
+    fn set_z_order(&self, id: WindowId, z_order: ZOrder) -> Result<()> {
+        let mut desktop = self.desktop.lock().unwrap();
+        let window = desktop.get_mut(id)?;
+        match z_order {
+            ZOrder::Top => desktop.raise(id),
+            ZOrder::Bottom => {
+                window.ex_style &= !WS_EX_TOPMOST;
+                desktop.lower(id);
+            }
+            ZOrder::Topmost => {
+                window.ex_style |= WS_EX_TOPMOST;
+                desktop.raise(id);
+            }
+            ZOrder::NoTopmost => {
+                if window.ex_style & WS_EX_TOPMOST != 0 {
+                    window.ex_style &= !WS_EX_TOPMOST;
+                    desktop.raise(id);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn activate(&self, id: WindowId) -> Result<()> {
+        let mut desktop = self.desktop.lock().unwrap();
+        desktop.get(id)?;
+        desktop.activate(id);
+        Ok(())
+    }
+
+    /// Simulated applications never refuse to close.
+    fn close(&self, id: WindowId) -> Result<()> {
+        self.destroy_window(id)
+    }
+
+    fn set_opacity(&self, id: WindowId, opacity: f64) -> Result<()> {
+        self.desktop.lock().unwrap().get_mut(id)?.opacity = opacity;
+        Ok(())
+    }
+
+    fn get_window_info(&self, id: WindowId) -> Result<WindowInfo> {
+        let window = self.window(id)?;
+        Ok(WindowInfo {
+            pid: window.pid,
+            thread_id: 0,
+            class_name: window.class,
+            exe: window.exe,
+        })
+    }
+
+    fn get_owner(&self, id: WindowId) -> Result<Option<WindowId>> {
+        Ok(self.window(id)?.owner)
+    }
+
+    fn get_parent(&self, id: WindowId) -> Result<Option<WindowId>> {
+        self.window(id)?;
+        Ok(None)
+    }
+
+    fn get_monitor(&self, id: WindowId) -> Result<Monitor> {
+        let desktop = self.desktop.lock().unwrap();
+        let window = desktop.get(id)?;
+        // Minimized windows are parked off every monitor
+        Ok(desktop.monitor_for(window.normal_rect))
+    }
+
+    fn list_windows(&self) -> Result<Vec<WindowId>> {
+        let desktop = self.desktop.lock().unwrap();
+        Ok(desktop
+            .z_order
+            .iter()
+            .cloned()
+            .filter(|id| desktop.windows[id].is_worthy(true))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(title: &str) -> SimulatedWindow {
+        SimulatedWindow::new(title, Rect::new(100, 100, 500, 400))
+    }
+
+    #[test]
+    fn z_order_keeps_topmost_windows_in_front() {
+        let backend = SimulatedBackend::new();
+        let a = backend.create_window(window("a"));
+        let b = backend.create_window(window("b"));
+        assert_eq!(backend.z_order(), vec![b, a]);
+
+        backend.set_z_order(a, ZOrder::Topmost).unwrap();
+        let c = backend.create_window(window("c"));
+        assert_eq!(backend.z_order(), vec![a, c, b]);
+
+        backend.set_window_rect(b, Rect::new(0, 0, 10, 10)).unwrap();
+        assert_eq!(backend.z_order(), vec![a, b, c]);
+
+        backend.set_z_order(a, ZOrder::Bottom).unwrap();
+        assert_eq!(backend.z_order(), vec![b, c, a]);
+        assert_eq!(backend.get_ex_style(a).unwrap() & WS_EX_TOPMOST, 0);
+    }
+
+    #[test]
+    fn show_commands_update_show_state_and_geometry() {
+        let backend = SimulatedBackend::with_monitors(vec![
+            Monitor::new(Rect::new(0, 0, 1920, 1080), Rect::new(0, 0, 1920, 1040)),
+            Monitor::new(
+                Rect::new(1920, 0, 3840, 1080),
+                Rect::new(1920, 0, 3840, 1080),
+            ),
+        ]);
+        let normal = Rect::new(2000, 100, 2400, 400);
+        let id = backend.create_window(SimulatedWindow::new("a", normal));
+        assert_eq!(backend.get_show_state(id).unwrap(), ShowState::Normal);
+        backend.show_window(id, SW_MAXIMIZE).unwrap();
+        assert_eq!(backend.get_show_state(id).unwrap(), ShowState::Maximized);
+        assert_eq!(
+            backend.get_window_rect(id).unwrap(),
+            Rect::new(1920, 0, 3840, 1080)
+        );
+        backend.show_window(id, SW_MINIMIZE).unwrap();
+        assert_eq!(backend.get_show_state(id).unwrap(), ShowState::Minimized);
+        assert_eq!(backend.get_window_rect(id).unwrap(), MINIMIZED_RECT);
+        backend.show_window(id, SW_HIDE).unwrap();
+        assert_eq!(backend.get_show_state(id).unwrap(), ShowState::Hidden);
+        backend.show_window(id, SW_RESTORE).unwrap();
+        assert_eq!(backend.get_show_state(id).unwrap(), ShowState::Normal);
+        assert_eq!(backend.get_window_rect(id).unwrap(), normal);
+    }
+
+    #[test]
+    fn open_window_fires_hooks_in_order() {
+        let backend = SimulatedBackend::new();
+        let mut events = Vec::new();
+        let id = backend.open_window(window("a"), &mut |event| {
+            let response = match event {
+                HookEvent::CbtCreateWindow { .. } => {
+                    Some(HookResponse::from_rect(Rect::new(0, 0, 960, 1040)))
+                }
+                _ => None,
+            };
+            events.push(event);
+            response
+        });
+        assert_eq!(
+            events,
+            vec![
+                HookEvent::CbtCreateWindow {
+                    hwnd: id,
+                    rect: Rect::new(100, 100, 500, 400)
+                },
+                HookEvent::CwpShowWindow {
+                    hwnd: id,
+                    shown: true
+                },
+                HookEvent::CbtActivate {
+                    hwnd: id,
+                    caused_by_mouse: false
+                },
+            ]
+        );
+        assert_eq!(
+            backend.get_window_rect(id).unwrap(),
+            Rect::new(0, 0, 960, 1040)
+        );
+        assert_eq!(backend.focused(), Some(id));
+    }
+
+    #[test]
+    fn unworthy_windows_are_not_reported() {
+        let backend = SimulatedBackend::new();
+        let mut events = Vec::new();
+        let mut tool = window("tool");
+        tool.ex_style |= WS_EX_TOOLWINDOW;
+        let id = backend.open_window(tool, &mut |event| {
+            events.push(event);
+            None
+        });
+        backend
+            .move_size_window(id, Rect::new(0, 0, 10, 10), &mut |event| {
+                events.push(event);
+                None
+            })
+            .unwrap();
+        backend
+            .close_window(id, &mut |event| {
+                events.push(event);
+                None
+            })
+            .unwrap();
+        assert_eq!(events, vec![HookEvent::CbtDestroyWindow { hwnd: id }]);
+    }
+
+    #[test]
+    fn focus_passes_to_next_window() {
+        let backend = SimulatedBackend::new();
+        let mut hook = |_: HookEvent| None;
+        let a = backend.open_window(window("a"), &mut hook);
+        let b = backend.open_window(window("b"), &mut hook);
+        assert_eq!(backend.focused(), Some(b));
+        backend.min_max_window(b, SW_MINIMIZE, &mut hook).unwrap();
+        assert_eq!(backend.focused(), Some(a));
+        backend.close_window(a, &mut hook).unwrap();
+        assert_eq!(backend.focused(), None);
+        backend.min_max_window(b, SW_RESTORE, &mut hook).unwrap();
+        assert_eq!(backend.focused(), Some(b));
+    }
+
+    #[test]
+    fn denied_operations_do_not_happen() {
+        let backend = SimulatedBackend::new();
+        let mut deny = |_: HookEvent| Some(HookResponse::deny());
+        let mut allow = |_: HookEvent| None;
+        let popup = backend.open_window(window("popup"), &mut deny);
+        assert!(backend.window(popup).is_err());
+
+        let a = backend.open_window(window("a"), &mut allow);
+        let b = backend.open_window(window("b"), &mut allow);
+        backend.activate_window(a, true, &mut deny).unwrap();
+        assert_eq!(backend.focused(), Some(b));
+        backend.min_max_window(b, SW_MAXIMIZE, &mut deny).unwrap();
+        assert_eq!(backend.get_show_state(b).unwrap(), ShowState::Normal);
+        backend
+            .move_size_window(b, Rect::new(0, 0, 10, 10), &mut deny)
+            .unwrap();
+        assert_eq!(
+            backend.get_window_rect(b).unwrap(),
+            Rect::new(100, 100, 500, 400)
+        );
+    }
+}
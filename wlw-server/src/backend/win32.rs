@@ -0,0 +1,209 @@
+use super::{
+    Monitor, Result, ShowState, WindowBackend, WindowId, ZOrder, WS_CAPTION, WS_EX_LAYERED,
+    WS_EX_TOOLWINDOW, WS_VISIBLE,
+};
+use crate::hookevent::WindowInfo;
+use crate::luauserdata::Rect;
+use crate::windows;
+use std::ffi::OsString;
+use std::path::Path;
+
+/// Manipulates real windows through the Win32 API.
+#[derive(Default)]
+pub struct Win32Backend;
+
+impl Win32Backend {
+    pub fn new() -> Self {
+        Win32Backend
+    }
+}
+
+fn hwnd(id: WindowId) -> windows::HWND {
+    id as windows::HWND
+}
+
+/// The file name of a process's executable.
+fn process_image_name(pid: windows::DWORD) -> windows::Result<String> {
+    unsafe {
+        let process = windows::OpenProcess(windows::PROCESS_QUERY_LIMITED_INFORMATION, false, pid)?;
+        let path = windows::QueryFullProcessImageName(process);
+        windows::CloseHandle(process)?;
+        Ok(Path::new(&path?)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default())
+    }
+}
+
+impl WindowBackend for Win32Backend {
+    fn get_title(&self, id: WindowId) -> Result<String> {
+        Ok(unsafe { windows::GetWindowText(hwnd(id)) }
+            .map(|s| s.into_string().unwrap_or_default())?)
+    }
+
+    fn set_title(&self, id: WindowId, title: &str) -> Result<()> {
+        Ok(unsafe { windows::SetWindowText(hwnd(id), OsString::from(title)) }?)
+    }
+
+    fn get_window_rect(&self, id: WindowId) -> Result<Rect> {
+        Ok(unsafe { windows::GetWindowRect(hwnd(id)) }.map(Rect::from)?)
+    }
+
+    fn set_window_rect(&self, id: WindowId, rect: Rect) -> Result<()> {
+        Ok(unsafe {
+            windows::SetWindowPos(
+                hwnd(id),
+                windows::HWND_TOP,
+                rect.left,
+                rect.top,
+                rect.right - rect.left,
+                rect.bottom - rect.top,
+                windows::SWP_NOACTIVATE,
+            )
+        }?)
+    }
+
+    fn get_client_rect(&self, id: WindowId) -> Result<Rect> {
+        Ok(unsafe { windows::GetClientRect(hwnd(id)) }.map(Rect::from)?)
+    }
+
+    fn get_style(&self, id: WindowId) -> Result<u32> {
+        Ok(unsafe { windows::GetWindowLong(hwnd(id), windows::GWL_STYLE) }? as u32)
+    }
+
+    fn set_style(&self, id: WindowId, style: u32) -> Result<()> {
+        unsafe { windows::SetWindowLong(hwnd(id), windows::GWL_STYLE, style as windows::LONG) }?;
+        Ok(())
+    }
+
+    fn get_ex_style(&self, id: WindowId) -> Result<u32> {
+        Ok(unsafe { windows::GetWindowLong(hwnd(id), windows::GWL_EXSTYLE) }? as u32)
+    }
+
+    fn set_ex_style(&self, id: WindowId, ex_style: u32) -> Result<()> {
+        unsafe {
+            windows::SetWindowLong(hwnd(id), windows::GWL_EXSTYLE, ex_style as windows::LONG)
+        }?;
+        Ok(())
+    }
+
+    fn get_show_state(&self, id: WindowId) -> Result<ShowState> {
+        let hwnd = hwnd(id);
+        unsafe {
+            if !windows::IsWindowVisible(hwnd) {
+                Ok(ShowState::Hidden)
+            } else if windows::IsIconic(hwnd) {
+                Ok(ShowState::Minimized)
+            } else if windows::IsZoomed(hwnd) {
+                Ok(ShowState::Maximized)
+            } else {
+                Ok(ShowState::Normal)
+            }
+        }
+    }
+
+    fn show_window(&self, id: WindowId, show_command: i32) -> Result<()> {
+        // The return value is the previous visibility, not an error status
+        unsafe { windows::ShowWindow(hwnd(id), show_command) };
+        Ok(())
+    }
+
+    fn set_z_order(&self, id: WindowId, z_order: ZOrder) -> Result<()> {
+        let insert_after = match z_order {
+            ZOrder::Top => windows::HWND_TOP,
+            ZOrder::Bottom => windows::HWND_BOTTOM,
+            ZOrder::Topmost => windows::HWND_TOPMOST,
+            ZOrder::NoTopmost => windows::HWND_NOTOPMOST,
+        };
+        Ok(unsafe {
+            windows::SetWindowPos(
+                hwnd(id),
+                insert_after,
+                0,
+                0,
+                0,
+                0,
+                windows::SWP_NOMOVE | windows::SWP_NOSIZE | windows::SWP_NOACTIVATE,
+            )
+        }?)
+    }
+
+    fn activate(&self, id: WindowId) -> Result<()> {
+        // Windows only lets the foreground process take the foreground, and
+        // flashes the taskbar button instead of failing otherwise
+        unsafe { windows::SetForegroundWindow(hwnd(id)) };
+        Ok(())
+    }
+
+    fn close(&self, id: WindowId) -> Result<()> {
+        Ok(unsafe { windows::PostMessage(hwnd(id), windows::WM_CLOSE, 0, 0) }?)
+    }
+
+    fn set_opacity(&self, id: WindowId, opacity: f64) -> Result<()> {
+        let ex_style = self.get_ex_style(id)?;
+        if ex_style & WS_EX_LAYERED == 0 {
+            self.set_ex_style(id, ex_style | WS_EX_LAYERED)?;
+        }
+        Ok(unsafe {
+            windows::SetLayeredWindowAttributes(
+                hwnd(id),
+                0,
+                (opacity * 255.0).round() as u8,
+                windows::LWA_ALPHA,
+            )
+        }?)
+    }
+
+    fn get_window_info(&self, id: WindowId) -> Result<WindowInfo> {
+        let hwnd = hwnd(id);
+        let (thread_id, pid) = unsafe { windows::GetWindowThreadProcessId(hwnd) }?;
+        let class_name = unsafe { windows::GetClassName(hwnd) }?;
+        Ok(WindowInfo {
+            pid,
+            thread_id,
+            class_name: class_name.to_string_lossy().into_owned(),
+            // Elevated processes won't let us look
+            exe: process_image_name(pid).unwrap_or_default(),
+        })
+    }
+
+    fn get_owner(&self, id: WindowId) -> Result<Option<WindowId>> {
+        let owner = unsafe { windows::GetOwner(hwnd(id)) };
+        Ok(if owner.is_null() {
+            None
+        } else {
+            Some(owner as WindowId)
+        })
+    }
+
+    fn get_parent(&self, id: WindowId) -> Result<Option<WindowId>> {
+        let parent = unsafe { windows::GetParent(hwnd(id)) };
+        Ok(if parent.is_null() {
+            None
+        } else {
+            Some(parent as WindowId)
+        })
+    }
+
+    fn get_monitor(&self, id: WindowId) -> Result<Monitor> {
+        let (rect, work_area) = unsafe { windows::GetMonitorInfo(hwnd(id)) }?;
+        Ok(Monitor::new(rect.into(), work_area.into()))
+    }
+
+    fn list_windows(&self) -> Result<Vec<WindowId>> {
+        // Mirrors is_worthy_window in dllmain.c. Windows which go away while
+        // we look are left out.
+        let is_worthy = |id| -> Result<bool> {
+            let style = self.get_style(id)?;
+            let ex_style = self.get_ex_style(id)?;
+            Ok(style & WS_VISIBLE != 0
+                && style & WS_CAPTION != 0
+                && ex_style & WS_EX_TOOLWINDOW == 0)
+        };
+        Ok(unsafe { windows::EnumWindows() }?
+            .into_iter()
+            .map(|hwnd| hwnd as WindowId)
+            .filter(|&id| is_worthy(id).unwrap_or(false))
+            .collect())
+    }
+}
@@ -0,0 +1,1414 @@
+use super::{Hook, Monitor, Result, ShowState, WindowBackend, WindowId, ZOrder};
+use super::{SW_FORCEMINIMIZE, SW_SHOWDEFAULT, SW_SHOWMINIMIZED, SW_SHOWMINNOACTIVE};
+use super::{SW_HIDE, SW_MAXIMIZE, SW_MINIMIZE, SW_RESTORE, SW_SHOW};
+use super::{SW_SHOWNA, SW_SHOWNOACTIVATE, SW_SHOWNORMAL};
+use super::{WS_CAPTION, WS_MAXIMIZE, WS_MINIMIZE, WS_OVERLAPPEDWINDOW, WS_POPUP, WS_VISIBLE};
+use super::{WS_EX_NOACTIVATE, WS_EX_TOOLWINDOW, WS_EX_TOPMOST};
+use crate::hookevent::{self, HookEvent, WindowInfo};
+use crate::luauserdata::Rect;
+use std::cmp;
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::sync::Mutex;
+use x11rb::connection::Connection;
+use x11rb::errors::{ConnectError, ConnectionError, ReplyError, ReplyOrIdError};
+use x11rb::protocol::xproto::{
+    Allow, AtomEnum, ButtonIndex, ButtonPressEvent, ChangeWindowAttributesAux, ClientMessageEvent,
+    ConfigWindow, ConfigureNotifyEvent, ConfigureRequestEvent, ConfigureWindowAux,
+    ConnectionExt as _, CreateWindowAux, EventMask, GrabMode, InputFocus, MapState, ModMask,
+    PropMode, StackMode, Window, WindowClass, CONFIGURE_NOTIFY_EVENT,
+};
+use x11rb::protocol::{ErrorKind, Event};
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
+use x11rb::x11_utils::X11Error;
+use x11rb::{COPY_DEPTH_FROM_PARENT, CURRENT_TIME, NONE};
+
+x11rb::atom_manager! {
+    Atoms: AtomsCookie {
+        UTF8_STRING,
+        WM_CHANGE_STATE,
+        WM_DELETE_WINDOW,
+        WM_PROTOCOLS,
+        WM_STATE,
+        _NET_ACTIVE_WINDOW,
+        _NET_CLIENT_LIST,
+        _NET_CLIENT_LIST_STACKING,
+        _NET_SUPPORTED,
+        _NET_SUPPORTING_WM_CHECK,
+        _NET_WM_NAME,
+        _NET_WM_PID,
+        _NET_WM_STATE,
+        _NET_WM_STATE_ABOVE,
+        _NET_WM_STATE_HIDDEN,
+        _NET_WM_STATE_MAXIMIZED_HORZ,
+        _NET_WM_STATE_MAXIMIZED_VERT,
+        _NET_WM_STRUT,
+        _NET_WM_WINDOW_OPACITY,
+        _NET_WM_WINDOW_TYPE,
+        _NET_WM_WINDOW_TYPE_DESKTOP,
+        _NET_WM_WINDOW_TYPE_DOCK,
+        _NET_WM_WINDOW_TYPE_MENU,
+        _NET_WM_WINDOW_TYPE_NOTIFICATION,
+        _NET_WM_WINDOW_TYPE_SPLASH,
+        _NET_WM_WINDOW_TYPE_TOOLBAR,
+        _NET_WM_WINDOW_TYPE_UTILITY,
+    }
+}
+
+// ICCCM WM_STATE values
+const WITHDRAWN_STATE: u32 = 0;
+const NORMAL_STATE: u32 = 1;
+const ICONIC_STATE: u32 = 3;
+
+// _NET_WM_STATE client message actions
+const NET_WM_STATE_REMOVE: u32 = 0;
+const NET_WM_STATE_ADD: u32 = 1;
+
+#[derive(Debug)]
+pub enum Error {
+    Connect(ConnectError),
+    Connection(ConnectionError),
+    Protocol(X11Error),
+    IdsExhausted,
+    OtherWindowManager,
+}
+
+impl error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Connect(e) => write!(f, "Error connecting to the X server: {}", e),
+            Error::Connection(e) => write!(f, "X connection error: {}", e),
+            Error::Protocol(e) => write!(f, "X protocol error: {:?}", e),
+            Error::IdsExhausted => write!(f, "X resource ids exhausted"),
+            Error::OtherWindowManager => write!(f, "Another window manager is already running"),
+        }
+    }
+}
+
+impl From<ConnectError> for Error {
+    fn from(err: ConnectError) -> Self {
+        Error::Connect(err)
+    }
+}
+
+impl From<ConnectionError> for Error {
+    fn from(err: ConnectionError) -> Self {
+        Error::Connection(err)
+    }
+}
+
+impl From<ReplyError> for Error {
+    fn from(err: ReplyError) -> Self {
+        match err {
+            ReplyError::ConnectionError(e) => Error::Connection(e),
+            ReplyError::X11Error(e) => Error::Protocol(e),
+        }
+    }
+}
+
+impl From<ReplyOrIdError> for Error {
+    fn from(err: ReplyOrIdError) -> Self {
+        match err {
+            ReplyOrIdError::IdsExhausted => Error::IdsExhausted,
+            ReplyOrIdError::ConnectionError(e) => Error::Connection(e),
+            ReplyOrIdError::X11Error(e) => Error::Protocol(e),
+        }
+    }
+}
+
+impl From<ConnectionError> for super::Error {
+    fn from(err: ConnectionError) -> Self {
+        super::Error::X11(err.into())
+    }
+}
+
+impl From<ReplyError> for super::Error {
+    fn from(err: ReplyError) -> Self {
+        super::Error::X11(err.into())
+    }
+}
+
+impl From<ReplyOrIdError> for super::Error {
+    fn from(err: ReplyOrIdError) -> Self {
+        super::Error::X11(err.into())
+    }
+}
+
+/// Reserved space along the screen edges, as `_NET_WM_STRUT` lists it:
+/// left, right, top, bottom.
+type Strut = [u32; 4];
+
+/// What the window manager knows about a window it manages. X has no window
+/// styles, so they are emulated: the state bits follow the window, and the
+/// rest only describe how the window is treated.
+struct Client {
+    style: u32,
+    ex_style: u32,
+    /// Where the window goes when it is restored from minimized or maximized.
+    normal_rect: Rect,
+    strut: Option<Strut>,
+    /// Unmaps the window manager made itself, whose notifications are not the
+    /// client withdrawing.
+    pending_unmaps: u32,
+}
+
+impl Client {
+    fn is_visible(&self) -> bool {
+        self.style & WS_VISIBLE != 0
+    }
+
+    fn is_mapped(&self) -> bool {
+        self.is_visible() && self.style & WS_MINIMIZE == 0
+    }
+
+    fn is_min_or_max(&self) -> bool {
+        self.style & (WS_MINIMIZE | WS_MAXIMIZE) != 0
+    }
+
+    // Mirrors is_worthy_window in dllmain.c
+    fn is_worthy(&self, exclude_hidden: bool) -> bool {
+        if exclude_hidden && !self.is_visible() {
+            return false;
+        }
+        self.style & WS_CAPTION != 0 && self.ex_style & WS_EX_TOOLWINDOW == 0
+    }
+}
+
+struct Desktop {
+    clients: HashMap<Window, Client>,
+    // In the order they were first mapped
+    client_list: Vec<Window>,
+    // Front to back
+    z_order: Vec<Window>,
+    focus: Option<Window>,
+    screen: Rect,
+}
+
+impl Desktop {
+    fn get(&self, id: WindowId) -> Result<&Client> {
+        self.clients.get(&id).ok_or(super::Error::NoSuchWindow(id))
+    }
+
+    fn get_mut(&mut self, id: WindowId) -> Result<&mut Client> {
+        self.clients
+            .get_mut(&id)
+            .ok_or(super::Error::NoSuchWindow(id))
+    }
+
+    fn is_topmost(&self, id: WindowId) -> bool {
+        self.clients[&id].ex_style & WS_EX_TOPMOST != 0
+    }
+
+    /// Moves the window in front of every window which is not topmost, or
+    /// every window if it is topmost itself. Returns the window it now sits
+    /// behind, if any.
+    fn raise(&mut self, id: WindowId) -> Option<Window> {
+        self.z_order.retain(|&other| other != id);
+        let index = if self.is_topmost(id) {
+            0
+        } else {
+            self.z_order
+                .iter()
+                .position(|&other| !self.is_topmost(other))
+                .unwrap_or(self.z_order.len())
+        };
+        self.z_order.insert(index, id);
+        if index == 0 {
+            None
+        } else {
+            Some(self.z_order[index - 1])
+        }
+    }
+
+    fn lower(&mut self, id: WindowId) {
+        self.z_order.retain(|&other| other != id);
+        self.z_order.push(id);
+    }
+
+    /// The frontmost window which can take focus from `id`.
+    fn next_focus(&self, id: WindowId) -> Option<Window> {
+        self.z_order.iter().cloned().find(|&other| {
+            let client = &self.clients[&other];
+            other != id && client.is_mapped() && client.is_worthy(true)
+        })
+    }
+
+    fn work_area(&self) -> Rect {
+        work_area(self.screen, self.clients.values().filter_map(|c| c.strut))
+    }
+}
+
+/// The screen less the space docks have reserved along its edges.
+fn work_area(screen: Rect, struts: impl Iterator<Item = Strut>) -> Rect {
+    let mut reserved = [0; 4];
+    for strut in struts {
+        for (reserved, edge) in reserved.iter_mut().zip(strut.iter()) {
+            *reserved = cmp::max(*reserved, *edge as i32);
+        }
+    }
+    Rect::new(
+        screen.left + reserved[0],
+        screen.top + reserved[2],
+        screen.right - reserved[1],
+        screen.bottom - reserved[3],
+    )
+}
+
+/// The rect a configure request asks for, keeping whatever it leaves out.
+fn requested_rect(current: Rect, request: &ConfigureRequestEvent) -> Rect {
+    let mask = request.value_mask;
+    let left = if mask.contains(ConfigWindow::X) {
+        i32::from(request.x)
+    } else {
+        current.left
+    };
+    let top = if mask.contains(ConfigWindow::Y) {
+        i32::from(request.y)
+    } else {
+        current.top
+    };
+    let width = if mask.contains(ConfigWindow::WIDTH) {
+        i32::from(request.width)
+    } else {
+        current.right - current.left
+    };
+    let height = if mask.contains(ConfigWindow::HEIGHT) {
+        i32::from(request.height)
+    } else {
+        current.bottom - current.top
+    };
+    Rect::new(left, top, left + width, top + height)
+}
+
+fn configure_aux(rect: Rect) -> ConfigureWindowAux {
+    ConfigureWindowAux::new()
+        .x(rect.left)
+        .y(rect.top)
+        .width(cmp::max(rect.right - rect.left, 1) as u32)
+        .height(cmp::max(rect.bottom - rect.top, 1) as u32)
+}
+
+/// Manages the windows of an X11 screen as an EWMH window manager would,
+/// without decorating them. Window ids are X window ids.
+///
+/// The `WindowBackend` methods act silently, the way a script sees its own
+/// calls take effect. `manage` reports what applications and the user do to
+/// `hook` exactly as the hook DLL would on Windows, and applies its responses.
+pub struct X11Backend {
+    conn: RustConnection,
+    root: Window,
+    atoms: Atoms,
+    desktop: Mutex<Desktop>,
+}
+
+impl X11Backend {
+    /// Connects to `display`, or `$DISPLAY` if it is `None`.
+    pub fn connect(display: Option<&str>) -> Result<Self> {
+        let (conn, screen_num) = x11rb::connect(display).map_err(Error::from)?;
+        let screen = &conn.setup().roots[screen_num];
+        let root = screen.root;
+        let screen_rect = Rect::new(
+            0,
+            0,
+            i32::from(screen.width_in_pixels),
+            i32::from(screen.height_in_pixels),
+        );
+        let atoms = Atoms::new(&conn)?.reply()?;
+        Ok(X11Backend {
+            conn,
+            root,
+            atoms,
+            desktop: Mutex::new(Desktop {
+                clients: HashMap::new(),
+                client_list: Vec::new(),
+                z_order: Vec::new(),
+                focus: None,
+                screen: screen_rect,
+            }),
+        })
+    }
+
+    /// Takes over as the screen's window manager and handles events until
+    /// the connection fails. Windows which are already mapped are managed
+    /// without being reported.
+    pub fn manage(&self, hook: Hook) -> Result<()> {
+        self.become_window_manager()?;
+        self.adopt_mapped_windows()?;
+        loop {
+            self.conn.flush()?;
+            let result = match self.conn.wait_for_event()? {
+                Event::Error(e) => Err(Error::Protocol(e).into()),
+                event => self.handle_event(event, hook),
+            };
+            match result {
+                // Windows can vanish at any time, taking requests about them
+                // down with them
+                Err(super::Error::X11(Error::Protocol(e))) => {
+                    debug!("Ignoring X error: {:?}", e);
+                }
+                result => result?,
+            }
+        }
+    }
+
+    fn become_window_manager(&self) -> Result<()> {
+        let attributes = ChangeWindowAttributesAux::new()
+            .event_mask(EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY);
+        match self
+            .conn
+            .change_window_attributes(self.root, &attributes)?
+            .check()
+        {
+            Err(ReplyError::X11Error(ref e)) if e.error_kind == ErrorKind::Access => {
+                return Err(Error::OtherWindowManager.into());
+            }
+            result => result?,
+        }
+
+        let check = self.conn.generate_id()?;
+        self.conn.create_window(
+            COPY_DEPTH_FROM_PARENT,
+            check,
+            self.root,
+            -1,
+            -1,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_ONLY,
+            0,
+            &CreateWindowAux::new(),
+        )?;
+        for &window in &[self.root, check] {
+            self.conn.change_property32(
+                PropMode::REPLACE,
+                window,
+                self.atoms._NET_SUPPORTING_WM_CHECK,
+                AtomEnum::WINDOW,
+                &[check],
+            )?;
+        }
+        self.conn.change_property8(
+            PropMode::REPLACE,
+            check,
+            self.atoms._NET_WM_NAME,
+            self.atoms.UTF8_STRING,
+            b"wlw",
+        )?;
+        let atoms = &self.atoms;
+        self.conn.change_property32(
+            PropMode::REPLACE,
+            self.root,
+            atoms._NET_SUPPORTED,
+            AtomEnum::ATOM,
+            &[
+                atoms._NET_ACTIVE_WINDOW,
+                atoms._NET_CLIENT_LIST,
+                atoms._NET_CLIENT_LIST_STACKING,
+                atoms._NET_SUPPORTING_WM_CHECK,
+                atoms._NET_WM_NAME,
+                atoms._NET_WM_STATE,
+                atoms._NET_WM_STATE_ABOVE,
+                atoms._NET_WM_STATE_HIDDEN,
+                atoms._NET_WM_STATE_MAXIMIZED_HORZ,
+                atoms._NET_WM_STATE_MAXIMIZED_VERT,
+                atoms._NET_WM_STRUT,
+                atoms._NET_WM_WINDOW_TYPE,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn adopt_mapped_windows(&self) -> Result<()> {
+        // Bottom to top
+        let children = self.conn.query_tree(self.root)?.reply()?.children;
+        for window in children {
+            let attributes = self.conn.get_window_attributes(window)?.reply()?;
+            if attributes.override_redirect || attributes.map_state != MapState::VIEWABLE {
+                continue;
+            }
+            let mut client = self.new_client(window)?;
+            client.style |= WS_VISIBLE;
+            self.add_client(window, client)?;
+            self.set_wm_state(window)?;
+        }
+        self.publish_client_lists()
+    }
+
+    fn handle_event(&self, event: Event, hook: Hook) -> Result<()> {
+        match event {
+            Event::MapRequest(e) => self.on_map_request(e.window, hook),
+            Event::ConfigureRequest(e) => self.on_configure_request(&e, hook),
+            Event::UnmapNotify(e) => self.on_unmap_notify(e.window, hook),
+            Event::DestroyNotify(e) => self.on_destroy_notify(e.window, hook),
+            Event::ClientMessage(e) => self.on_client_message(&e, hook),
+            Event::ButtonPress(e) => self.on_button_press(&e, hook),
+            _ => Ok(()),
+        }
+    }
+
+    fn is_managed(&self, window: Window) -> bool {
+        self.desktop.lock().unwrap().clients.contains_key(&window)
+    }
+
+    fn client_style(&self, window: Window) -> Result<u32> {
+        Ok(self.desktop.lock().unwrap().get(window)?.style)
+    }
+
+    fn is_worthy(&self, window: Window, exclude_hidden: bool) -> Result<bool> {
+        Ok(self
+            .desktop
+            .lock()
+            .unwrap()
+            .get(window)?
+            .is_worthy(exclude_hidden))
+    }
+
+    /// A hidden client whose styles follow its `_NET_WM_WINDOW_TYPE`. Docks
+    /// and the like are not reported, and utilities act as tool windows.
+    fn new_client(&self, window: Window) -> Result<Client> {
+        let atoms = &self.atoms;
+        let window_type = self
+            .conn
+            .get_property(
+                false,
+                window,
+                atoms._NET_WM_WINDOW_TYPE,
+                AtomEnum::ATOM,
+                0,
+                1,
+            )?
+            .reply()?
+            .value32()
+            .and_then(|mut types| types.next());
+        let (style, ex_style) = match window_type {
+            Some(t)
+                if t == atoms._NET_WM_WINDOW_TYPE_DESKTOP
+                    || t == atoms._NET_WM_WINDOW_TYPE_DOCK
+                    || t == atoms._NET_WM_WINDOW_TYPE_NOTIFICATION
+                    || t == atoms._NET_WM_WINDOW_TYPE_SPLASH =>
+            {
+                (WS_POPUP, 0)
+            }
+            Some(t)
+                if t == atoms._NET_WM_WINDOW_TYPE_MENU
+                    || t == atoms._NET_WM_WINDOW_TYPE_TOOLBAR
+                    || t == atoms._NET_WM_WINDOW_TYPE_UTILITY =>
+            {
+                (WS_OVERLAPPEDWINDOW, WS_EX_TOOLWINDOW)
+            }
+            _ => (WS_OVERLAPPEDWINDOW, 0),
+        };
+        let strut = if window_type == Some(atoms._NET_WM_WINDOW_TYPE_DOCK) {
+            let reply = self
+                .conn
+                .get_property(false, window, atoms._NET_WM_STRUT, AtomEnum::CARDINAL, 0, 4)?
+                .reply()?;
+            reply.value32().and_then(|values| {
+                let values: Vec<u32> = values.collect();
+                match *values.as_slice() {
+                    [left, right, top, bottom] => Some([left, right, top, bottom]),
+                    _ => None,
+                }
+            })
+        } else {
+            None
+        };
+        Ok(Client {
+            style,
+            ex_style,
+            normal_rect: self.get_window_rect_unchecked(window)?,
+            strut,
+            pending_unmaps: 0,
+        })
+    }
+
+    /// Starts managing a window, placing it in front of every other.
+    fn add_client(&self, window: Window, client: Client) -> Result<()> {
+        {
+            let mut desktop = self.desktop.lock().unwrap();
+            desktop.clients.insert(window, client);
+            desktop.client_list.push(window);
+        }
+        // Clicks activate the window, then go through to it
+        self.conn.grab_button(
+            false,
+            window,
+            EventMask::BUTTON_PRESS,
+            GrabMode::SYNC,
+            GrabMode::ASYNC,
+            NONE,
+            NONE,
+            ButtonIndex::ANY,
+            ModMask::ANY,
+        )?;
+        self.raise(window)
+    }
+
+    fn remove_client(&self, window: Window) -> Result<()> {
+        {
+            let mut desktop = self.desktop.lock().unwrap();
+            desktop.clients.remove(&window);
+            desktop.client_list.retain(|&other| other != window);
+            desktop.z_order.retain(|&other| other != window);
+        }
+        self.unfocus(window)?;
+        self.publish_client_lists()
+    }
+
+    fn publish_client_lists(&self) -> Result<()> {
+        let (client_list, mut stacking) = {
+            let desktop = self.desktop.lock().unwrap();
+            (desktop.client_list.clone(), desktop.z_order.clone())
+        };
+        stacking.reverse();
+        self.conn.change_property32(
+            PropMode::REPLACE,
+            self.root,
+            self.atoms._NET_CLIENT_LIST,
+            AtomEnum::WINDOW,
+            &client_list,
+        )?;
+        self.conn.change_property32(
+            PropMode::REPLACE,
+            self.root,
+            self.atoms._NET_CLIENT_LIST_STACKING,
+            AtomEnum::WINDOW,
+            &stacking,
+        )?;
+        Ok(())
+    }
+
+    /// Brings `WM_STATE` and `_NET_WM_STATE` in line with the client's style.
+    fn set_wm_state(&self, window: Window) -> Result<()> {
+        let (style, ex_style) = {
+            let desktop = self.desktop.lock().unwrap();
+            let client = desktop.get(window)?;
+            (client.style, client.ex_style)
+        };
+        let wm_state = if style & WS_VISIBLE == 0 {
+            WITHDRAWN_STATE
+        } else if style & WS_MINIMIZE != 0 {
+            ICONIC_STATE
+        } else {
+            NORMAL_STATE
+        };
+        self.conn.change_property32(
+            PropMode::REPLACE,
+            window,
+            self.atoms.WM_STATE,
+            self.atoms.WM_STATE,
+            &[wm_state, NONE],
+        )?;
+        let mut net_wm_state = Vec::new();
+        if style & WS_MINIMIZE != 0 {
+            net_wm_state.push(self.atoms._NET_WM_STATE_HIDDEN);
+        }
+        if style & WS_MAXIMIZE != 0 {
+            net_wm_state.push(self.atoms._NET_WM_STATE_MAXIMIZED_HORZ);
+            net_wm_state.push(self.atoms._NET_WM_STATE_MAXIMIZED_VERT);
+        }
+        if ex_style & WS_EX_TOPMOST != 0 {
+            net_wm_state.push(self.atoms._NET_WM_STATE_ABOVE);
+        }
+        self.conn.change_property32(
+            PropMode::REPLACE,
+            window,
+            self.atoms._NET_WM_STATE,
+            AtomEnum::ATOM,
+            &net_wm_state,
+        )?;
+        Ok(())
+    }
+
+    fn get_window_rect_unchecked(&self, window: Window) -> Result<Rect> {
+        let geometry = self.conn.get_geometry(window)?.reply()?;
+        let left = i32::from(geometry.x);
+        let top = i32::from(geometry.y);
+        Ok(Rect::new(
+            left,
+            top,
+            left + i32::from(geometry.width),
+            top + i32::from(geometry.height),
+        ))
+    }
+
+    fn set_rect(&self, window: Window, rect: Rect) -> Result<()> {
+        {
+            let mut desktop = self.desktop.lock().unwrap();
+            let client = desktop.get_mut(window)?;
+            if !client.is_min_or_max() {
+                client.normal_rect = rect;
+            }
+        }
+        self.conn.configure_window(window, &configure_aux(rect))?;
+        Ok(())
+    }
+
+    fn raise(&self, window: Window) -> Result<()> {
+        let behind = self.desktop.lock().unwrap().raise(window);
+        let aux = match behind {
+            Some(sibling) => ConfigureWindowAux::new()
+                .sibling(sibling)
+                .stack_mode(StackMode::BELOW),
+            None => ConfigureWindowAux::new().stack_mode(StackMode::ABOVE),
+        };
+        self.conn.configure_window(window, &aux)?;
+        self.publish_client_lists()
+    }
+
+    fn focus(&self, window: Option<Window>) -> Result<()> {
+        self.desktop.lock().unwrap().focus = window;
+        match window {
+            Some(window) => self
+                .conn
+                .set_input_focus(InputFocus::PARENT, window, CURRENT_TIME)?,
+            None => self.conn.set_input_focus(
+                InputFocus::POINTER_ROOT,
+                InputFocus::POINTER_ROOT,
+                CURRENT_TIME,
+            )?,
+        };
+        self.conn.change_property32(
+            PropMode::REPLACE,
+            self.root,
+            self.atoms._NET_ACTIVE_WINDOW,
+            AtomEnum::WINDOW,
+            &[window.unwrap_or(NONE)],
+        )?;
+        Ok(())
+    }
+
+    /// Passes focus to the frontmost remaining window, as Windows does when
+    /// the focused window goes away.
+    fn unfocus(&self, window: Window) -> Result<()> {
+        let next = {
+            let desktop = self.desktop.lock().unwrap();
+            if desktop.focus != Some(window) {
+                return Ok(());
+            }
+            desktop.next_focus(window)
+        };
+        self.focus(next)
+    }
+
+    fn show(&self, window: Window, show_command: i32) -> Result<()> {
+        let current = self.get_window_rect_unchecked(window)?;
+        let (was_mapped, style, rect) = {
+            let desktop = self.desktop.lock().unwrap();
+            let client = desktop.get(window)?;
+            let style = client.style;
+            match show_command {
+                SW_HIDE => (client.is_mapped(), style & !WS_VISIBLE, current),
+                SW_MINIMIZE | SW_SHOWMINIMIZED | SW_SHOWMINNOACTIVE | SW_FORCEMINIMIZE => (
+                    client.is_mapped(),
+                    (style | WS_VISIBLE | WS_MINIMIZE) & !WS_MAXIMIZE,
+                    current,
+                ),
+                SW_MAXIMIZE => (
+                    client.is_mapped(),
+                    (style | WS_VISIBLE | WS_MAXIMIZE) & !WS_MINIMIZE,
+                    desktop.work_area(),
+                ),
+                SW_SHOWNORMAL | SW_RESTORE | SW_SHOWDEFAULT => (
+                    client.is_mapped(),
+                    (style | WS_VISIBLE) & !(WS_MINIMIZE | WS_MAXIMIZE),
+                    client.normal_rect,
+                ),
+                SW_SHOW | SW_SHOWNA | SW_SHOWNOACTIVATE => {
+                    (client.is_mapped(), style | WS_VISIBLE, current)
+                }
+                _ => (client.is_mapped(), style, current),
+            }
+        };
+        let is_mapped = style & WS_VISIBLE != 0 && style & WS_MINIMIZE == 0;
+        {
+            let mut desktop = self.desktop.lock().unwrap();
+            let client = desktop.get_mut(window)?;
+            client.style = style;
+            if was_mapped && !is_mapped {
+                client.pending_unmaps += 1;
+            }
+        }
+        if rect != current {
+            self.conn.configure_window(window, &configure_aux(rect))?;
+        }
+        if is_mapped && !was_mapped {
+            self.conn.map_window(window)?;
+        } else if was_mapped && !is_mapped {
+            self.conn.unmap_window(window)?;
+            self.unfocus(window)?;
+        }
+        self.set_wm_state(window)
+    }
+
+    fn set_shown(&self, window: Window, shown: bool, hook: Hook) -> Result<()> {
+        let style = self.client_style(window)?;
+        if (style & WS_VISIBLE != 0) == shown {
+            return Ok(());
+        }
+        if self.is_worthy(window, false)? {
+            hook(HookEvent::CwpShowWindow {
+                hwnd: window,
+                shown,
+            });
+        }
+        self.show(window, if shown { SW_SHOW } else { SW_HIDE })
+    }
+
+    fn activate(&self, window: Window, caused_by_mouse: bool, hook: Hook) -> Result<()> {
+        let (ex_style, focused) = {
+            let desktop = self.desktop.lock().unwrap();
+            (desktop.get(window)?.ex_style, desktop.focus)
+        };
+        if ex_style & WS_EX_NOACTIVATE != 0 || focused == Some(window) {
+            return Ok(());
+        }
+        if self.is_worthy(window, true)?
+            && !hookevent::allows(hook(HookEvent::CbtActivate {
+                hwnd: window,
+                caused_by_mouse,
+            }))
+        {
+            return Ok(());
+        }
+        self.raise(window)?;
+        self.focus(Some(window))
+    }
+
+    fn min_max(&self, window: Window, show_command: i32, hook: Hook) -> Result<()> {
+        if self.is_worthy(window, true)?
+            && !hookevent::allows(hook(HookEvent::CbtMinMax {
+                hwnd: window,
+                show_command,
+            }))
+        {
+            return Ok(());
+        }
+        self.show(window, show_command)?;
+        if show_command == SW_MAXIMIZE || show_command == SW_RESTORE {
+            self.activate(window, false, hook)?;
+        }
+        Ok(())
+    }
+
+    fn on_map_request(&self, window: Window, hook: Hook) -> Result<()> {
+        if self.is_managed(window) {
+            return if self.client_style(window)? & WS_MINIMIZE != 0 {
+                self.min_max(window, SW_RESTORE, hook)
+            } else {
+                self.set_shown(window, true, hook)
+            };
+        }
+        if self
+            .conn
+            .get_window_attributes(window)?
+            .reply()?
+            .override_redirect
+        {
+            self.conn.map_window(window)?;
+            return Ok(());
+        }
+        let client = self.new_client(window)?;
+        let rect = client.normal_rect;
+        let worthy = client.is_worthy(false);
+        self.add_client(window, client)?;
+        if worthy {
+            let response = hook(HookEvent::CbtCreateWindow { hwnd: window, rect });
+            if !hookevent::allows(response) {
+                // Left unmapped and unmanaged, as if it had never asked
+                self.conn
+                    .ungrab_button(ButtonIndex::ANY, window, ModMask::ANY)?;
+                return self.remove_client(window);
+            }
+            if let Some(rect) = response.and_then(|response| response.rect()) {
+                self.set_rect(window, rect)?;
+            }
+        }
+        self.set_shown(window, true, hook)?;
+        self.activate(window, false, hook)
+    }
+
+    fn on_configure_request(&self, request: &ConfigureRequestEvent, hook: Hook) -> Result<()> {
+        let window = request.window;
+        if !self.is_managed(window) {
+            self.conn
+                .configure_window(window, &ConfigureWindowAux::from_configure_request(request))?;
+            return Ok(());
+        }
+        let current = self.get_window_rect_unchecked(window)?;
+        let mut rect = requested_rect(current, request);
+        if rect != current && self.is_worthy(window, true)? {
+            let response = hook(HookEvent::CbtMoveSize { hwnd: window, rect });
+            if !hookevent::allows(response) {
+                rect = current;
+            } else if let Some(response_rect) = response.and_then(|response| response.rect()) {
+                rect = response_rect;
+            }
+        }
+        if rect == current {
+            // The client gets no real ConfigureNotify when nothing changes,
+            // which ICCCM says to make up for
+            let notify = ConfigureNotifyEvent {
+                response_type: CONFIGURE_NOTIFY_EVENT,
+                sequence: 0,
+                event: window,
+                window,
+                above_sibling: NONE,
+                x: rect.left as i16,
+                y: rect.top as i16,
+                width: (rect.right - rect.left) as u16,
+                height: (rect.bottom - rect.top) as u16,
+                border_width: request.border_width,
+                override_redirect: false,
+            };
+            self.conn
+                .send_event(false, window, EventMask::STRUCTURE_NOTIFY, notify)?;
+            Ok(())
+        } else {
+            self.set_rect(window, rect)
+        }
+    }
+
+    fn on_unmap_notify(&self, window: Window, hook: Hook) -> Result<()> {
+        {
+            let mut desktop = self.desktop.lock().unwrap();
+            match desktop.clients.get_mut(&window) {
+                Some(client) if client.pending_unmaps > 0 => {
+                    client.pending_unmaps -= 1;
+                    return Ok(());
+                }
+                Some(client) if client.is_visible() => {}
+                _ => return Ok(()),
+            }
+        }
+        // The client withdrew the window itself
+        if self.is_worthy(window, false)? {
+            hook(HookEvent::CwpShowWindow {
+                hwnd: window,
+                shown: false,
+            });
+        }
+        self.desktop.lock().unwrap().get_mut(window)?.style &= !(WS_VISIBLE | WS_MINIMIZE);
+        self.unfocus(window)?;
+        self.set_wm_state(window)
+    }
+
+    fn on_destroy_notify(&self, window: Window, hook: Hook) -> Result<()> {
+        if self.is_managed(window) {
+            hook(HookEvent::CbtDestroyWindow { hwnd: window });
+            self.remove_client(window)?;
+        }
+        Ok(())
+    }
+
+    fn on_client_message(&self, message: &ClientMessageEvent, hook: Hook) -> Result<()> {
+        let window = message.window;
+        if !self.is_managed(window) {
+            return Ok(());
+        }
+        let data = message.data.as_data32();
+        let style = self.client_style(window)?;
+        let atoms = &self.atoms;
+        if message.type_ == atoms._NET_ACTIVE_WINDOW {
+            if style & WS_MINIMIZE != 0 {
+                self.min_max(window, SW_RESTORE, hook)?;
+            }
+            self.activate(window, false, hook)
+        } else if message.type_ == atoms.WM_CHANGE_STATE && data[0] == ICONIC_STATE {
+            if style & WS_MINIMIZE == 0 {
+                self.min_max(window, SW_MINIMIZE, hook)?;
+            }
+            Ok(())
+        } else if message.type_ == atoms._NET_WM_STATE {
+            let wants = |current: bool| match data[0] {
+                NET_WM_STATE_REMOVE => false,
+                NET_WM_STATE_ADD => true,
+                _ => !current,
+            };
+            let names =
+                |a: u32, b: u32| data[1] == a || data[1] == b || data[2] == a || data[2] == b;
+            if names(
+                atoms._NET_WM_STATE_MAXIMIZED_HORZ,
+                atoms._NET_WM_STATE_MAXIMIZED_VERT,
+            ) {
+                let maximized = style & WS_MAXIMIZE != 0;
+                if wants(maximized) != maximized {
+                    let command = if maximized { SW_RESTORE } else { SW_MAXIMIZE };
+                    self.min_max(window, command, hook)?;
+                }
+            }
+            if names(atoms._NET_WM_STATE_HIDDEN, atoms._NET_WM_STATE_HIDDEN) {
+                let minimized = style & WS_MINIMIZE != 0;
+                if wants(minimized) != minimized {
+                    let command = if minimized { SW_RESTORE } else { SW_MINIMIZE };
+                    self.min_max(window, command, hook)?;
+                }
+            }
+            if names(atoms._NET_WM_STATE_ABOVE, atoms._NET_WM_STATE_ABOVE) {
+                let ex_style = self.get_ex_style(window)?;
+                let z_order = if wants(ex_style & WS_EX_TOPMOST != 0) {
+                    ZOrder::Topmost
+                } else {
+                    ZOrder::NoTopmost
+                };
+                self.set_z_order(window, z_order)?;
+            }
+            Ok(())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn on_button_press(&self, press: &ButtonPressEvent, hook: Hook) -> Result<()> {
+        let result = if self.is_managed(press.event) {
+            self.activate(press.event, true, hook)
+        } else {
+            Ok(())
+        };
+        self.conn.allow_events(Allow::REPLAY_POINTER, press.time)?;
+        result
+    }
+}
+
+impl WindowBackend for X11Backend {
+    fn get_title(&self, id: WindowId) -> Result<String> {
+        self.desktop.lock().unwrap().get(id)?;
+        let net_wm_name = self
+            .conn
+            .get_property(
+                false,
+                id,
+                self.atoms._NET_WM_NAME,
+                self.atoms.UTF8_STRING,
+                0,
+                1024,
+            )?
+            .reply()?;
+        let name = if net_wm_name.value_len > 0 {
+            net_wm_name.value
+        } else {
+            self.conn
+                .get_property(false, id, AtomEnum::WM_NAME, AtomEnum::ANY, 0, 1024)?
+                .reply()?
+                .value
+        };
+        Ok(String::from_utf8_lossy(&name).into_owned())
+    }
+
+    fn set_title(&self, id: WindowId, title: &str) -> Result<()> {
+        self.desktop.lock().unwrap().get(id)?;
+        self.conn.change_property8(
+            PropMode::REPLACE,
+            id,
+            self.atoms._NET_WM_NAME,
+            self.atoms.UTF8_STRING,
+            title.as_bytes(),
+        )?;
+        self.conn.change_property8(
+            PropMode::REPLACE,
+            id,
+            AtomEnum::WM_NAME,
+            AtomEnum::STRING,
+            title.as_bytes(),
+        )?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    fn get_window_rect(&self, id: WindowId) -> Result<Rect> {
+        self.desktop.lock().unwrap().get(id)?;
+        self.get_window_rect_unchecked(id)
+    }
+
+    fn set_window_rect(&self, id: WindowId, rect: Rect) -> Result<()> {
+        self.set_rect(id, rect)?;
+        self.raise(id)?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    /// Clients are not framed, so they are all client area.
+    fn get_client_rect(&self, id: WindowId) -> Result<Rect> {
+        self.get_window_rect(id)
+    }
+
+    fn get_style(&self, id: WindowId) -> Result<u32> {
+        self.client_style(id)
+    }
+
+    fn set_style(&self, id: WindowId, style: u32) -> Result<()> {
+        self.desktop.lock().unwrap().get_mut(id)?.style = style;
+        Ok(())
+    }
+
+    fn get_ex_style(&self, id: WindowId) -> Result<u32> {
+        Ok(self.desktop.lock().unwrap().get(id)?.ex_style)
+    }
+
+    fn set_ex_style(&self, id: WindowId, ex_style: u32) -> Result<()> {
+        self.desktop.lock().unwrap().get_mut(id)?.ex_style = ex_style;
+        self.set_wm_state(id)?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    fn get_show_state(&self, id: WindowId) -> Result<ShowState> {
+        let style = self.client_style(id)?;
+        Ok(if style & WS_VISIBLE == 0 {
+            ShowState::Hidden
+        } else if style & WS_MINIMIZE != 0 {
+            ShowState::Minimized
+        } else if style & WS_MAXIMIZE != 0 {
+            ShowState::Maximized
+        } else {
+            ShowState::Normal
+        })
+    }
+
+    fn show_window(&self, id: WindowId, show_command: i32) -> Result<()> {
+        self.show(id, show_command)?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    fn set_z_order(&self, id: WindowId, z_order: ZOrder) -> Result<()> {
+        match z_order {
+            ZOrder::Top => self.raise(id)?,
+            ZOrder::Bottom => {
+                {
+                    let mut desktop = self.desktop.lock().unwrap();
+                    desktop.get_mut(id)?.ex_style &= !WS_EX_TOPMOST;
+                    desktop.lower(id);
+                }
+                self.conn.configure_window(
+                    id,
+                    &ConfigureWindowAux::new().stack_mode(StackMode::BELOW),
+                )?;
+                self.set_wm_state(id)?;
+                self.publish_client_lists()?;
+            }
+            ZOrder::Topmost => {
+                self.desktop.lock().unwrap().get_mut(id)?.ex_style |= WS_EX_TOPMOST;
+                self.set_wm_state(id)?;
+                self.raise(id)?;
+            }
+            ZOrder::NoTopmost => {
+                let was_topmost = {
+                    let mut desktop = self.desktop.lock().unwrap();
+                    let client = desktop.get_mut(id)?;
+                    let was_topmost = client.ex_style & WS_EX_TOPMOST != 0;
+                    client.ex_style &= !WS_EX_TOPMOST;
+                    was_topmost
+                };
+                if was_topmost {
+                    self.set_wm_state(id)?;
+                    self.raise(id)?;
+                }
+            }
+        }
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    fn activate(&self, id: WindowId) -> Result<()> {
+        self.raise(id)?;
+        self.focus(Some(id))?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    fn close(&self, id: WindowId) -> Result<()> {
+        self.desktop.lock().unwrap().get(id)?;
+        let atoms = &self.atoms;
+        let protocols = self
+            .conn
+            .get_property(false, id, atoms.WM_PROTOCOLS, AtomEnum::ATOM, 0, 1024)?
+            .reply()?;
+        let polite = protocols
+            .value32()
+            .is_some_and(|mut protocols| protocols.any(|p| p == atoms.WM_DELETE_WINDOW));
+        // Clients which don't take part in WM_DELETE_WINDOW can only be
+        // thrown off the display
+        if polite {
+            let message = ClientMessageEvent::new(
+                32,
+                id,
+                atoms.WM_PROTOCOLS,
+                [atoms.WM_DELETE_WINDOW, CURRENT_TIME, 0, 0, 0],
+            );
+            self.conn
+                .send_event(false, id, EventMask::NO_EVENT, message)?;
+        } else {
+            self.conn.kill_client(id)?;
+        }
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    fn set_opacity(&self, id: WindowId, opacity: f64) -> Result<()> {
+        self.desktop.lock().unwrap().get(id)?;
+        // Compositors take a missing opacity to mean opaque
+        if opacity >= 1.0 {
+            self.conn
+                .delete_property(id, self.atoms._NET_WM_WINDOW_OPACITY)?;
+        } else {
+            self.conn.change_property32(
+                PropMode::REPLACE,
+                id,
+                self.atoms._NET_WM_WINDOW_OPACITY,
+                AtomEnum::CARDINAL,
+                &[(opacity * f64::from(u32::MAX)).round() as u32],
+            )?;
+        }
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    fn get_window_info(&self, id: WindowId) -> Result<WindowInfo> {
+        self.desktop.lock().unwrap().get(id)?;
+        let pid = self
+            .conn
+            .get_property(false, id, self.atoms._NET_WM_PID, AtomEnum::CARDINAL, 0, 1)?
+            .reply()?
+            .value32()
+            .and_then(|mut values| values.next())
+            .unwrap_or(0);
+        // WM_CLASS holds the instance name and then the class name, each
+        // null-terminated
+        let wm_class = self
+            .conn
+            .get_property(false, id, AtomEnum::WM_CLASS, AtomEnum::STRING, 0, 1024)?
+            .reply()?
+            .value;
+        let class_name = wm_class
+            .split(|&byte| byte == 0)
+            .nth(1)
+            .map(|class| String::from_utf8_lossy(class).into_owned())
+            .unwrap_or_default();
+        // Only clients on this machine have their executable to hand
+        let exe = if pid == 0 {
+            String::new()
+        } else {
+            fs::read_link(format!("/proc/{}/exe", pid))
+                .ok()
+                .and_then(|path| {
+                    path.file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                })
+                .unwrap_or_default()
+        };
+        Ok(WindowInfo {
+            pid,
+            // X has no idea which thread made a window
+            thread_id: 0,
+            class_name,
+            exe,
+        })
+    }
+
+    fn get_owner(&self, id: WindowId) -> Result<Option<WindowId>> {
+        self.desktop.lock().unwrap().get(id)?;
+        let owner = self
+            .conn
+            .get_property(
+                false,
+                id,
+                AtomEnum::WM_TRANSIENT_FOR,
+                AtomEnum::WINDOW,
+                0,
+                1,
+            )?
+            .reply()?
+            .value32()
+            .and_then(|mut values| values.next());
+        // Dialogs of unmanaged windows are as good as unowned
+        Ok(owner.filter(|&owner| self.is_managed(owner)))
+    }
+
+    fn get_parent(&self, id: WindowId) -> Result<Option<WindowId>> {
+        self.desktop.lock().unwrap().get(id)?;
+        Ok(None)
+    }
+
+    /// The whole screen is one monitor.
+    fn get_monitor(&self, id: WindowId) -> Result<Monitor> {
+        let desktop = self.desktop.lock().unwrap();
+        desktop.get(id)?;
+        Ok(Monitor::new(desktop.screen, desktop.work_area()))
+    }
+
+    fn list_windows(&self) -> Result<Vec<WindowId>> {
+        let desktop = self.desktop.lock().unwrap();
+        Ok(desktop
+            .z_order
+            .iter()
+            .cloned()
+            .filter(|id| desktop.clients[id].is_worthy(true))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hookevent::HookResponse;
+    use std::sync::mpsc;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn docks_shrink_the_work_area() {
+        let screen = Rect::new(0, 0, 1920, 1080);
+        assert_eq!(work_area(screen, Vec::new().into_iter()), screen);
+        assert_eq!(
+            work_area(
+                screen,
+                vec![[0, 0, 24, 0], [0, 0, 0, 40], [0, 0, 30, 0]].into_iter()
+            ),
+            Rect::new(0, 30, 1920, 1040)
+        );
+    }
+
+    #[test]
+    fn configure_requests_keep_unrequested_fields() {
+        let mut request = ConfigureRequestEvent {
+            response_type: 0,
+            stack_mode: StackMode::ABOVE,
+            sequence: 0,
+            parent: 0,
+            window: 0,
+            sibling: NONE,
+            x: 50,
+            y: 60,
+            width: 300,
+            height: 200,
+            border_width: 0,
+            value_mask: ConfigWindow::X | ConfigWindow::WIDTH,
+        };
+        let current = Rect::new(10, 20, 110, 220);
+        assert_eq!(
+            requested_rect(current, &request),
+            Rect::new(50, 20, 350, 220)
+        );
+        request.value_mask = ConfigWindow::Y | ConfigWindow::HEIGHT;
+        assert_eq!(
+            requested_rect(current, &request),
+            Rect::new(10, 60, 110, 260)
+        );
+    }
+
+    /// Needs an X server without a window manager, such as
+    /// `Xvfb :99 & DISPLAY=:99 cargo test -- --ignored`.
+    #[test]
+    #[ignore]
+    fn manages_new_windows() {
+        let backend = Arc::new(X11Backend::connect(None).unwrap());
+        let (event_sender, event_receiver) = mpsc::channel();
+        let manager = backend.clone();
+        thread::spawn(move || {
+            manager
+                .manage(&mut |event| {
+                    let response = match event {
+                        HookEvent::CbtCreateWindow { .. } => {
+                            Some(HookResponse::from_rect(Rect::new(0, 0, 640, 480)))
+                        }
+                        _ => None,
+                    };
+                    event_sender.send(event).unwrap();
+                    response
+                })
+                .unwrap()
+        });
+        thread::sleep(Duration::from_millis(200));
+
+        let (client, screen_num) = x11rb::connect(None).unwrap();
+        let root = client.setup().roots[screen_num].root;
+        let window = client.generate_id().unwrap();
+        client
+            .create_window(
+                COPY_DEPTH_FROM_PARENT,
+                window,
+                root,
+                100,
+                100,
+                200,
+                150,
+                0,
+                WindowClass::INPUT_OUTPUT,
+                0,
+                &CreateWindowAux::new(),
+            )
+            .unwrap();
+        client
+            .change_property8(
+                PropMode::REPLACE,
+                window,
+                AtomEnum::WM_NAME,
+                AtomEnum::STRING,
+                b"xterm",
+            )
+            .unwrap();
+        client
+            .change_property8(
+                PropMode::REPLACE,
+                window,
+                AtomEnum::WM_CLASS,
+                AtomEnum::STRING,
+                b"xterm\0XTerm\0",
+            )
+            .unwrap();
+        client.map_window(window).unwrap();
+        client.flush().unwrap();
+
+        let timeout = Duration::from_secs(5);
+        assert_eq!(
+            event_receiver.recv_timeout(timeout).unwrap(),
+            HookEvent::CbtCreateWindow {
+                hwnd: window,
+                rect: Rect::new(100, 100, 300, 250)
+            }
+        );
+        assert_eq!(
+            event_receiver.recv_timeout(timeout).unwrap(),
+            HookEvent::CwpShowWindow {
+                hwnd: window,
+                shown: true
+            }
+        );
+        assert_eq!(
+            event_receiver.recv_timeout(timeout).unwrap(),
+            HookEvent::CbtActivate {
+                hwnd: window,
+                caused_by_mouse: false
+            }
+        );
+        assert_eq!(backend.get_title(window).unwrap(), "xterm");
+        assert_eq!(backend.get_window_info(window).unwrap().class_name, "XTerm");
+        assert_eq!(
+            backend.get_window_rect(window).unwrap(),
+            Rect::new(0, 0, 640, 480)
+        );
+        assert_eq!(backend.get_show_state(window).unwrap(), ShowState::Normal);
+
+        client.destroy_window(window).unwrap();
+        client.flush().unwrap();
+        assert_eq!(
+            event_receiver.recv_timeout(timeout).unwrap(),
+            HookEvent::CwpShowWindow {
+                hwnd: window,
+                shown: false
+            }
+        );
+        assert_eq!(
+            event_receiver.recv_timeout(timeout).unwrap(),
+            HookEvent::CbtDestroyWindow { hwnd: window }
+        );
+    }
+}
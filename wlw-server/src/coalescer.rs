@@ -0,0 +1,121 @@
+use crate::clock::Clock;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+struct Held<K, T> {
+    key: K,
+    due: Instant,
+    item: T,
+}
+
+/// Holds back items which only matter for their latest value, such as where
+/// a window ended up, so that a burst of them for one key comes out as one.
+/// An item is due a fixed window after the first of its burst came in, so a
+/// steady stream still comes out once per window.
+pub struct Coalescer<K, T> {
+    window: Duration,
+    clock: Arc<dyn Clock>,
+    // Oldest first, which is also soonest due
+    held: VecDeque<Held<K, T>>,
+    num_merged: usize,
+}
+
+impl<K: PartialEq, T> Coalescer<K, T> {
+    pub fn new(window: Duration, clock: Arc<dyn Clock>) -> Self {
+        Coalescer {
+            window,
+            clock,
+            held: VecDeque::new(),
+            num_merged: 0,
+        }
+    }
+
+    /// Holds `item` in place of whatever is held for `key`, which is
+    /// dropped.
+    pub fn hold(&mut self, key: K, item: T) {
+        if let Some(held) = self.held.iter_mut().find(|held| held.key == key) {
+            held.item = item;
+            self.num_merged += 1;
+            return;
+        }
+        self.held.push_back(Held {
+            key,
+            due: self.clock.now() + self.window,
+            item,
+        });
+    }
+
+    /// Takes the items which are due, oldest first.
+    pub fn take_due(&mut self) -> Vec<T> {
+        let now = self.clock.now();
+        let num_due = self.held.iter().take_while(|held| held.due <= now).count();
+        self.held.drain(..num_due).map(|held| held.item).collect()
+    }
+
+    /// Takes every item held, oldest first.
+    pub fn take_all(&mut self) -> Vec<T> {
+        self.held.drain(..).map(|held| held.item).collect()
+    }
+
+    /// `None` with nothing held.
+    pub fn time_to_next_due(&self) -> Option<Duration> {
+        let now = self.clock.now();
+        self.held
+            .front()
+            .map(|held| held.due.saturating_duration_since(now))
+    }
+
+    /// How many items were dropped for later ones with the same key.
+    pub fn num_merged(&self) -> usize {
+        self.num_merged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+
+    fn coalescer() -> (Coalescer<u32, &'static str>, Arc<ManualClock>) {
+        let clock = Arc::new(ManualClock::new());
+        (
+            Coalescer::new(Duration::from_millis(100), clock.clone()),
+            clock,
+        )
+    }
+
+    #[test]
+    fn bursts_come_out_once_per_window() {
+        let (mut coalescer, clock) = coalescer();
+        assert_eq!(coalescer.time_to_next_due(), None);
+        coalescer.hold(1, "a1");
+        coalescer.hold(1, "a2");
+        clock.advance(Duration::from_millis(40));
+        coalescer.hold(2, "b1");
+        coalescer.hold(1, "a3");
+        assert_eq!(
+            coalescer.time_to_next_due(),
+            Some(Duration::from_millis(60))
+        );
+        assert!(coalescer.take_due().is_empty());
+
+        clock.advance(Duration::from_millis(60));
+        assert_eq!(coalescer.take_due(), vec!["a3"]);
+        coalescer.hold(1, "a4");
+        clock.advance(Duration::from_millis(40));
+        assert_eq!(coalescer.take_due(), vec!["b1"]);
+        assert_eq!(coalescer.take_all(), vec!["a4"]);
+        assert_eq!(coalescer.time_to_next_due(), None);
+        assert_eq!(coalescer.num_merged(), 2);
+    }
+
+    #[test]
+    fn zero_window_holds_nothing_back() {
+        let clock = Arc::new(ManualClock::new());
+        let mut coalescer = Coalescer::new(Duration::from_millis(0), clock);
+        coalescer.hold(1, "a1");
+        assert_eq!(coalescer.time_to_next_due(), Some(Duration::from_millis(0)));
+        assert_eq!(coalescer.take_due(), vec!["a1"]);
+    }
+}
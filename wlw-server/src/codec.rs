@@ -0,0 +1,490 @@
+//! The framing shared by the server and the hook DLL (see
+//! `wlw-hook-dll/HookEvent.h`).
+//!
+//! Every message is one frame: a 12-byte header holding the body length as a
+//! `u32`, the message kind as a `u16`, a `u16` of flags and a `u32` message
+//! id, then the body. The sender picks ids; an answer carries the id of the
+//! frame it answers. The server copes with several requests in flight on one
+//! connection and answers them in whatever order they finish, though the
+//! hook DLL sends one at a time and waits for its answer.
+//! Bodies are the kind's fields in order, packed: little-endian integers,
+//! booleans as one byte, rects as four `i32`s, and strings as a `u16` byte
+//! count followed by that much UTF-8. Receivers ignore bytes past the fields
+//! they know, so later versions may append fields to a kind. Bodies which
+//! carry more after a part that may grow put that part behind a `u16` byte
+//! count. Which kinds exist and what their fields are is up to
+//! `protocol.schema`.
+use crate::luauserdata::Rect;
+use std::cmp;
+use std::error;
+use std::fmt;
+
+pub const HEADER_SIZE: usize = 12;
+/// The largest frame either side sends, header included.
+pub const MAX_FRAME_SIZE: usize = 64 * 1024;
+/// A body-less frame of this kind answers a request without saying anything.
+pub const EMPTY_KIND: u16 = 0;
+/// Set by senders which wait for an answer.
+pub const FLAG_WANTS_REPLY: u16 = 1;
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    ShortFrame(usize),
+    LengthMismatch { declared: usize, actual: usize },
+    UnknownKind(u16),
+    ShortBody(u16),
+    FrameTooLarge(usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::ShortFrame(len) => write!(f, "{} byte frame is too short for a header", len),
+            Error::LengthMismatch { declared, actual } => write!(
+                f,
+                "Frame declares a {} byte body but has {} bytes",
+                declared, actual
+            ),
+            Error::UnknownKind(kind) => write!(f, "Unknown message kind {}", kind),
+            Error::ShortBody(kind) => write!(f, "Body of kind {} ends mid-field", kind),
+            Error::FrameTooLarge(len) => write!(
+                f,
+                "{} byte frame is larger than the largest of {} bytes",
+                len, MAX_FRAME_SIZE
+            ),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// A message which travels in frames.
+pub trait Message: Sized {
+    fn kind(&self) -> u16;
+
+    fn encode_body(&self, body: &mut Encoder);
+
+    /// Fails with `Error::UnknownKind` for kinds this type has no variant
+    /// for.
+    fn decode_body(kind: u16, body: &mut Decoder) -> Result<Self>;
+}
+
+/// Fields which travel inside other messages' bodies rather than as
+/// messages of their own.
+pub trait Fields: Sized {
+    fn encode_fields(&self, body: &mut Encoder);
+
+    fn decode_fields(body: &mut Decoder) -> Result<Self>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Header {
+    pub kind: u16,
+    pub flags: u16,
+    pub id: u32,
+}
+
+pub struct Encoder {
+    buffer: Vec<u8>,
+    /// How long `buffer` may grow before the part being written outgrows
+    /// its count.
+    limit: usize,
+}
+
+impl Encoder {
+    pub fn u8(&mut self, value: u8) {
+        self.buffer.push(value);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn i32(&mut self, value: i32) {
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn rect(&mut self, rect: Rect) {
+        self.i32(rect.left);
+        self.i32(rect.top);
+        self.i32(rect.right);
+        self.i32(rect.bottom);
+    }
+
+    /// Writes whatever `encode` writes behind a `u16` byte count, so that
+    /// fields appended to it later do not shift what follows. Strings in the
+    /// part are cut short so that it fits its count.
+    pub fn part(&mut self, encode: impl FnOnce(&mut Encoder)) {
+        let start = self.buffer.len();
+        self.u16(0);
+        let outer_limit = self.limit;
+        self.limit = cmp::min(outer_limit, self.buffer.len() + u16::MAX as usize);
+        encode(self);
+        self.limit = outer_limit;
+        // Only fixed-size fields can still overflow the count, and a part
+        // that long makes the frame too large to send anyway
+        let len = cmp::min(self.buffer.len() - start - 2, u16::MAX as usize) as u16;
+        self.buffer[start..start + 2].copy_from_slice(&len.to_le_bytes());
+    }
+
+    /// Cuts strings too long for their count, or for the part they are in, at
+    /// a character boundary.
+    pub fn str(&mut self, value: &str) {
+        let room = self.limit.saturating_sub(self.buffer.len() + 2);
+        let mut len = cmp::min(value.len(), cmp::min(room, u16::MAX as usize));
+        while !value.is_char_boundary(len) {
+            len -= 1;
+        }
+        self.u16(len as u16);
+        self.buffer.extend_from_slice(&value.as_bytes()[..len]);
+    }
+}
+
+pub struct Decoder<'a> {
+    kind: u16,
+    remaining: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.remaining.len() < len {
+            return Err(Error::ShortBody(self.kind));
+        }
+        let (taken, rest) = self.remaining.split_at(len);
+        self.remaining = rest;
+        Ok(taken)
+    }
+
+    pub fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    /// Any nonzero byte is true, as with a C `BOOL`.
+    pub fn bool(&mut self) -> Result<bool> {
+        Ok(self.u8()? != 0)
+    }
+
+    pub fn u16(&mut self) -> Result<u16> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn u32(&mut self) -> Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub fn i32(&mut self) -> Result<i32> {
+        Ok(self.u32()? as i32)
+    }
+
+    pub fn rect(&mut self) -> Result<Rect> {
+        Ok(Rect {
+            left: self.i32()?,
+            top: self.i32()?,
+            right: self.i32()?,
+            bottom: self.i32()?,
+        })
+    }
+
+    /// Reads a part written by `Encoder::part`, skipping whatever of it
+    /// the returned decoder leaves unread.
+    pub fn part(&mut self) -> Result<Decoder<'a>> {
+        let len = self.u16()? as usize;
+        Ok(Decoder {
+            kind: self.kind,
+            remaining: self.take(len)?,
+        })
+    }
+
+    /// Invalid UTF-8 is replaced rather than refused; hooked processes hand
+    /// over whatever their windows contain.
+    pub fn str(&mut self) -> Result<String> {
+        let len = self.u16()? as usize;
+        Ok(String::from_utf8_lossy(self.take(len)?).into_owned())
+    }
+}
+
+fn frame(kind: u16, flags: u16, id: u32, encode_body: impl FnOnce(&mut Encoder)) -> Vec<u8> {
+    let mut encoder = Encoder {
+        buffer: vec![0; HEADER_SIZE],
+        limit: usize::MAX,
+    };
+    encode_body(&mut encoder);
+    let mut buffer = encoder.buffer;
+    let len = (buffer.len() - HEADER_SIZE) as u32;
+    buffer[0..4].copy_from_slice(&len.to_le_bytes());
+    buffer[4..6].copy_from_slice(&kind.to_le_bytes());
+    buffer[6..8].copy_from_slice(&flags.to_le_bytes());
+    buffer[8..12].copy_from_slice(&id.to_le_bytes());
+    buffer
+}
+
+/// Frames `message` with id 0, for frames nobody will answer.
+pub fn encode(message: &impl Message, flags: u16) -> Result<Vec<u8>> {
+    encode_with_id(message, flags, 0)
+}
+
+/// Fails with `Error::FrameTooLarge` rather than make a frame the other
+/// side can't read.
+pub fn encode_with_id(message: &impl Message, flags: u16, id: u32) -> Result<Vec<u8>> {
+    let frame = frame(message.kind(), flags, id, |body| message.encode_body(body));
+    if frame.len() > MAX_FRAME_SIZE {
+        return Err(Error::FrameTooLarge(frame.len()));
+    }
+    Ok(frame)
+}
+
+/// Just the bytes `fields` take up inside a body.
+pub fn encode_fields(fields: &impl Fields) -> Vec<u8> {
+    frame(EMPTY_KIND, 0, 0, |body| fields.encode_fields(body)).split_off(HEADER_SIZE)
+}
+
+/// Answers the frame with id `id` without saying anything.
+pub fn empty_frame(id: u32) -> Vec<u8> {
+    frame(EMPTY_KIND, 0, id, |_| {})
+}
+
+/// Checks that `frame` is exactly one whole frame.
+pub fn decode_header(frame: &[u8]) -> Result<Header> {
+    if frame.len() < HEADER_SIZE {
+        return Err(Error::ShortFrame(frame.len()));
+    }
+    let declared = u32::from_le_bytes([frame[0], frame[1], frame[2], frame[3]]) as usize;
+    let actual = frame.len() - HEADER_SIZE;
+    if declared != actual {
+        return Err(Error::LengthMismatch { declared, actual });
+    }
+    Ok(Header {
+        kind: u16::from_le_bytes([frame[4], frame[5]]),
+        flags: u16::from_le_bytes([frame[6], frame[7]]),
+        id: u32::from_le_bytes([frame[8], frame[9], frame[10], frame[11]]),
+    })
+}
+
+pub fn decode<M: Message>(frame: &[u8]) -> Result<M> {
+    let header = decode_header(frame)?;
+    M::decode_body(
+        header.kind,
+        &mut Decoder {
+            kind: header.kind,
+            remaining: &frame[HEADER_SIZE..],
+        },
+    )
+}
+
+/// Reads fields from bytes laid out by `encode_fields`.
+pub fn decode_fields<F: Fields>(bytes: &[u8]) -> Result<F> {
+    F::decode_fields(&mut Decoder {
+        kind: EMPTY_KIND,
+        remaining: bytes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Toy messages, which the pipe server tests send too
+    impl Message for u32 {
+        fn kind(&self) -> u16 {
+            1
+        }
+
+        fn encode_body(&self, body: &mut Encoder) {
+            body.u32(*self);
+        }
+
+        fn decode_body(kind: u16, body: &mut Decoder) -> Result<Self> {
+            match kind {
+                1 => body.u32(),
+                _ => Err(Error::UnknownKind(kind)),
+            }
+        }
+    }
+
+    impl Message for [u8; 4] {
+        fn kind(&self) -> u16 {
+            2
+        }
+
+        fn encode_body(&self, body: &mut Encoder) {
+            for &byte in self {
+                body.u8(byte);
+            }
+        }
+
+        fn decode_body(kind: u16, body: &mut Decoder) -> Result<Self> {
+            match kind {
+                2 => Ok([body.u8()?, body.u8()?, body.u8()?, body.u8()?]),
+                _ => Err(Error::UnknownKind(kind)),
+            }
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Everything {
+        flag: bool,
+        rect: Rect,
+        name: String,
+        count: u16,
+    }
+
+    impl Message for Everything {
+        fn kind(&self) -> u16 {
+            7
+        }
+
+        fn encode_body(&self, body: &mut Encoder) {
+            body.bool(self.flag);
+            body.rect(self.rect);
+            body.str(&self.name);
+            body.u16(self.count);
+        }
+
+        fn decode_body(kind: u16, body: &mut Decoder) -> Result<Self> {
+            match kind {
+                7 => Ok(Everything {
+                    flag: body.bool()?,
+                    rect: body.rect()?,
+                    name: body.str()?,
+                    count: body.u16()?,
+                }),
+                _ => Err(Error::UnknownKind(kind)),
+            }
+        }
+    }
+
+    fn everything(name: &str) -> Everything {
+        Everything {
+            flag: true,
+            rect: Rect::new(-1, 2, 300, 400),
+            name: name.to_owned(),
+            count: 9,
+        }
+    }
+
+    #[test]
+    fn frames_round_trip() {
+        let message = everything("Ünïcode");
+        let frame = encode_with_id(&message, FLAG_WANTS_REPLY, 0xdead_beef).unwrap();
+        assert_eq!(
+            decode_header(&frame),
+            Ok(Header {
+                kind: 7,
+                flags: FLAG_WANTS_REPLY,
+                id: 0xdead_beef
+            })
+        );
+        assert_eq!(decode(&frame), Ok(message));
+    }
+
+    #[test]
+    fn frames_match_c_layout() {
+        let frame = encode_with_id(&0x0102_0304u32, FLAG_WANTS_REPLY, 0x0506_0708).unwrap();
+        assert_eq!(frame, [4, 0, 0, 0, 1, 0, 1, 0, 8, 7, 6, 5, 4, 3, 2, 1]);
+        assert_eq!(empty_frame(9), [0, 0, 0, 0, 0, 0, 0, 0, 9, 0, 0, 0]);
+
+        let frame = encode(&everything("ab"), 0).unwrap();
+        let mut body = vec![1];
+        for value in &[-1i32, 2, 300, 400] {
+            body.extend_from_slice(&value.to_le_bytes());
+        }
+        body.extend_from_slice(&[2, 0, b'a', b'b', 9, 0]);
+        assert_eq!(
+            &frame[..HEADER_SIZE],
+            &[body.len() as u8, 0, 0, 0, 7, 0, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(&frame[HEADER_SIZE..], &body[..]);
+    }
+
+    #[test]
+    fn bad_frames_are_refused() {
+        assert_eq!(
+            decode::<u32>(&[4, 0, 0, 0, 1, 0, 0, 0]),
+            Err(Error::ShortFrame(8))
+        );
+        assert_eq!(
+            decode::<u32>(&[5, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1, 2, 3, 4]),
+            Err(Error::LengthMismatch {
+                declared: 5,
+                actual: 4
+            })
+        );
+        assert_eq!(
+            decode::<u32>(&[2, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1, 2]),
+            Err(Error::ShortBody(1))
+        );
+        assert_eq!(
+            decode::<u32>(&encode(&[1u8, 2, 3, 4], 0).unwrap()),
+            Err(Error::UnknownKind(2))
+        );
+    }
+
+    #[test]
+    fn appended_fields_are_ignored() {
+        let mut frame = encode(&7u32, 0).unwrap();
+        frame.extend_from_slice(&[0xff; 3]);
+        frame[0] += 3;
+        assert_eq!(decode(&frame), Ok(7u32));
+    }
+
+    #[test]
+    fn strings_are_cut_and_repaired() {
+        // Strings this long don't fit a frame `encode` would send, so go
+        // around it
+        let long = "é".repeat(u16::MAX as usize);
+        let frame = frame(7, 0, 0, |body| everything(&long).encode_body(body));
+        let decoded: Everything = decode(&frame).unwrap();
+        assert_eq!(decoded.name, "é".repeat(u16::MAX as usize / 2));
+
+        let mut frame = encode(&everything("a.exe"), 0).unwrap();
+        frame[HEADER_SIZE + 1 + 16 + 2] = 0xff;
+        let decoded: Everything = decode(&frame).unwrap();
+        assert_eq!(decoded.name, "\u{fffd}.exe");
+    }
+
+    #[test]
+    fn parts_cut_strings_to_fit_their_count() {
+        let long = "a".repeat(u16::MAX as usize);
+        let frame = frame(1, 0, 0, |body| {
+            body.part(|part| {
+                part.u32(5);
+                part.str(&long);
+            });
+            body.u8(7);
+        });
+        let mut body = Decoder {
+            kind: 1,
+            remaining: &frame[HEADER_SIZE..],
+        };
+        let mut part = body.part().unwrap();
+        assert_eq!(part.u32(), Ok(5));
+        assert_eq!(part.str().unwrap().len(), u16::MAX as usize - 4 - 2);
+        assert_eq!(body.u8(), Ok(7));
+    }
+
+    #[test]
+    fn frames_too_large_to_read_are_refused() {
+        let largest = "a".repeat(MAX_FRAME_SIZE - HEADER_SIZE - 1 - 16 - 2 - 2);
+        assert_eq!(
+            encode(&everything(&largest), 0).map(|frame| frame.len()),
+            Ok(MAX_FRAME_SIZE)
+        );
+        let larger = format!("{}a", largest);
+        assert_eq!(
+            encode(&everything(&larger), 0),
+            Err(Error::FrameTooLarge(MAX_FRAME_SIZE + 1))
+        );
+    }
+}
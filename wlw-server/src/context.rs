@@ -0,0 +1,1422 @@
+use crate::backend::{self, WindowBackend, WindowId};
+use crate::clock::{Clock, SystemClock};
+use crate::coalescer::Coalescer;
+use crate::hookevent::{Hello, HookEvent, HookResponse, WindowEvent, WindowInfo};
+#[cfg(windows)]
+use crate::hookmanager::HookManager;
+use crate::luauserdata::{self, Rect, WindowFilter, WindowHandle};
+use crate::pipeserver::{self, Deadline, PipeServer};
+use crate::recording::Recorder;
+use crossbeam_channel as xchan;
+use dirs;
+use rlua::{FromLua, ToLua};
+use std::error;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How long hooked applications wait on Lua before carrying on as if it had
+/// left their windows alone.
+pub const DEFAULT_RESPONSE_DEADLINE: Duration = Duration::from_millis(250);
+
+/// How long notifications which a later one can make moot, such as where a
+/// window moved to, are held back for the later one to come.
+pub const DEFAULT_COALESCE_WINDOW: Duration = Duration::from_millis(20);
+
+#[derive(Debug)]
+pub enum Error {
+    PipeServerInit(pipeserver::Error),
+    PipeServerFail(pipeserver::Error),
+    BackendFail(backend::Error),
+    LuaScriptOpen(io::Error),
+    LuaInit(rlua::Error),
+    LuaCallback(rlua::Error),
+    Record(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::PipeServerInit(e) => write!(f, "Error creating pipe server: {}", e),
+            Error::PipeServerFail(e) => write!(f, "Error in pipe server: {}", e),
+            Error::BackendFail(e) => write!(f, "Error in window backend: {}", e),
+            Error::LuaScriptOpen(e) => write!(f, "Error reading Lua script: {}", e),
+            Error::LuaInit(e) => write!(f, "Error initializing Lua context: {}", e),
+            Error::LuaCallback(e) => write!(f, "Error running Lua callback: {}", e),
+            Error::Record(e) => write!(f, "Error writing recording: {}", e),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+pub enum Event {
+    Interrupt,
+    NewRequest(pipeserver::Request<WindowEvent, HookResponse>),
+    PipeServerFail(pipeserver::Error),
+    /// A hook event from a backend which observes windows in-process, and
+    /// where to send the response.
+    Hook(HookEvent, xchan::Sender<Option<HookResponse>>),
+    BackendFail(backend::Error),
+}
+
+fn accept_hello(hello: Hello) -> bool {
+    match hello.check_version() {
+        Ok(()) => {
+            debug!(
+                "Hook connected from {} (pid {}, {}-bit)",
+                hello.image_name, hello.pid, hello.pointer_width
+            );
+            true
+        }
+        Err(e) => {
+            warn!("Rejecting hook: {}", e);
+            false
+        }
+    }
+}
+
+/// What a hook waiting on `event` gets when Lua takes too long: the rect it
+/// asked about, unchanged, or nothing, which lets the event go ahead.
+fn fallback_response(event: &WindowEvent) -> Option<HookResponse> {
+    match event.event {
+        HookEvent::CbtCreateWindow { rect, .. } | HookEvent::CbtMoveSize { rect, .. } => {
+            Some(HookResponse::from_rect(rect))
+        }
+        _ => None,
+    }
+}
+
+/// What a callback which may veto its event returned: `false` vetoes it,
+/// and anything else is the callback's answer.
+enum Vetoable<T> {
+    Vetoed,
+    Allowed(T),
+}
+
+impl<'lua, T: rlua::FromLua<'lua>> rlua::FromLua<'lua> for Vetoable<T> {
+    fn from_lua(value: rlua::Value<'lua>, lua_ctx: rlua::Context<'lua>) -> rlua::Result<Self> {
+        match value {
+            rlua::Value::Boolean(false) => Ok(Vetoable::Vetoed),
+            value => T::from_lua(value, lua_ctx).map(Vetoable::Allowed),
+        }
+    }
+}
+
+impl<'lua> Vetoable<rlua::Value<'lua>> {
+    fn into_response(self) -> Option<HookResponse> {
+        match self {
+            Vetoable::Vetoed => Some(HookResponse::deny()),
+            Vetoable::Allowed(_) => None,
+        }
+    }
+
+    /// Reads `callback`'s answer as a rect. An answer which isn't one is
+    /// logged and leaves the rect unchanged, rather than stopping the server.
+    fn into_rect(self, lua_ctx: rlua::Context<'lua>, callback: &str) -> Vetoable<Option<Rect>> {
+        match self {
+            Vetoable::Vetoed => Vetoable::Vetoed,
+            Vetoable::Allowed(value) => match Option::<Rect>::from_lua(value, lua_ctx) {
+                Ok(rect) => Vetoable::Allowed(rect),
+                Err(e) => {
+                    warn!("Ignoring the answer of {}: {}", callback, e);
+                    Vetoable::Allowed(None)
+                }
+            },
+        }
+    }
+}
+
+/// No rect, which is what nil gives, leaves `unchanged` as it was.
+impl Vetoable<Option<Rect>> {
+    fn into_response(self, unchanged: Rect) -> Option<HookResponse> {
+        Some(match self {
+            Vetoable::Vetoed => HookResponse::deny(),
+            Vetoable::Allowed(rect) => HookResponse::from_rect(rect.unwrap_or(unchanged)),
+        })
+    }
+}
+
+/// `wlw.windows`, which lists the windows Lua has handles for, in id order.
+/// Takes an optional table of filters.
+fn windows_function(lua_ctx: rlua::Context) -> rlua::Result<rlua::Function> {
+    lua_ctx.create_function(|lua_ctx, filter: Option<rlua::Table>| {
+        let filter = WindowFilter::from_lua_table(filter)?;
+        let window_table: rlua::Table = lua_ctx.named_registry_value(luauserdata::WINDOW_TABLE)?;
+        let mut windows = Vec::new();
+        for pair in window_table.pairs::<WindowId, rlua::AnyUserData>() {
+            let (id, window_handle) = pair?;
+            if filter.matches(lua_ctx, &*window_handle.borrow::<WindowHandle>()?)? {
+                windows.push((id, window_handle));
+            }
+        }
+        windows.sort_by_key(|&(id, _)| id);
+        Ok(windows
+            .into_iter()
+            .map(|(_, window_handle)| window_handle)
+            .collect::<Vec<_>>())
+    })
+}
+
+/// Hands a hook event to the context taking events from `sender` and waits
+/// for its response. Gives no response once the context has stopped.
+pub fn forward_hook_event(sender: &xchan::Sender<Event>, event: HookEvent) -> Option<HookResponse> {
+    let (response_sender, response_receiver) = xchan::bounded(1);
+    sender.send(Event::Hook(event, response_sender)).ok()?;
+    response_receiver.recv().ok()?
+}
+
+pub struct Context {
+    lua: rlua::Lua,
+    backend: Arc<dyn WindowBackend>,
+    _pipe_server: Option<PipeServer<Hello, WindowEvent, HookResponse>>,
+    #[cfg(windows)]
+    _hook_manager: Option<HookManager>,
+    event_sender: xchan::Sender<Event>,
+    event_receiver: xchan::Receiver<Event>,
+    /// Notifications held back in case later ones make them moot.
+    held: Coalescer<WindowId, pipeserver::Request<WindowEvent, HookResponse>>,
+    recorder: Option<Recorder>,
+}
+
+/// `~/wlw.lua`, the script the server runs unless told otherwise.
+pub fn default_script_path() -> PathBuf {
+    let mut script_path = dirs::home_dir().unwrap();
+    script_path.push("wlw.lua");
+    script_path
+}
+
+/// The pipe the hook DLL connects to.
+pub fn default_pipe_name() -> String {
+    format!("wlw_server_{}", process::id())
+}
+
+pub fn read_script(path: impl AsRef<Path>) -> Result<String, Error> {
+    let mut script_file = File::open(path).map_err(Error::LuaScriptOpen)?;
+    let mut script_content = String::new();
+    script_file
+        .read_to_string(&mut script_content)
+        .map_err(Error::LuaScriptOpen)?;
+    Ok(script_content)
+}
+
+enum Script {
+    Source(String),
+    Path(PathBuf),
+}
+
+/// Assembles a `Context`. By default it runs `~/wlw.lua`, makes its own event
+/// channel, and touches nothing outside the process: the pipe server and the
+/// hook processes only start when asked for.
+pub struct ContextBuilder {
+    script: Script,
+    backend: Arc<dyn WindowBackend>,
+    events: Option<(xchan::Sender<Event>, xchan::Receiver<Event>)>,
+    pipe_name: Option<String>,
+    response_deadline: Duration,
+    coalesce_window: Duration,
+    clock: Arc<dyn Clock>,
+    #[cfg(windows)]
+    supervise_hooks: bool,
+    recorder: Option<Recorder>,
+}
+
+impl ContextBuilder {
+    pub fn new(backend: Arc<dyn WindowBackend>) -> Self {
+        ContextBuilder {
+            script: Script::Path(default_script_path()),
+            backend,
+            events: None,
+            pipe_name: None,
+            response_deadline: DEFAULT_RESPONSE_DEADLINE,
+            coalesce_window: DEFAULT_COALESCE_WINDOW,
+            clock: Arc::new(SystemClock),
+            #[cfg(windows)]
+            supervise_hooks: false,
+            recorder: None,
+        }
+    }
+
+    pub fn script_source(mut self, source: impl Into<String>) -> Self {
+        self.script = Script::Source(source.into());
+        self
+    }
+
+    pub fn script_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.script = Script::Path(path.into());
+        self
+    }
+
+    /// The channel `Context::run` takes events from. Keep the sender to
+    /// interrupt it.
+    pub fn events(
+        mut self,
+        sender: xchan::Sender<Event>,
+        receiver: xchan::Receiver<Event>,
+    ) -> Self {
+        self.events = Some((sender, receiver));
+        self
+    }
+
+    /// Serves hook events on the pipe named `name`.
+    pub fn pipe_server(mut self, name: impl Into<String>) -> Self {
+        self.pipe_name = Some(name.into());
+        self
+    }
+
+    /// How long the pipe server waits on a response before answering hooks
+    /// itself, instead of `DEFAULT_RESPONSE_DEADLINE`.
+    pub fn response_deadline(mut self, timeout: Duration) -> Self {
+        self.response_deadline = timeout;
+        self
+    }
+
+    /// How long notifications are held back for later ones which make them
+    /// moot, instead of `DEFAULT_COALESCE_WINDOW`. Zero holds nothing back.
+    pub fn coalesce_window(mut self, window: Duration) -> Self {
+        self.coalesce_window = window;
+        self
+    }
+
+    /// Where response deadlines and coalesce windows are measured from,
+    /// instead of the system clock.
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Starts the hook processes, which only ever connect to the pipe named
+    /// by `default_pipe_name`, and serves that pipe.
+    #[cfg(windows)]
+    pub fn supervise_hooks(mut self) -> Self {
+        self.supervise_hooks = true;
+        self.pipe_server(default_pipe_name())
+    }
+
+    /// Records every hook event handled, along with the window's title and
+    /// the response.
+    pub fn recorder(mut self, recorder: Recorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    pub fn build(self) -> Result<Context, Error> {
+        let script = match self.script {
+            Script::Source(source) => source,
+            Script::Path(path) => read_script(path)?,
+        };
+        let (es, er) = self.events.unwrap_or_else(xchan::unbounded);
+
+        trace!("Creating Lua context");
+        let lua = rlua::Lua::new();
+        lua.context(move |lua_ctx| {
+            let globals = lua_ctx.globals();
+            let wlw = lua_ctx.create_table()?;
+            wlw.set("windows", windows_function(lua_ctx)?)?;
+            wlw.set("rect", luauserdata::rect_function(lua_ctx)?)?;
+            globals.set("wlw", wlw)?;
+            lua_ctx.set_named_registry_value(luauserdata::WINDOW_TABLE, lua_ctx.create_table()?)?;
+            lua_ctx.load(&script).exec()
+        })
+        .map_err(Error::LuaInit)?;
+
+        let pipe_server = match self.pipe_name {
+            Some(pipe_name) => {
+                let pipe_server_req_es = es.clone();
+                let pipe_server_fail_es = es.clone();
+                trace!("Creating pipe server");
+                Some(
+                    PipeServer::with_deadline(
+                        pipe_name,
+                        Deadline {
+                            timeout: self.response_deadline,
+                            fallback: Box::new(fallback_response),
+                            clock: self.clock.clone(),
+                        },
+                        accept_hello,
+                        move |req| pipe_server_req_es.send(Event::NewRequest(req)).unwrap(),
+                        move |e| pipe_server_fail_es.send(Event::PipeServerFail(e)).unwrap(),
+                    )
+                    .map_err(Error::PipeServerInit)?,
+                )
+            }
+            None => None,
+        };
+        #[cfg(windows)]
+        let hook_manager = if self.supervise_hooks {
+            trace!("Creating hook manager");
+            Some(HookManager::new())
+        } else {
+            None
+        };
+
+        Ok(Context {
+            lua,
+            backend: self.backend,
+            _pipe_server: pipe_server,
+            #[cfg(windows)]
+            _hook_manager: hook_manager,
+            event_sender: es,
+            event_receiver: er,
+            held: Coalescer::new(self.coalesce_window, self.clock),
+            recorder: self.recorder,
+        })
+    }
+}
+
+impl Context {
+    pub fn event_sender(&self) -> xchan::Sender<Event> {
+        self.event_sender.clone()
+    }
+
+    /// How many hook requests were answered without waiting for Lua.
+    pub fn num_timeouts(&self) -> usize {
+        self._pipe_server
+            .as_ref()
+            .map_or(0, |pipe_server| pipe_server.num_timeouts())
+    }
+
+    /// How many notifications were dropped for later ones which made them
+    /// moot.
+    pub fn num_coalesced(&self) -> usize {
+        self.held.num_merged()
+    }
+
+    /// Gives Lua handles to the windows which were open before the hooks
+    /// were, front to back, and runs `wlw.on_window_adopt` for each.
+    pub fn adopt_windows(&mut self) -> Result<(), Error> {
+        let hwnds = self.backend.list_windows().map_err(Error::BackendFail)?;
+        trace!("Adopting {} windows", hwnds.len());
+        self.lua.context(|lua_ctx| {
+            for hwnd in hwnds {
+                let window_handle = self.get_window_handle(lua_ctx, hwnd)?;
+                Context::run_lua_callback(lua_ctx, "on_window_adopt", window_handle, rlua::Nil)?;
+            }
+            Ok(())
+        })
+    }
+
+    pub fn run(&mut self) -> Result<(), Error> {
+        trace!("Entering event loop");
+        loop {
+            // Held notifications wake the loop once they are due
+            let event = match self.held.time_to_next_due() {
+                Some(timeout) => self.event_receiver.recv_timeout(timeout).ok(),
+                None => Some(self.event_receiver.recv().unwrap()),
+            };
+            if let Some(event) = event {
+                if !self.handle_event(event)? {
+                    break;
+                }
+            }
+            self.dispatch_held_notifications(false)?;
+        }
+        Ok(())
+    }
+
+    /// Handles one event, giving `false` once it is time to stop.
+    fn handle_event(&mut self, event: Event) -> Result<bool, Error> {
+        match event {
+            Event::Interrupt => return Ok(false),
+            Event::NewRequest(req)
+                if !req.wants_reply() && req.message.event.is_superseded_by_later() =>
+            {
+                self.held.hold(req.message.event.hwnd(), req);
+            }
+            Event::NewRequest(req) => {
+                // Lua sees events in the order they came
+                self.dispatch_held_notifications(true)?;
+                match self.handle_window_event(&req.message)? {
+                    Some(r) => req.respond(r),
+                    None => req.acknowledge(),
+                }
+            }
+            Event::PipeServerFail(e) => return Err(Error::PipeServerFail(e)),
+            Event::Hook(event, response_sender) => {
+                self.dispatch_held_notifications(true)?;
+                let response = self.handle_hook_event(event)?;
+                // The backend may have given up waiting
+                response_sender.send(response).ok();
+            }
+            Event::BackendFail(e) => return Err(Error::BackendFail(e)),
+        }
+        Ok(true)
+    }
+
+    /// Handles the notifications held back which are due, or all of them.
+    fn dispatch_held_notifications(&mut self, all: bool) -> Result<(), Error> {
+        let requests = if all {
+            self.held.take_all()
+        } else {
+            self.held.take_due()
+        };
+        for req in requests {
+            self.handle_window_event(&req.message)?;
+        }
+        Ok(())
+    }
+
+    /// Handles an event from a hook, first noting what the hook said about
+    /// the window on its handle.
+    pub fn handle_window_event(
+        &mut self,
+        event: &WindowEvent,
+    ) -> Result<Option<HookResponse>, Error> {
+        let hwnd = event.event.hwnd();
+        self.lua.context(|lua_ctx| {
+            // Destroyed windows only get handles if Lua already knew them
+            let window_handle = match event.event {
+                HookEvent::CbtDestroyWindow { .. } => self.find_window_handle(lua_ctx, hwnd)?,
+                _ => Some(self.get_window_handle(lua_ctx, hwnd)?),
+            };
+            if let Some(window_handle) = window_handle {
+                window_handle
+                    .borrow_mut::<WindowHandle>()
+                    .map_err(Error::LuaCallback)?
+                    .set_info(event.window.clone());
+            }
+            Ok(())
+        })?;
+        self.record_hook_event(event.event.clone(), Some(&event.window))
+    }
+
+    pub fn handle_hook_event(&mut self, event: HookEvent) -> Result<Option<HookResponse>, Error> {
+        self.record_hook_event(event, None)
+    }
+
+    /// Dispatches `event`, recording it if there is a recorder. Events which
+    /// came without a WindowInfo get the backend's.
+    fn record_hook_event(
+        &mut self,
+        event: HookEvent,
+        window: Option<&WindowInfo>,
+    ) -> Result<Option<HookResponse>, Error> {
+        if self.recorder.is_none() {
+            return self.dispatch_hook_event(event);
+        }
+        // Titles are read first since a destroyed window has none after, and
+        // title changes record the title being set
+        let title = match &event {
+            HookEvent::CwpSetText { title, .. } => title.clone(),
+            _ => self.backend.get_title(event.hwnd()).unwrap_or_default(),
+        };
+        let window = match window {
+            Some(window) => window.clone(),
+            None => self
+                .backend
+                .get_window_info(event.hwnd())
+                .unwrap_or_default(),
+        };
+        let response = self.dispatch_hook_event(event.clone())?;
+        self.recorder
+            .as_mut()
+            .unwrap()
+            .record(event, window, title, response)
+            .map_err(Error::Record)?;
+        Ok(response)
+    }
+
+    fn dispatch_hook_event(&mut self, event: HookEvent) -> Result<Option<HookResponse>, Error> {
+        match event {
+            HookEvent::CwpShowWindow { hwnd, shown } => {
+                self.lua.context(|lua_ctx| {
+                    let window_handle = self.get_window_handle(lua_ctx, hwnd)?;
+                    Context::run_lua_callback(
+                        lua_ctx,
+                        "on_window_show",
+                        (window_handle, shown),
+                        rlua::Nil,
+                    )?;
+                    Ok(())
+                })?;
+                Ok(None)
+            }
+            HookEvent::CbtActivate {
+                hwnd,
+                caused_by_mouse,
+            } => self.lua.context(|lua_ctx| {
+                let window_handle = self.get_window_handle(lua_ctx, hwnd)?;
+                let verdict: Vetoable<rlua::Value> = Context::run_lua_callback(
+                    lua_ctx,
+                    "on_window_activate",
+                    (window_handle, caused_by_mouse),
+                    Vetoable::Allowed(rlua::Nil),
+                )?;
+                Ok(verdict.into_response())
+            }),
+            HookEvent::CbtCreateWindow { hwnd, rect } => self.lua.context(|lua_ctx| {
+                let window_handle = self.get_window_handle(lua_ctx, hwnd)?;
+                let verdict: Vetoable<rlua::Value> = Context::run_lua_callback(
+                    lua_ctx,
+                    "on_window_create",
+                    (window_handle, rect),
+                    Vetoable::Allowed(rlua::Nil),
+                )?;
+                Ok(verdict
+                    .into_rect(lua_ctx, "on_window_create")
+                    .into_response(rect))
+            }),
+            HookEvent::CbtDestroyWindow { hwnd } => {
+                self.lua
+                    .context(|lua_ctx| match self.delete_window_handle(lua_ctx, hwnd) {
+                        Ok(window_handle) => {
+                            Context::run_lua_callback(
+                                lua_ctx,
+                                "on_window_destroy",
+                                window_handle.clone(),
+                                rlua::Nil,
+                            )?;
+                            // Only once the script has seen them one last time
+                            luauserdata::clear_window_fields(&window_handle)
+                                .map_err(Error::LuaCallback)?;
+                            Ok(())
+                        }
+                        Err(_) => Ok(()),
+                    })?;
+                Ok(None)
+            }
+            HookEvent::CbtMinMax { hwnd, show_command } => self.lua.context(|lua_ctx| {
+                let window_handle = self.get_window_handle(lua_ctx, hwnd)?;
+                // Hooks pass on whatever the application asked for, which
+                // may not be a command we know
+                let command = match luauserdata::show_command_to_str(show_command) {
+                    Some(name) => name.to_lua(lua_ctx),
+                    None => show_command.to_lua(lua_ctx),
+                }
+                .map_err(Error::LuaCallback)?;
+                let verdict: Vetoable<rlua::Value> = Context::run_lua_callback(
+                    lua_ctx,
+                    "on_window_min_max",
+                    (window_handle, command),
+                    Vetoable::Allowed(rlua::Nil),
+                )?;
+                Ok(verdict.into_response())
+            }),
+            HookEvent::CbtMoveSize { hwnd, rect } => self.lua.context(|lua_ctx| {
+                let window_handle = self.get_window_handle(lua_ctx, hwnd)?;
+                let verdict: Vetoable<rlua::Value> = Context::run_lua_callback(
+                    lua_ctx,
+                    "on_window_move_resize",
+                    (window_handle, rect),
+                    Vetoable::Allowed(rlua::Nil),
+                )?;
+                Ok(verdict
+                    .into_rect(lua_ctx, "on_window_move_resize")
+                    .into_response(rect))
+            }),
+            HookEvent::CwpSetText { hwnd, title } => {
+                self.lua.context(|lua_ctx| {
+                    let window_handle = self.get_window_handle(lua_ctx, hwnd)?;
+                    Context::run_lua_callback(
+                        lua_ctx,
+                        "on_window_title_change",
+                        (window_handle, title),
+                        rlua::Nil,
+                    )?;
+                    Ok(())
+                })?;
+                Ok(None)
+            }
+            HookEvent::CbtSetFocus { hwnd } => {
+                self.lua.context(|lua_ctx| {
+                    let window_handle = self.get_window_handle(lua_ctx, hwnd)?;
+                    Context::run_lua_callback(
+                        lua_ctx,
+                        "on_window_focus",
+                        window_handle,
+                        rlua::Nil,
+                    )?;
+                    Ok(())
+                })?;
+                Ok(None)
+            }
+            HookEvent::CbtSysCommand { hwnd, command } => {
+                self.lua.context(|lua_ctx| {
+                    let window_handle = self.get_window_handle(lua_ctx, hwnd)?;
+                    // Commands applications add to the window menu have no name
+                    let command = match luauserdata::syscommand_to_str(command) {
+                        Some(name) => name.to_lua(lua_ctx),
+                        None => command.to_lua(lua_ctx),
+                    }
+                    .map_err(Error::LuaCallback)?;
+                    Context::run_lua_callback(
+                        lua_ctx,
+                        "on_window_syscommand",
+                        (window_handle, command),
+                        rlua::Nil,
+                    )?;
+                    Ok(())
+                })?;
+                Ok(None)
+            }
+            HookEvent::CwpWindowPosChanged { hwnd, rect } => {
+                self.lua.context(|lua_ctx| {
+                    let window_handle = self.get_window_handle(lua_ctx, hwnd)?;
+                    Context::run_lua_callback(
+                        lua_ctx,
+                        "on_window_moved",
+                        (window_handle, rect),
+                        rlua::Nil,
+                    )?;
+                    Ok(())
+                })?;
+                Ok(None)
+            }
+        }
+    }
+
+    fn get_window_handle<'lua>(
+        &self,
+        lua_ctx: rlua::Context<'lua>,
+        hwnd: WindowId,
+    ) -> Result<rlua::AnyUserData<'lua>, Error> {
+        luauserdata::get_window_handle(lua_ctx, &self.backend, hwnd).map_err(Error::LuaCallback)
+    }
+
+    fn find_window_handle<'lua>(
+        &self,
+        lua_ctx: rlua::Context<'lua>,
+        hwnd: WindowId,
+    ) -> Result<Option<rlua::AnyUserData<'lua>>, Error> {
+        let window_table: rlua::Table = lua_ctx
+            .named_registry_value(luauserdata::WINDOW_TABLE)
+            .map_err(Error::LuaCallback)?;
+        window_table.get(hwnd).map_err(Error::LuaCallback)
+    }
+
+    fn delete_window_handle<'lua>(
+        &self,
+        lua_ctx: rlua::Context<'lua>,
+        hwnd: WindowId,
+    ) -> Result<rlua::AnyUserData<'lua>, Error> {
+        let window_table: rlua::Table = lua_ctx
+            .named_registry_value(luauserdata::WINDOW_TABLE)
+            .map_err(Error::LuaCallback)?;
+        let handle = window_table.get(hwnd).map_err(Error::LuaCallback)?;
+        window_table
+            .set(hwnd, rlua::Nil)
+            .map_err(Error::LuaCallback)?;
+        Ok(handle)
+    }
+
+    fn run_lua_callback<'lua, T: rlua::FromLua<'lua>>(
+        lua_ctx: rlua::Context<'lua>,
+        name: &str,
+        args: impl rlua::ToLuaMulti<'lua>,
+        default: T,
+    ) -> Result<T, Error> {
+        let globals = lua_ctx.globals();
+        let wlw: rlua::Table = globals.get("wlw").map_err(Error::LuaCallback)?;
+        if let Ok(func) = wlw.get::<_, rlua::Function>(name) {
+            func.call::<_, T>(args).map_err(Error::LuaCallback)
+        } else {
+            Ok(default)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::simulated::{SimulatedBackend, SimulatedWindow};
+    use crate::backend::SW_MAXIMIZE;
+    use crate::clock::ManualClock;
+    use crate::luauserdata::Rect;
+    use std::env;
+    use std::thread;
+
+    const SCRIPT: &str = r#"
+        events = {}
+        function wlw.on_window_create(w, rect)
+            rect.x = 0
+            rect.y = 0
+            rect.width = 960
+            rect.height = 1040
+            return rect
+        end
+        function wlw.on_window_show(w, shown)
+            table.insert(events, "show " .. w.title)
+        end
+        function wlw.on_window_activate(w, caused_by_mouse)
+            table.insert(events, "activate " .. w.title)
+        end
+        function wlw.on_window_min_max(w, command)
+            table.insert(events, command .. " " .. w.title)
+        end
+        function wlw.on_window_move_resize(w, rect)
+            rect.width = math.min(rect.width, 800)
+            return rect
+        end
+        function wlw.on_window_destroy(w)
+            table.insert(events, "destroy " .. w.title)
+        end
+    "#;
+
+    #[test]
+    fn script_drives_simulated_desktop() {
+        let backend = Arc::new(SimulatedBackend::new());
+        let mut context = ContextBuilder::new(backend.clone())
+            .script_source(SCRIPT)
+            .build()
+            .unwrap();
+        let mut hook = |event| context.handle_hook_event(event).unwrap();
+
+        let id = backend.open_window(
+            SimulatedWindow::new("Firefox", Rect::new(100, 100, 900, 700)),
+            &mut hook,
+        );
+        assert_eq!(
+            backend.get_window_rect(id).unwrap(),
+            Rect::new(0, 0, 960, 1040)
+        );
+        backend
+            .move_size_window(id, Rect::new(10, 10, 1210, 610), &mut hook)
+            .unwrap();
+        assert_eq!(
+            backend.get_window_rect(id).unwrap(),
+            Rect::new(10, 10, 810, 610)
+        );
+        backend.min_max_window(id, SW_MAXIMIZE, &mut hook).unwrap();
+        backend.close_window(id, &mut hook).unwrap();
+
+        let events: Vec<String> = context
+            .lua
+            .context(|lua_ctx| lua_ctx.globals().get("events"))
+            .unwrap();
+        assert_eq!(
+            events,
+            vec![
+                "show Firefox",
+                "activate Firefox",
+                "maximize Firefox",
+                "destroy Firefox"
+            ]
+        );
+    }
+
+    #[test]
+    fn builds_without_side_effects() {
+        let missing = env::temp_dir().join("wlw_test_missing_script.lua");
+        match ContextBuilder::new(Arc::new(SimulatedBackend::new()))
+            .script_path(&missing)
+            .build()
+        {
+            Err(Error::LuaScriptOpen(_)) => {}
+            _ => panic!("expected a script open error"),
+        }
+
+        let mut context = ContextBuilder::new(Arc::new(SimulatedBackend::new()))
+            .script_source("")
+            .build()
+            .unwrap();
+        assert!(context._pipe_server.is_none());
+        context.event_sender().send(Event::Interrupt).unwrap();
+        context.run().unwrap();
+    }
+
+    #[test]
+    fn runs_forwarded_hook_events() {
+        let backend = Arc::new(SimulatedBackend::new());
+        let id = backend.create_window(SimulatedWindow::new("Firefox", Rect::new(0, 0, 10, 10)));
+        let mut context = ContextBuilder::new(backend)
+            .script_source(SCRIPT)
+            .build()
+            .unwrap();
+        let event_sender = context.event_sender();
+        let forwarder = thread::spawn(move || {
+            let response = forward_hook_event(
+                &event_sender,
+                HookEvent::CbtCreateWindow {
+                    hwnd: id,
+                    rect: Rect::new(100, 100, 900, 700),
+                },
+            );
+            event_sender.send(Event::Interrupt).unwrap();
+            response
+        });
+        context.run().unwrap();
+        assert_eq!(
+            forwarder
+                .join()
+                .unwrap()
+                .and_then(|response| response.rect()),
+            Some(Rect::new(0, 0, 960, 1040))
+        );
+    }
+
+    #[test]
+    fn late_hooks_keep_their_rects() {
+        let rect = Rect::new(100, 100, 900, 700);
+        let hwnd = 1;
+        for event in [
+            HookEvent::CbtCreateWindow { hwnd, rect },
+            HookEvent::CbtMoveSize { hwnd, rect },
+        ] {
+            assert_eq!(
+                fallback_response(&window_event(event)).and_then(|response| response.rect()),
+                Some(rect)
+            );
+        }
+        assert!(fallback_response(&window_event(HookEvent::CbtDestroyWindow { hwnd })).is_none());
+    }
+
+    fn window_event(event: HookEvent) -> WindowEvent {
+        WindowEvent {
+            event,
+            window: WindowInfo::default(),
+        }
+    }
+
+    #[test]
+    fn hooks_describe_their_windows() {
+        let backend = Arc::new(SimulatedBackend::new());
+        let id = backend.create_window(SimulatedWindow::new("Editor", Rect::new(0, 0, 10, 10)));
+        let mut context = ContextBuilder::new(backend)
+            .script_source(
+                r#"
+                seen = {}
+                function wlw.on_window_activate(w, caused_by_mouse)
+                    table.insert(seen, w.exe .. " " .. w.class .. " " .. w.pid)
+                end
+                function wlw.on_window_destroy(w)
+                    table.insert(seen, w.exe .. " " .. w.thread_id)
+                end
+            "#,
+            )
+            .build()
+            .unwrap();
+        let window = WindowInfo {
+            pid: 12,
+            thread_id: 34,
+            class_name: "Edit".to_owned(),
+            exe: "editor.exe".to_owned(),
+        };
+        let events = [
+            HookEvent::CbtActivate {
+                hwnd: id,
+                caused_by_mouse: false,
+            },
+            HookEvent::CbtDestroyWindow { hwnd: id },
+            // Never seen alive, so there is nothing to tell Lua
+            HookEvent::CbtDestroyWindow { hwnd: id + 1 },
+        ];
+        for event in events {
+            let event = WindowEvent {
+                event,
+                window: window.clone(),
+            };
+            assert_eq!(context.handle_window_event(&event).unwrap(), None);
+        }
+        let seen: Vec<String> = context
+            .lua
+            .context(|lua_ctx| lua_ctx.globals().get("seen"))
+            .unwrap();
+        assert_eq!(seen, vec!["editor.exe Edit 12", "editor.exe 34"]);
+    }
+
+    #[test]
+    fn runs_title_focus_syscommand_and_moved_callbacks() {
+        let backend = Arc::new(SimulatedBackend::new());
+        let id = backend.create_window(SimulatedWindow::new("Mail", Rect::new(0, 0, 10, 10)));
+        let mut context = ContextBuilder::new(backend)
+            .script_source(
+                r#"
+                events = {}
+                function wlw.on_window_title_change(w, title)
+                    table.insert(events, w.title .. " -> " .. title)
+                end
+                function wlw.on_window_focus(w)
+                    table.insert(events, "focus " .. w.title)
+                end
+                function wlw.on_window_syscommand(w, command)
+                    table.insert(events, "syscommand " .. command)
+                    -- Hooks only report system commands, so this vetoes nothing
+                    return false
+                end
+                function wlw.on_window_moved(w, rect)
+                    table.insert(events, "moved " .. rect.x .. "," .. rect.y)
+                end
+            "#,
+            )
+            .build()
+            .unwrap();
+        let mut handle = |event| context.handle_hook_event(event).unwrap();
+        assert_eq!(
+            handle(HookEvent::CwpSetText {
+                hwnd: id,
+                title: "Mail (1)".to_owned(),
+            }),
+            None
+        );
+        assert_eq!(handle(HookEvent::CbtSetFocus { hwnd: id }), None);
+        // The low four bits of system commands are Windows' own
+        assert_eq!(
+            handle(HookEvent::CbtSysCommand {
+                hwnd: id,
+                command: backend::SC_CLOSE | 2,
+            }),
+            None
+        );
+        assert_eq!(
+            handle(HookEvent::CbtSysCommand {
+                hwnd: id,
+                command: 0x10,
+            }),
+            None
+        );
+        assert_eq!(
+            handle(HookEvent::CwpWindowPosChanged {
+                hwnd: id,
+                rect: Rect::new(5, 6, 7, 8),
+            }),
+            None
+        );
+        let events: Vec<String> = context
+            .lua
+            .context(|lua_ctx| lua_ctx.globals().get("events"))
+            .unwrap();
+        assert_eq!(
+            events,
+            vec![
+                "Mail -> Mail (1)",
+                "focus Mail",
+                "syscommand close",
+                "syscommand 16",
+                "moved 5,6",
+            ]
+        );
+    }
+
+    #[test]
+    fn lua_vetoes_requests() {
+        let backend = Arc::new(SimulatedBackend::new());
+        let popup = backend.create_window(SimulatedWindow::new("Popup", Rect::new(0, 0, 10, 10)));
+        let editor = backend.create_window(SimulatedWindow::new("Editor", Rect::new(0, 0, 10, 10)));
+        let mut context = ContextBuilder::new(backend)
+            .script_source(
+                r#"
+                function wlw.on_window_activate(w, caused_by_mouse)
+                    return w.title ~= "Popup"
+                end
+                function wlw.on_window_min_max(w, command)
+                    if command == "maximize" then
+                        return false
+                    end
+                end
+                function wlw.on_window_create(w, rect)
+                    if w.title == "Popup" then
+                        return false
+                    end
+                    return rect
+                end
+            "#,
+            )
+            .build()
+            .unwrap();
+        let rect = Rect::new(1, 2, 3, 4);
+        let events = [
+            HookEvent::CbtActivate {
+                hwnd: popup,
+                caused_by_mouse: false,
+            },
+            HookEvent::CbtActivate {
+                hwnd: editor,
+                caused_by_mouse: false,
+            },
+            HookEvent::CbtMinMax {
+                hwnd: editor,
+                show_command: SW_MAXIMIZE,
+            },
+            HookEvent::CbtMinMax {
+                hwnd: editor,
+                show_command: backend::SW_MINIMIZE,
+            },
+            HookEvent::CbtCreateWindow { hwnd: popup, rect },
+            HookEvent::CbtCreateWindow { hwnd: editor, rect },
+        ];
+        let clients: Vec<_> = events
+            .iter()
+            .map(|event| {
+                let (request, client) = pipeserver::Request::fake(window_event(event.clone()));
+                context
+                    .event_sender()
+                    .send(Event::NewRequest(request))
+                    .unwrap();
+                client
+            })
+            .collect();
+        context.event_sender().send(Event::Interrupt).unwrap();
+        context.run().unwrap();
+
+        let answers: Vec<_> = clients.iter().map(|client| client.answer()).collect();
+        assert_eq!(
+            answers,
+            vec![
+                Some(Some(HookResponse::deny())),
+                Some(None),
+                Some(Some(HookResponse::deny())),
+                Some(None),
+                Some(Some(HookResponse::deny())),
+                Some(Some(HookResponse::from_rect(rect))),
+            ]
+        );
+    }
+
+    #[test]
+    fn failed_requests_leave_nobody_waiting() {
+        let backend = Arc::new(SimulatedBackend::new());
+        let id = backend.create_window(SimulatedWindow::new("Editor", Rect::new(0, 0, 10, 10)));
+        let mut context = ContextBuilder::new(backend)
+            .script_source(
+                r#"
+                function wlw.on_window_activate(w, caused_by_mouse)
+                    error("broken script")
+                end
+            "#,
+            )
+            .build()
+            .unwrap();
+        let clients: Vec<_> = (0..2)
+            .map(|_| {
+                let (request, client) =
+                    pipeserver::Request::fake(window_event(HookEvent::CbtActivate {
+                        hwnd: id,
+                        caused_by_mouse: false,
+                    }));
+                context
+                    .event_sender()
+                    .send(Event::NewRequest(request))
+                    .unwrap();
+                client
+            })
+            .collect();
+        match context.run() {
+            Err(Error::LuaCallback(_)) => {}
+            _ => panic!("expected a callback error"),
+        }
+        // The request which failed, and the one still queued behind it
+        assert_eq!(clients[0].answer(), Some(None));
+        assert_eq!(clients[1].answer(), None);
+        drop(context);
+        assert_eq!(clients[1].answer(), Some(None));
+    }
+
+    #[test]
+    fn moves_are_coalesced_but_transactions_are_not() {
+        let backend = Arc::new(SimulatedBackend::new());
+        let editor = backend.create_window(SimulatedWindow::new("Editor", Rect::new(0, 0, 10, 10)));
+        let notes = backend.create_window(SimulatedWindow::new("Notes", Rect::new(0, 0, 10, 10)));
+        let clock = Arc::new(ManualClock::new());
+        let mut context = ContextBuilder::new(backend)
+            .script_source(
+                r#"
+                events = {}
+                function wlw.on_window_moved(w, rect)
+                    table.insert(events, "moved " .. w.title .. " " .. rect.x)
+                end
+                function wlw.on_window_move_resize(w, rect)
+                    table.insert(events, "move_resize " .. w.title)
+                    return rect
+                end
+            "#,
+            )
+            .coalesce_window(Duration::from_millis(100))
+            .clock(clock.clone())
+            .build()
+            .unwrap();
+        let mut moved = |hwnd, x| {
+            let event = HookEvent::CwpWindowPosChanged {
+                hwnd,
+                rect: Rect::new(x, 0, x + 10, 10),
+            };
+            let request = pipeserver::Request::fake_notification(window_event(event));
+            assert!(context.handle_event(Event::NewRequest(request)).unwrap());
+            context.dispatch_held_notifications(false).unwrap();
+            context
+                .lua
+                .context(|lua_ctx| lua_ctx.globals().get::<_, Vec<String>>("events"))
+                .unwrap()
+        };
+        assert!(moved(editor, 1).is_empty());
+        assert!(moved(editor, 2).is_empty());
+        clock.advance(Duration::from_millis(40));
+        assert!(moved(notes, 3).is_empty());
+        assert!(moved(editor, 4).is_empty());
+        clock.advance(Duration::from_millis(60));
+        assert_eq!(moved(notes, 5), vec!["moved Editor 4"]);
+        assert_eq!(moved(editor, 6), vec!["moved Editor 4"]);
+
+        // Whatever was held goes first
+        let rect = Rect::new(7, 0, 17, 10);
+        let (request, client) =
+            pipeserver::Request::fake(window_event(HookEvent::CbtMoveSize { hwnd: editor, rect }));
+        assert!(context.handle_event(Event::NewRequest(request)).unwrap());
+        assert_eq!(client.answer(), Some(Some(HookResponse::from_rect(rect))));
+        let events: Vec<String> = context
+            .lua
+            .context(|lua_ctx| lua_ctx.globals().get("events"))
+            .unwrap();
+        assert_eq!(
+            events,
+            vec![
+                "moved Editor 4",
+                "moved Notes 5",
+                "moved Editor 6",
+                "move_resize Editor"
+            ]
+        );
+        assert_eq!(context.num_coalesced(), 3);
+    }
+
+    #[test]
+    fn lists_windows_lua_knows() {
+        let backend = Arc::new(SimulatedBackend::new());
+        let mut editor = SimulatedWindow::new("main.rs - Editor", Rect::new(0, 0, 10, 10));
+        editor.class = "Editor".to_owned();
+        editor.pid = 7;
+        editor.exe = "editor.exe".to_owned();
+        let editor = backend.create_window(editor);
+        let mut notes = SimulatedWindow::new("Notes", Rect::new(0, 0, 10, 10));
+        notes.style |= backend::WS_MINIMIZE;
+        let notes = backend.create_window(notes);
+        let mut hidden = SimulatedWindow::new("Hidden", Rect::new(0, 0, 10, 10));
+        hidden.style &= !backend::WS_VISIBLE;
+        let hidden = backend.create_window(hidden);
+        backend.create_window(SimulatedWindow::new("Unseen", Rect::new(0, 0, 10, 10)));
+        let mut context = ContextBuilder::new(backend)
+            .script_source(
+                r#"
+                function titles(filter)
+                    local titles = {}
+                    for _, w in ipairs(wlw.windows(filter)) do
+                        table.insert(titles, w.title)
+                    end
+                    return table.concat(titles, ", ")
+                end
+            "#,
+            )
+            .build()
+            .unwrap();
+        for &hwnd in &[hidden, notes, editor] {
+            context
+                .handle_hook_event(HookEvent::CbtSetFocus { hwnd })
+                .unwrap();
+        }
+        let titles = |filter: &str| -> rlua::Result<String> {
+            context
+                .lua
+                .context(|lua_ctx| lua_ctx.load(&format!("titles({})", filter)).eval())
+        };
+        assert_eq!(titles("").unwrap(), "main.rs - Editor, Notes, Hidden");
+        assert_eq!(
+            titles("{visible = true}").unwrap(),
+            "main.rs - Editor, Notes"
+        );
+        assert_eq!(
+            titles("{minimized = false}").unwrap(),
+            "main.rs - Editor, Hidden"
+        );
+        assert_eq!(titles("{title = '^main%.rs'}").unwrap(), "main.rs - Editor");
+        assert_eq!(
+            titles("{class = 'Editor', pid = 7, exe = 'EDITOR.EXE'}").unwrap(),
+            "main.rs - Editor"
+        );
+        assert_eq!(titles("{pid = 8, visible = true}").unwrap(), "");
+        assert!(titles("{colour = 'red'}").is_err());
+    }
+
+    #[test]
+    fn filters_skip_windows_gone_from_the_desktop() {
+        let backend = Arc::new(SimulatedBackend::new());
+        let window = |title: &str| SimulatedWindow::new(title, Rect::new(0, 0, 10, 10));
+        backend.create_window(window("Editor"));
+        let gone = backend.create_window(window("Gone"));
+        backend.create_window(window("Notes"));
+        let mut context = ContextBuilder::new(backend.clone())
+            .script_source("")
+            .build()
+            .unwrap();
+        context.adopt_windows().unwrap();
+        // Closed before its destroy event arrived
+        backend.destroy_window(gone).unwrap();
+        for filter in &["{visible = true}", "{title = '.'}", "{exe = ''}"] {
+            let count: rlua::Result<usize> = context
+                .lua
+                .context(|lua_ctx| lua_ctx.load(&format!("#wlw.windows({})", filter)).eval());
+            assert_eq!(count.unwrap(), 2, "{}", filter);
+        }
+    }
+
+    #[test]
+    fn adopts_windows_open_before_it() {
+        let backend = Arc::new(SimulatedBackend::new());
+        let window = |title: &str| SimulatedWindow::new(title, Rect::new(0, 0, 10, 10));
+        backend.create_window(window("Editor"));
+        let mut notes = window("Notes");
+        notes.style |= backend::WS_MINIMIZE;
+        backend.create_window(notes);
+        let mut hidden = window("Hidden");
+        hidden.style &= !backend::WS_VISIBLE;
+        backend.create_window(hidden);
+        let mut palette = window("Palette");
+        palette.ex_style |= backend::WS_EX_TOOLWINDOW;
+        backend.create_window(palette);
+        let mut splash = window("Splash");
+        splash.style &= !backend::WS_CAPTION;
+        backend.create_window(splash);
+        let mut context = ContextBuilder::new(backend)
+            .script_source(
+                r#"
+                adopted = {}
+                function wlw.on_window_adopt(w)
+                    table.insert(adopted, w.title)
+                end
+            "#,
+            )
+            .build()
+            .unwrap();
+        context.adopt_windows().unwrap();
+        let (adopted, known) = context
+            .lua
+            .context(|lua_ctx| -> rlua::Result<(String, usize)> {
+                let adopted = lua_ctx.load("table.concat(adopted, ', ')").eval()?;
+                let known = lua_ctx.load("#wlw.windows()").eval()?;
+                Ok((adopted, known))
+            })
+            .unwrap();
+        assert_eq!(adopted, "Notes, Editor");
+        assert_eq!(known, 2);
+    }
+
+    #[test]
+    fn script_fields_last_until_the_window_is_destroyed() {
+        let backend = Arc::new(SimulatedBackend::new());
+        let hwnd = backend.create_window(SimulatedWindow::new("Editor", Rect::new(0, 0, 10, 10)));
+        let mut context = ContextBuilder::new(backend)
+            .script_source(
+                r#"
+                function wlw.on_window_focus(w)
+                    w.workspace = 3
+                    kept = w
+                end
+                function wlw.on_window_destroy(w)
+                    seen_on_destroy = w.workspace
+                end
+            "#,
+            )
+            .build()
+            .unwrap();
+        context
+            .handle_hook_event(HookEvent::CbtSetFocus { hwnd })
+            .unwrap();
+        context
+            .handle_hook_event(HookEvent::CbtDestroyWindow { hwnd })
+            .unwrap();
+        let (seen_on_destroy, kept) = context
+            .lua
+            .context(|lua_ctx| -> rlua::Result<(i64, Option<i64>)> {
+                Ok((
+                    lua_ctx.globals().get("seen_on_destroy")?,
+                    lua_ctx.load("kept.workspace").eval()?,
+                ))
+            })
+            .unwrap();
+        assert_eq!(seen_on_destroy, 3);
+        assert_eq!(kept, None);
+    }
+
+    #[test]
+    fn scripts_do_layout_math_with_rects() {
+        let context = ContextBuilder::new(Arc::new(SimulatedBackend::new()))
+            .script_source(
+                r#"
+                local screen = wlw.rect(0, 0, 1920, 1040)
+                local left, right = screen:split_h(0.5)
+                assert(left == wlw.rect(0, 0, 960, 1040))
+                assert(tostring(right) == "Rect(x=960, y=0, width=960, height=1040)")
+                assert(screen:contains(right) and screen:contains(1919, 0))
+                assert(not left:intersects(right) and left:intersection(right) == nil)
+                assert(left:union(right) == screen)
+                assert(screen:inset(10) == wlw.rect(10, 10, 1900, 1020))
+                assert(left:translate(960, 0) == right)
+                local x, y = screen:center()
+                assert(x == 960 and y == 520)
+                local cells = screen:grid(2, 2)
+                assert(#cells == 4 and cells[4] == wlw.rect(960, 520, 960, 520))
+                assert(wlw.rect(10, 10, -10, -10) == wlw.rect(0, 0, 10, 10))
+                assert(not pcall(screen.split_v, screen, 2))
+            "#,
+            )
+            .build();
+        if let Err(e) = context {
+            panic!("{}", e);
+        }
+    }
+
+    #[test]
+    fn callbacks_may_answer_with_tables_or_nil() {
+        let backend = Arc::new(SimulatedBackend::new());
+        let mut context = ContextBuilder::new(backend)
+            .script_source(
+                r#"
+                answers = {
+                    {x = 0, y = 0, width = 800, height = 600},
+                    {10, 20, 300, 400},
+                    nil,
+                    wlw.rect(5, 5, 50, 50),
+                    {x = 0, y = 0, width = 800},
+                    "rect",
+                    false,
+                }
+                function wlw.on_window_create(w, rect)
+                    return answers[w.id]
+                end
+            "#,
+            )
+            .build()
+            .unwrap();
+        let rect = Rect::new(1, 2, 3, 4);
+        let mut create =
+            |hwnd| context.handle_hook_event(HookEvent::CbtCreateWindow { hwnd, rect });
+        let answer = |rect| Some(HookResponse::from_rect(rect));
+        assert_eq!(create(1).unwrap(), answer(Rect::new(0, 0, 800, 600)));
+        assert_eq!(create(2).unwrap(), answer(Rect::new(10, 20, 310, 420)));
+        assert_eq!(create(3).unwrap(), answer(rect));
+        assert_eq!(create(4).unwrap(), answer(Rect::new(5, 5, 55, 55)));
+        // Answers which aren't rects leave the rect as it was
+        assert_eq!(create(5).unwrap(), answer(rect));
+        assert_eq!(create(6).unwrap(), answer(rect));
+        assert_eq!(create(7).unwrap(), Some(HookResponse::deny()));
+    }
+
+    #[test]
+    fn unknown_show_commands_reach_lua_as_numbers() {
+        let backend = Arc::new(SimulatedBackend::new());
+        let hwnd = backend.create_window(SimulatedWindow::new("Editor", Rect::new(0, 0, 10, 10)));
+        let mut context = ContextBuilder::new(backend)
+            .script_source(
+                r#"
+                commands = {}
+                function wlw.on_window_min_max(w, command)
+                    table.insert(commands, tostring(command))
+                end
+            "#,
+            )
+            .build()
+            .unwrap();
+        for &show_command in &[99, SW_MAXIMIZE] {
+            let event = window_event(HookEvent::CbtMinMax { hwnd, show_command });
+            assert_eq!(context.handle_window_event(&event).unwrap(), None);
+        }
+        let commands: String = context
+            .lua
+            .context(|lua_ctx| lua_ctx.load("table.concat(commands, ', ')").eval())
+            .unwrap();
+        assert_eq!(commands, "99, maximize");
+    }
+}
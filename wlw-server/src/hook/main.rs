@@ -1,18 +1,30 @@
+#[cfg(windows)]
 mod hook;
+#[cfg(windows)]
 mod process;
+#[cfg(windows)]
 mod servermonitor;
+#[cfg(windows)]
 mod windowsloop;
+#[cfg(windows)]
 use crate::hook::{HookDll, HookId, Library, WindowsHook};
+#[cfg(windows)]
 use crate::servermonitor::ServerMonitor;
+#[cfg(windows)]
 use std::env;
+#[cfg(windows)]
 use std::error::Error;
+#[cfg(windows)]
 use std::fmt;
+#[cfg(windows)]
 use wintrap::{self, Signal};
+#[cfg(windows)]
 use wlw_server::windows;
 #[macro_use]
 extern crate log;
 use flexi_logger::Logger;
 
+#[cfg(windows)]
 #[derive(Debug)]
 enum MainError {
     PidIsMissing,
@@ -24,6 +36,7 @@ enum MainError {
     EventLoop(windows::Error),
 }
 
+#[cfg(windows)]
 impl fmt::Display for MainError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -38,12 +51,14 @@ impl fmt::Display for MainError {
     }
 }
 
+#[cfg(windows)]
 impl Error for MainError {}
 
+#[cfg(windows)]
 fn run() -> Result<i32, MainError> {
     let main_thread_id = unsafe { windows::GetCurrentThreadId() };
     wintrap::trap(
-        vec![Signal::CtrlC, Signal::CloseWindow, Signal::CloseConsole],
+        &[Signal::CtrlC, Signal::CloseWindow, Signal::CloseConsole],
         move |_| {
             trace!("Received interrupt");
             windowsloop::post_quit_message(main_thread_id, 1).unwrap();
@@ -79,6 +94,11 @@ fn run() -> Result<i32, MainError> {
     .unwrap()
 }
 
+#[cfg(not(windows))]
+fn run() -> Result<i32, &'static str> {
+    Err("wlw-hook requires Windows")
+}
+
 fn main() {
     Logger::with_env_or_str("trace")
         .format(|w, record| {
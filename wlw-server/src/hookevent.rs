@@ -0,0 +1,497 @@
+use crate::backend::WindowId;
+use crate::codec::{self, Fields, Message};
+use crate::luauserdata::Rect;
+use std::convert::TryFrom;
+use std::error;
+use std::fmt;
+
+include!(concat!(env!("OUT_DIR"), "/hookevent.rs"));
+
+impl HookEvent {
+    /// The window the event is about.
+    pub fn hwnd(&self) -> WindowId {
+        match *self {
+            HookEvent::CwpShowWindow { hwnd, .. }
+            | HookEvent::CbtActivate { hwnd, .. }
+            | HookEvent::CbtCreateWindow { hwnd, .. }
+            | HookEvent::CbtDestroyWindow { hwnd }
+            | HookEvent::CbtMinMax { hwnd, .. }
+            | HookEvent::CbtMoveSize { hwnd, .. }
+            | HookEvent::CwpSetText { hwnd, .. }
+            | HookEvent::CbtSetFocus { hwnd }
+            | HookEvent::CbtSysCommand { hwnd, .. }
+            | HookEvent::CwpWindowPosChanged { hwnd, .. } => hwnd,
+        }
+    }
+
+    /// Whether a later event of the same kind for the same window makes this
+    /// one moot.
+    pub fn is_superseded_by_later(&self) -> bool {
+        matches!(self, HookEvent::CwpWindowPosChanged { .. })
+    }
+}
+
+/// A hook event along with what the hook knew about its window at the
+/// time, which is how events travel over the pipe.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WindowEvent {
+    pub event: HookEvent,
+    pub window: WindowInfo,
+}
+
+impl Message for WindowEvent {
+    fn kind(&self) -> u16 {
+        self.event.kind()
+    }
+
+    fn encode_body(&self, body: &mut codec::Encoder) {
+        body.part(|event| self.event.encode_body(event));
+        self.window.encode_fields(body);
+    }
+
+    fn decode_body(kind: u16, body: &mut codec::Decoder) -> codec::Result<Self> {
+        Ok(WindowEvent {
+            event: HookEvent::decode_body(kind, &mut body.part()?)?,
+            window: WindowInfo::decode_fields(body)?,
+        })
+    }
+}
+
+/// Decodes one whole frame.
+impl<'a> TryFrom<&'a [u8]> for WindowEvent {
+    type Error = codec::Error;
+
+    fn try_from(frame: &'a [u8]) -> Result<Self, Self::Error> {
+        codec::decode(frame)
+    }
+}
+
+impl HookResponse {
+    pub fn from_rect(rect: Rect) -> Self {
+        HookResponse::PosAndSize { rect }
+    }
+
+    pub fn deny() -> Self {
+        HookResponse::Verdict { allow: false }
+    }
+
+    /// Where the window goes, if the response says.
+    pub fn rect(&self) -> Option<Rect> {
+        match *self {
+            HookResponse::PosAndSize { rect } => Some(rect),
+            HookResponse::Verdict { .. } => None,
+        }
+    }
+
+    pub fn allows(&self) -> bool {
+        match *self {
+            HookResponse::PosAndSize { .. } => true,
+            HookResponse::Verdict { allow } => allow,
+        }
+    }
+}
+
+/// Whether the event answered with `response` goes ahead; saying nothing
+/// lets it.
+pub fn allows(response: Option<HookResponse>) -> bool {
+    response.is_none_or(|response| response.allows())
+}
+
+impl Hello {
+    /// Fails for hooks speaking another version of the protocol.
+    pub fn check_version(&self) -> Result<(), HelloError> {
+        if self.protocol_version == PROTOCOL_VERSION {
+            Ok(())
+        } else {
+            Err(HelloError::VersionMismatch(self.clone()))
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum HelloError {
+    VersionMismatch(Hello),
+}
+
+impl fmt::Display for HelloError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HelloError::VersionMismatch(hello) => write!(
+                f,
+                "Hook in {} (pid {}) speaks protocol version {}, not {}",
+                hello.image_name, hello.pid, hello.protocol_version, PROTOCOL_VERSION
+            ),
+        }
+    }
+}
+
+impl error::Error for HelloError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::{FLAG_WANTS_REPLY, HEADER_SIZE};
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+    use std::fs;
+    use std::path::Path;
+
+    fn hello(image_name: &str) -> Hello {
+        Hello {
+            protocol_version: PROTOCOL_VERSION,
+            pointer_width: 32,
+            pid: 0x1234,
+            image_name: image_name.to_owned(),
+        }
+    }
+
+    #[test]
+    fn events_round_trip() {
+        let rect = Rect::new(1, -2, 3, 4);
+        let events = [
+            HookEvent::CwpShowWindow {
+                hwnd: 1,
+                shown: true,
+            },
+            HookEvent::CbtActivate {
+                hwnd: 2,
+                caused_by_mouse: false,
+            },
+            HookEvent::CbtCreateWindow { hwnd: 3, rect },
+            HookEvent::CbtDestroyWindow { hwnd: 4 },
+            HookEvent::CbtMinMax {
+                hwnd: 5,
+                show_command: 3,
+            },
+            HookEvent::CbtMoveSize { hwnd: 6, rect },
+            HookEvent::CwpSetText {
+                hwnd: 7,
+                title: "Inbox (3) \u{2013} Mail".to_owned(),
+            },
+            HookEvent::CbtSetFocus { hwnd: 8 },
+            HookEvent::CbtSysCommand {
+                hwnd: 9,
+                command: 0xf060,
+            },
+            HookEvent::CwpWindowPosChanged { hwnd: 10, rect },
+        ];
+        for event in &events {
+            let frame = codec::encode(event, FLAG_WANTS_REPLY).unwrap();
+            assert_eq!(codec::decode::<HookEvent>(&frame).as_ref(), Ok(event));
+        }
+        for response in &[HookResponse::from_rect(rect), HookResponse::deny()] {
+            let frame = codec::encode(response, 0).unwrap();
+            assert_eq!(codec::decode::<HookResponse>(&frame), Ok(*response));
+        }
+    }
+
+    #[test]
+    fn responses_say_whether_to_go_ahead() {
+        let rect = Rect::new(1, 2, 3, 4);
+        assert!(allows(None));
+        assert!(allows(Some(HookResponse::from_rect(rect))));
+        assert!(allows(Some(HookResponse::Verdict { allow: true })));
+        assert!(!allows(Some(HookResponse::deny())));
+        assert_eq!(HookResponse::from_rect(rect).rect(), Some(rect));
+        assert_eq!(HookResponse::deny().rect(), None);
+    }
+
+    #[test]
+    fn window_events_carry_window_info() {
+        let event = WindowEvent {
+            event: HookEvent::CbtDestroyWindow { hwnd: 0x0102 },
+            window: WindowInfo {
+                pid: 3,
+                thread_id: 4,
+                class_name: "Notepad".to_owned(),
+                exe: "notepad.exe".to_owned(),
+            },
+        };
+        let frame = codec::encode(&event, 0).unwrap();
+        assert_eq!(WindowEvent::try_from(&frame[..]), Ok(event.clone()));
+        // Bare events come first, so that the server can tell what happened
+        // before reading who it happened to
+        let bare_body = &codec::encode(&event.event, 0).unwrap()[HEADER_SIZE..];
+        let mut expected = (bare_body.len() as u16).to_le_bytes().to_vec();
+        expected.extend(bare_body);
+        expected.extend(codec::encode_fields(&event.window));
+        assert_eq!(&frame[HEADER_SIZE..], &expected[..]);
+        let bare = codec::encode(&event.event, 0).unwrap();
+        assert_eq!(
+            WindowEvent::try_from(&bare[..]),
+            Err(codec::Error::ShortBody(KIND_CBT_DESTROY_WINDOW))
+        );
+    }
+
+    #[test]
+    fn long_titles_round_trip_with_window_info() {
+        let event = WindowEvent {
+            event: HookEvent::CwpSetText {
+                hwnd: 1,
+                title: "t".repeat(65_000),
+            },
+            window: WindowInfo {
+                pid: 3,
+                thread_id: 4,
+                class_name: "Notepad".to_owned(),
+                exe: "notepad.exe".to_owned(),
+            },
+        };
+        let frame = codec::encode(&event, 0).unwrap();
+        assert_eq!(WindowEvent::try_from(&frame[..]), Ok(event.clone()));
+        // Titles which fill the event's count leave no room for the rest
+        let mut longest = event;
+        longest.event = HookEvent::CwpSetText {
+            hwnd: 1,
+            title: "t".repeat(u16::MAX as usize),
+        };
+        assert!(matches!(
+            codec::encode(&longest, 0),
+            Err(codec::Error::FrameTooLarge(_))
+        ));
+    }
+
+    #[test]
+    fn window_info_survives_events_growing() {
+        let window = WindowInfo {
+            pid: 3,
+            thread_id: 4,
+            class_name: "Notepad".to_owned(),
+            exe: "notepad.exe".to_owned(),
+        };
+        // A destroy event from a later version, with a field this one does
+        // not know about
+        let mut body = vec![6, 0, 2, 1, 0, 0, 0xff, 0xff];
+        body.extend(codec::encode_fields(&window));
+        let mut frame = (body.len() as u32).to_le_bytes().to_vec();
+        frame.extend(&[KIND_CBT_DESTROY_WINDOW as u8, 0, 0, 0, 0, 0, 0, 0]);
+        frame.extend(body);
+        assert_eq!(
+            WindowEvent::try_from(&frame[..]),
+            Ok(WindowEvent {
+                event: HookEvent::CbtDestroyWindow { hwnd: 0x0102 },
+                window,
+            })
+        );
+    }
+
+    #[test]
+    fn events_match_c_layout() {
+        let frame = codec::encode(&HookEvent::CbtDestroyWindow { hwnd: 0x0102 }, 0).unwrap();
+        assert_eq!(frame, [4, 0, 0, 0, 5, 0, 0, 0, 0, 0, 0, 0, 2, 1, 0, 0]);
+
+        let frame = codec::encode(
+            &HookEvent::CwpShowWindow {
+                hwnd: 7,
+                shown: true,
+            },
+            0,
+        )
+        .unwrap();
+        assert_eq!(&frame[HEADER_SIZE..], &[7, 0, 0, 0, 1]);
+
+        let frame = codec::encode(
+            &HookEvent::CwpSetText {
+                hwnd: 7,
+                title: "ab".to_owned(),
+            },
+            0,
+        )
+        .unwrap();
+        assert_eq!(&frame[4..6], &[8, 0]);
+        assert_eq!(&frame[HEADER_SIZE..], &[7, 0, 0, 0, 2, 0, b'a', b'b']);
+
+        let frame = codec::encode(&HookResponse::from_rect(Rect::new(1, 2, 3, -1)), 0).unwrap();
+        assert_eq!(&frame[4..6], &[1, 0]);
+        assert_eq!(
+            &frame[HEADER_SIZE..],
+            &[1, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0, 0xff, 0xff, 0xff, 0xff]
+        );
+    }
+
+    #[test]
+    fn hello_matches_c_layout() {
+        let frame = codec::encode(&hello("a.exe"), 0).unwrap();
+        assert_eq!(codec::decode(&frame), Ok(hello("a.exe")));
+        let body = &frame[HEADER_SIZE..];
+        assert_eq!(&body[..2], &PROTOCOL_VERSION.to_le_bytes());
+        assert_eq!(body[2], 32);
+        assert_eq!(&body[3..7], &[0x34, 0x12, 0, 0]);
+        assert_eq!(&body[7..9], &[5, 0]);
+        assert_eq!(&body[9..], b"a.exe");
+    }
+
+    #[test]
+    fn hello_checks_version() {
+        assert_eq!(hello("a.exe").check_version(), Ok(()));
+        let stale = Hello {
+            protocol_version: 1,
+            ..hello("a.exe")
+        };
+        assert_eq!(
+            stale.check_version(),
+            Err(HelloError::VersionMismatch(stale))
+        );
+    }
+
+    #[test]
+    fn kinds_do_not_cross() {
+        let frame = codec::encode(&hello("a.exe"), 0).unwrap();
+        assert_eq!(
+            codec::decode::<HookEvent>(&frame),
+            Err(codec::Error::UnknownKind(KIND_HELLO))
+        );
+        let frame = codec::encode(&HookEvent::CbtDestroyWindow { hwnd: 1 }, 0).unwrap();
+        assert_eq!(
+            codec::decode::<Hello>(&frame),
+            Err(codec::Error::UnknownKind(KIND_CBT_DESTROY_WINDOW))
+        );
+    }
+
+    /// The build script writes the header to `OUT_DIR`; copy it over the
+    /// checked-in one after editing the schema.
+    #[test]
+    fn c_header_matches_schema() {
+        const GENERATED: &str = concat!(env!("OUT_DIR"), "/HookEvent.h");
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../wlw-hook-dll/HookEvent.h");
+        let checked_in = fs::read_to_string(&path).unwrap();
+        assert!(
+            checked_in == include_str!(concat!(env!("OUT_DIR"), "/HookEvent.h")),
+            "{} is out of date with protocol.schema; copy {} over it",
+            path.display(),
+            GENERATED
+        );
+    }
+
+    fn any_rect() -> impl Strategy<Value = Rect> {
+        any::<[i32; 4]>().prop_map(|[left, top, right, bottom]| Rect::new(left, top, right, bottom))
+    }
+
+    fn any_event() -> impl Strategy<Value = HookEvent> {
+        prop_oneof![
+            (any::<u32>(), any::<bool>())
+                .prop_map(|(hwnd, shown)| HookEvent::CwpShowWindow { hwnd, shown }),
+            (any::<u32>(), any::<bool>()).prop_map(|(hwnd, caused_by_mouse)| {
+                HookEvent::CbtActivate {
+                    hwnd,
+                    caused_by_mouse,
+                }
+            }),
+            (any::<u32>(), any_rect())
+                .prop_map(|(hwnd, rect)| HookEvent::CbtCreateWindow { hwnd, rect }),
+            any::<u32>().prop_map(|hwnd| HookEvent::CbtDestroyWindow { hwnd }),
+            (any::<u32>(), any::<i32>())
+                .prop_map(|(hwnd, show_command)| { HookEvent::CbtMinMax { hwnd, show_command } }),
+            (any::<u32>(), any_rect())
+                .prop_map(|(hwnd, rect)| HookEvent::CbtMoveSize { hwnd, rect }),
+            (any::<u32>(), ".{0,40}")
+                .prop_map(|(hwnd, title)| HookEvent::CwpSetText { hwnd, title }),
+            any::<u32>().prop_map(|hwnd| HookEvent::CbtSetFocus { hwnd }),
+            (any::<u32>(), any::<i32>())
+                .prop_map(|(hwnd, command)| HookEvent::CbtSysCommand { hwnd, command }),
+            (any::<u32>(), any_rect())
+                .prop_map(|(hwnd, rect)| HookEvent::CwpWindowPosChanged { hwnd, rect }),
+        ]
+    }
+
+    fn any_window_event() -> impl Strategy<Value = WindowEvent> {
+        (
+            any_event(),
+            any::<u32>(),
+            any::<u32>(),
+            ".{0,20}",
+            ".{0,20}",
+        )
+            .prop_map(|(event, pid, thread_id, class_name, exe)| WindowEvent {
+                event,
+                window: WindowInfo {
+                    pid,
+                    thread_id,
+                    class_name,
+                    exe,
+                },
+            })
+    }
+
+    fn frame(kind: u16, flags: u16, body: &[u8]) -> Vec<u8> {
+        let mut frame = Vec::new();
+        frame.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        frame.extend_from_slice(&kind.to_le_bytes());
+        frame.extend_from_slice(&flags.to_le_bytes());
+        frame.extend_from_slice(body);
+        frame
+    }
+
+    proptest! {
+        #[test]
+        fn arbitrary_bytes_decode_or_fail(bytes in vec(any::<u8>(), 0..64)) {
+            let _ = HookEvent::try_from(&bytes[..]);
+            let _ = WindowEvent::try_from(&bytes[..]);
+            let _ = Hello::try_from(&bytes[..]);
+            let _ = HookResponse::try_from(&bytes[..]);
+        }
+
+        #[test]
+        fn arbitrary_bodies_decode_to_stable_messages(
+            kind in 0u16..16,
+            flags in any::<u16>(),
+            body in vec(any::<u8>(), 0..300),
+        ) {
+            let frame = frame(kind, flags, &body);
+            // Bytes decode to at most one message, which survives another
+            // trip through the codec
+            if let Ok(event) = HookEvent::try_from(&frame[..]) {
+                prop_assert_eq!(codec::decode(&codec::encode(&event, flags).unwrap()), Ok(event));
+            }
+            if let Ok(event) = WindowEvent::try_from(&frame[..]) {
+                let encoded = codec::encode(&event, flags).unwrap();
+                prop_assert_eq!(codec::decode(&encoded), Ok(event));
+            }
+            if let Ok(hello) = Hello::try_from(&frame[..]) {
+                let encoded = codec::encode(&hello, flags).unwrap();
+                prop_assert_eq!(codec::decode(&encoded), Ok(hello));
+            }
+            if let Ok(response) = HookResponse::try_from(&frame[..]) {
+                let encoded = codec::encode(&response, flags).unwrap();
+                prop_assert_eq!(codec::decode(&encoded), Ok(response));
+            }
+        }
+
+        #[test]
+        fn any_event_round_trips(event in any_event(), flags in any::<u16>()) {
+            let frame = codec::encode(&event, flags).unwrap();
+            prop_assert_eq!(HookEvent::try_from(&frame[..]), Ok(event));
+        }
+
+        #[test]
+        fn any_window_event_round_trips(event in any_window_event()) {
+            let frame = codec::encode(&event, 0).unwrap();
+            prop_assert_eq!(WindowEvent::try_from(&frame[..]), Ok(event));
+        }
+
+        #[test]
+        fn truncated_events_are_refused(event in any_event(), cut in 1usize..24) {
+            let mut frame = codec::encode(&event, 0).unwrap();
+            let cut = cut.min(frame.len() - HEADER_SIZE);
+            frame.truncate(frame.len() - cut);
+            let len = (frame.len() - HEADER_SIZE) as u32;
+            frame[..4].copy_from_slice(&len.to_le_bytes());
+            prop_assert_eq!(
+                HookEvent::try_from(&frame[..]),
+                Err(codec::Error::ShortBody(event.kind()))
+            );
+        }
+
+        #[test]
+        fn any_hello_round_trips(
+            protocol_version in any::<u16>(),
+            pointer_width in any::<u8>(),
+            pid in any::<u32>(),
+            image_name in ".{0,40}",
+        ) {
+            let hello = Hello { protocol_version, pointer_width, pid, image_name };
+            let frame = codec::encode(&hello, 0).unwrap();
+            prop_assert_eq!(Hello::try_from(&frame[..]), Ok(hello));
+        }
+    }
+}
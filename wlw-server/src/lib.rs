@@ -1,5 +1,19 @@
-pub mod windows;
 #[macro_use]
 extern crate log;
-#[macro_use]
-extern crate failure;
+pub mod backend;
+pub mod clock;
+pub mod coalescer;
+pub mod codec;
+pub mod context;
+#[cfg(all(windows, debug_assertions))]
+mod debug;
+pub mod hookevent;
+#[cfg(windows)]
+pub mod hookmanager;
+pub mod luauserdata;
+#[cfg(any(windows, unix))]
+pub mod pipeserver;
+pub mod recording;
+pub mod scenario;
+#[cfg(windows)]
+pub mod windows;
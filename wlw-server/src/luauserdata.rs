@@ -0,0 +1,1380 @@
+use crate::backend::{self, ShowState, WindowBackend, WindowId, ZOrder};
+use crate::hookevent::WindowInfo;
+#[cfg(windows)]
+use crate::windows;
+use rlua::{FromLua, ToLua};
+use std::cmp;
+use std::error;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+#[derive(Debug)]
+enum Error {
+    KeyDoesNotExist(String),
+    ReadOnly(String),
+    OpacityOutOfRange(f64),
+    RatioOutOfRange(f64),
+    EmptyGrid,
+    GridTooLarge(u32, u32),
+    RectOverflow,
+    Backend(backend::Error),
+}
+
+impl error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::KeyDoesNotExist(key) => write!(f, "Key does not exist: {}", key),
+            Error::ReadOnly(key) => write!(f, "Key is read-only: {}", key),
+            Error::OpacityOutOfRange(opacity) => {
+                write!(f, "Opacity must be from 0 to 1: {}", opacity)
+            }
+            Error::RatioOutOfRange(ratio) => write!(f, "Ratio must be from 0 to 1: {}", ratio),
+            Error::EmptyGrid => write!(f, "A grid needs at least one column and one row"),
+            Error::GridTooLarge(columns, rows) => write!(
+                f,
+                "A grid may have at most {} cells: {} by {}",
+                MAX_GRID_CELLS, columns, rows
+            ),
+            Error::RectOverflow => write!(f, "Rect does not fit in 32-bit coordinates"),
+            Error::Backend(e) => write!(f, "Window backend error: {}", e),
+        }
+    }
+}
+
+impl From<Error> for rlua::Error {
+    fn from(err: Error) -> Self {
+        rlua::Error::ExternalError(Arc::new(err))
+    }
+}
+
+impl From<backend::Error> for Error {
+    fn from(err: backend::Error) -> Self {
+        Error::Backend(err)
+    }
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Far more cells than any screen has room for, so that scripts can't ask
+/// for gigabytes of them.
+const MAX_GRID_CELLS: u64 = 10_000;
+
+/// Names the registry table which holds the one handle of each window Lua
+/// knows, by id.
+pub const WINDOW_TABLE: &str = "wlw_windows";
+
+/// The window's handle from `WINDOW_TABLE`, made if Lua has none yet.
+pub fn get_window_handle<'lua>(
+    lua_ctx: rlua::Context<'lua>,
+    backend: &Arc<dyn WindowBackend>,
+    id: WindowId,
+) -> rlua::Result<rlua::AnyUserData<'lua>> {
+    let window_table: rlua::Table = lua_ctx.named_registry_value(WINDOW_TABLE)?;
+    if let Some(handle) = window_table.get(id)? {
+        return Ok(handle);
+    }
+    window_table.set(id, WindowHandle::new(backend.clone(), id))?;
+    window_table.get(id)
+}
+
+/// Drops the fields a script set on the window's handle.
+pub fn clear_window_fields(window_handle: &rlua::AnyUserData) -> rlua::Result<()> {
+    window_handle.set_user_value(rlua::Nil)
+}
+
+pub struct WindowHandle {
+    backend: Arc<dyn WindowBackend>,
+    id: WindowId,
+    /// What the hook last said about the window, which outlives it.
+    info: Option<WindowInfo>,
+}
+
+impl WindowHandle {
+    const PROPERTIES: [&'static str; 14] = [
+        "title",
+        "style",
+        "pid",
+        "thread_id",
+        "class",
+        "exe",
+        "id",
+        "visible",
+        "minimized",
+        "maximized",
+        "owner",
+        "parent",
+        "monitor",
+        "client_rect",
+    ];
+    /// The methods `add_methods` registers, which scripts can no more
+    /// overwrite than the properties.
+    const METHODS: [&'static str; 13] = [
+        "get_window_rect",
+        "set_window_rect",
+        "focus",
+        "minimize",
+        "maximize",
+        "restore",
+        "hide",
+        "show",
+        "close",
+        "raise",
+        "lower",
+        "set_topmost",
+        "set_opacity",
+    ];
+
+    pub fn new(backend: Arc<dyn WindowBackend>, id: WindowId) -> Self {
+        WindowHandle {
+            backend,
+            id,
+            info: None,
+        }
+    }
+
+    pub fn set_info(&mut self, info: WindowInfo) {
+        self.info = Some(info);
+    }
+
+    fn get_info(&self) -> Result<WindowInfo> {
+        match &self.info {
+            Some(info) => Ok(info.clone()),
+            None => Ok(self.backend.get_window_info(self.id)?),
+        }
+    }
+
+    fn get_title(&self) -> Result<String> {
+        Ok(self.backend.get_title(self.id)?)
+    }
+
+    fn get_window_rect(&self) -> Result<Rect> {
+        Ok(self.backend.get_window_rect(self.id)?)
+    }
+
+    fn set_window_rect(&self, x: i32, y: i32, w: i32, h: i32) -> Result<()> {
+        let right = x.checked_add(w).ok_or(Error::RectOverflow)?;
+        let bottom = y.checked_add(h).ok_or(Error::RectOverflow)?;
+        Ok(self
+            .backend
+            .set_window_rect(self.id, Rect::new(x, y, right, bottom))?)
+    }
+
+    fn get_property<'lua>(
+        &self,
+        lua_ctx: rlua::Context<'lua>,
+        key: &str,
+    ) -> rlua::Result<rlua::Value<'lua>> {
+        match key {
+            "title" => Ok(self.get_title()?.to_lua(lua_ctx)?),
+            "style" => Ok(WindowStyle::new(self.backend.clone(), self.id)?.to_lua(lua_ctx)?),
+            "pid" => Ok(self.get_info()?.pid.to_lua(lua_ctx)?),
+            "thread_id" => Ok(self.get_info()?.thread_id.to_lua(lua_ctx)?),
+            "class" => Ok(self.get_info()?.class_name.to_lua(lua_ctx)?),
+            "exe" => Ok(self.get_info()?.exe.to_lua(lua_ctx)?),
+            "id" => Ok(self.id.to_lua(lua_ctx)?),
+            "visible" => Ok((self.get_show_state()? != ShowState::Hidden).to_lua(lua_ctx)?),
+            "minimized" => Ok((self.get_show_state()? == ShowState::Minimized).to_lua(lua_ctx)?),
+            "maximized" => Ok((self.get_show_state()? == ShowState::Maximized).to_lua(lua_ctx)?),
+            "owner" => self.get_relative(lua_ctx, self.backend.get_owner(self.id)),
+            "parent" => self.get_relative(lua_ctx, self.backend.get_parent(self.id)),
+            "monitor" => {
+                let monitor = self.backend.get_monitor(self.id).map_err(Error::from)?;
+                let table = lua_ctx.create_table()?;
+                table.set("rect", monitor.rect)?;
+                table.set("work_area", monitor.work_area)?;
+                Ok(rlua::Value::Table(table))
+            }
+            "client_rect" => Ok(self
+                .backend
+                .get_client_rect(self.id)
+                .map_err(Error::from)?
+                .to_lua(lua_ctx)?),
+            _ => Err(Error::KeyDoesNotExist(key.to_owned()).into()),
+        }
+    }
+
+    fn get_show_state(&self) -> Result<ShowState> {
+        Ok(self.backend.get_show_state(self.id)?)
+    }
+
+    /// The handle of the window `id` names, or nil.
+    fn get_relative<'lua>(
+        &self,
+        lua_ctx: rlua::Context<'lua>,
+        id: backend::Result<Option<WindowId>>,
+    ) -> rlua::Result<rlua::Value<'lua>> {
+        match id.map_err(Error::from)? {
+            Some(id) => Ok(rlua::Value::UserData(get_window_handle(
+                lua_ctx,
+                &self.backend,
+                id,
+            )?)),
+            None => Ok(rlua::Value::Nil),
+        }
+    }
+
+    fn show(&self, show_command: i32) -> Result<()> {
+        Ok(self.backend.show_window(self.id, show_command)?)
+    }
+
+    fn set_z_order(&self, z_order: ZOrder) -> Result<()> {
+        Ok(self.backend.set_z_order(self.id, z_order)?)
+    }
+
+    fn set_opacity(&self, opacity: f64) -> Result<()> {
+        if !(0.0..=1.0).contains(&opacity) {
+            return Err(Error::OpacityOutOfRange(opacity));
+        }
+        Ok(self.backend.set_opacity(self.id, opacity)?)
+    }
+}
+
+impl rlua::UserData for WindowHandle {
+    fn add_methods<'lua, M: rlua::UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("get_window_rect", |_, this, ()| Ok(this.get_window_rect()?));
+
+        methods.add_method("set_window_rect", |_, this, args: (i32, i32, i32, i32)| {
+            this.set_window_rect(args.0, args.1, args.2, args.3)?;
+            Ok(())
+        });
+
+        methods.add_method("focus", |_, this, ()| {
+            Ok(this.backend.activate(this.id).map_err(Error::from)?)
+        });
+        methods.add_method("minimize", |_, this, ()| {
+            Ok(this.show(backend::SW_MINIMIZE)?)
+        });
+        methods.add_method("maximize", |_, this, ()| {
+            Ok(this.show(backend::SW_MAXIMIZE)?)
+        });
+        methods.add_method("restore", |_, this, ()| Ok(this.show(backend::SW_RESTORE)?));
+        methods.add_method("hide", |_, this, ()| Ok(this.show(backend::SW_HIDE)?));
+        methods.add_method("show", |_, this, ()| Ok(this.show(backend::SW_SHOW)?));
+        methods.add_method("close", |_, this, ()| {
+            Ok(this.backend.close(this.id).map_err(Error::from)?)
+        });
+        methods.add_method("raise", |_, this, ()| Ok(this.set_z_order(ZOrder::Top)?));
+        methods.add_method("lower", |_, this, ()| Ok(this.set_z_order(ZOrder::Bottom)?));
+        methods.add_method("set_topmost", |_, this, topmost: bool| {
+            Ok(this.set_z_order(if topmost {
+                ZOrder::Topmost
+            } else {
+                ZOrder::NoTopmost
+            })?)
+        });
+        methods.add_method("set_opacity", |_, this, opacity: f64| {
+            Ok(this.set_opacity(opacity)?)
+        });
+
+        // Keys which aren't properties are the script's own fields
+        methods.add_meta_function(
+            rlua::MetaMethod::Index,
+            |lua_ctx, (window_handle, key): (rlua::AnyUserData, String)| {
+                let this = window_handle.borrow::<WindowHandle>()?;
+                if WindowHandle::PROPERTIES.contains(&key.as_str()) {
+                    return this.get_property(lua_ctx, &key);
+                }
+                match window_handle.get_user_value::<Option<rlua::Table>>()? {
+                    Some(fields) => fields.get(key),
+                    None => Ok(rlua::Value::Nil),
+                }
+            },
+        );
+
+        methods.add_meta_function(
+            rlua::MetaMethod::NewIndex,
+            |lua_ctx, (window_handle, key, value): (rlua::AnyUserData, String, rlua::Value)| {
+                if WindowHandle::PROPERTIES.contains(&key.as_str())
+                    || WindowHandle::METHODS.contains(&key.as_str())
+                {
+                    return Err(Error::ReadOnly(key).into());
+                }
+                let fields = match window_handle.get_user_value::<Option<rlua::Table>>()? {
+                    Some(fields) => fields,
+                    None => {
+                        let fields = lua_ctx.create_table()?;
+                        window_handle.set_user_value(fields.clone())?;
+                        fields
+                    }
+                };
+                fields.set(key, value)
+            },
+        );
+    }
+}
+
+/// What `wlw.windows` picks windows by. Filters left out match every
+/// window.
+#[derive(Default)]
+pub struct WindowFilter {
+    visible: Option<bool>,
+    minimized: Option<bool>,
+    class: Option<String>,
+    /// A Lua pattern, as `string.find` takes.
+    title: Option<String>,
+    pid: Option<u32>,
+    exe: Option<String>,
+}
+
+impl WindowFilter {
+    const KEYS: [&'static str; 6] = ["visible", "minimized", "class", "title", "pid", "exe"];
+
+    pub fn from_lua_table(table: Option<rlua::Table>) -> rlua::Result<Self> {
+        let table = match table {
+            Some(table) => table,
+            None => return Ok(WindowFilter::default()),
+        };
+        for pair in table.clone().pairs::<String, rlua::Value>() {
+            let (key, _) = pair?;
+            if !WindowFilter::KEYS.contains(&key.as_str()) {
+                return Err(Error::KeyDoesNotExist(key).into());
+            }
+        }
+        Ok(WindowFilter {
+            visible: table.get("visible")?,
+            minimized: table.get("minimized")?,
+            class: table.get("class")?,
+            title: table.get("title")?,
+            pid: table.get("pid")?,
+            exe: table.get("exe")?,
+        })
+    }
+
+    /// Executable names match whatever their case, as Windows has it.
+    /// Windows which are gone by the time they are looked at match nothing.
+    pub fn matches(&self, lua_ctx: rlua::Context, window: &WindowHandle) -> rlua::Result<bool> {
+        if self.visible.is_some() || self.minimized.is_some() {
+            let state = match unless_gone(window.get_show_state())? {
+                Some(state) => state,
+                None => return Ok(false),
+            };
+            if self
+                .visible
+                .is_some_and(|visible| visible != (state != ShowState::Hidden))
+                || self
+                    .minimized
+                    .is_some_and(|minimized| minimized != (state == ShowState::Minimized))
+            {
+                return Ok(false);
+            }
+        }
+        if self.class.is_some() || self.pid.is_some() || self.exe.is_some() {
+            let info = match unless_gone(window.get_info())? {
+                Some(info) => info,
+                None => return Ok(false),
+            };
+            if self
+                .class
+                .as_ref()
+                .is_some_and(|class| *class != info.class_name)
+                || self.pid.is_some_and(|pid| pid != info.pid)
+                || self
+                    .exe
+                    .as_ref()
+                    .is_some_and(|exe| !exe.eq_ignore_ascii_case(&info.exe))
+            {
+                return Ok(false);
+            }
+        }
+        if let Some(pattern) = &self.title {
+            let string: rlua::Table = lua_ctx.globals().get("string")?;
+            let find: rlua::Function = string.get("find")?;
+            let title = match unless_gone(window.get_title())? {
+                Some(title) => title,
+                None => return Ok(false),
+            };
+            let found: rlua::Value = find.call((title, pattern.as_str()))?;
+            if let rlua::Value::Nil = found {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// `None` for a window which has gone; windows can close at any moment
+/// between Lua learning of them and looking at them.
+fn unless_gone<T>(result: Result<T>) -> rlua::Result<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(Error::Backend(backend::Error::NoSuchWindow(_))) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub struct WindowStyle {
+    backend: Arc<dyn WindowBackend>,
+    id: WindowId,
+    style: u32,
+    ex_style: u32,
+}
+
+impl WindowStyle {
+    fn new(backend: Arc<dyn WindowBackend>, id: WindowId) -> Result<Self> {
+        let style = backend.get_style(id)?;
+        let ex_style = backend.get_ex_style(id)?;
+        Ok(WindowStyle {
+            backend,
+            id,
+            style,
+            ex_style,
+        })
+    }
+
+    fn get_style_flag(&self, key: String) -> Result<bool> {
+        match WindowStyle::str_to_style_flag(key.as_str()) {
+            Some(flag) => Ok(self.style & flag != 0),
+            None => match WindowStyle::str_to_ex_style_flag(key.as_str()) {
+                Some(flag) => Ok(self.ex_style & flag != 0),
+                None => Err(Error::KeyDoesNotExist(key)),
+            },
+        }
+    }
+
+    fn set_style_flag(&mut self, key: String, val: bool) -> Result<bool> {
+        match WindowStyle::str_to_style_flag(key.as_str()) {
+            Some(flag) => {
+                if val {
+                    self.style |= flag;
+                } else {
+                    self.style &= !flag;
+                }
+                self.backend.set_style(self.id, self.style)?;
+                Ok(val)
+            }
+            None => match WindowStyle::str_to_ex_style_flag(key.as_str()) {
+                Some(flag) => {
+                    if val {
+                        self.ex_style |= flag;
+                    } else {
+                        self.ex_style &= !flag;
+                    }
+                    self.backend.set_ex_style(self.id, self.ex_style)?;
+                    Ok(val)
+                }
+                None => Err(Error::KeyDoesNotExist(key)),
+            },
+        }
+    }
+
+    fn str_to_style_flag(key: &str) -> Option<u32> {
+        match key {
+            "border" => Some(backend::WS_BORDER),
+            "caption" => Some(backend::WS_CAPTION),
+            "child" => Some(backend::WS_CHILD),
+            "clipchildren" => Some(backend::WS_CLIPCHILDREN),
+            "clipsiblings" => Some(backend::WS_CLIPSIBLINGS),
+            "disabled" => Some(backend::WS_DISABLED),
+            "dlgframe" => Some(backend::WS_DLGFRAME),
+            "group" => Some(backend::WS_GROUP),
+            "hscroll" => Some(backend::WS_HSCROLL),
+            "iconic" => Some(backend::WS_ICONIC),
+            "maximize" => Some(backend::WS_MAXIMIZE),
+            "maximizebox" => Some(backend::WS_MAXIMIZEBOX),
+            "minimize" => Some(backend::WS_MINIMIZE),
+            "minimizebox" => Some(backend::WS_MINIMIZEBOX),
+            "popup" => Some(backend::WS_POPUP),
+            "sysmenu" => Some(backend::WS_SYSMENU),
+            "tabstop" => Some(backend::WS_TABSTOP),
+            "thickframe" => Some(backend::WS_THICKFRAME),
+            "visible" => Some(backend::WS_VISIBLE),
+            "vscroll" => Some(backend::WS_VSCROLL),
+            _ => None,
+        }
+    }
+
+    fn str_to_ex_style_flag(key: &str) -> Option<u32> {
+        match key {
+            "acceptfiles" => Some(backend::WS_EX_ACCEPTFILES),
+            "appwindow" => Some(backend::WS_EX_APPWINDOW),
+            "clientedge" => Some(backend::WS_EX_CLIENTEDGE),
+            "composited" => Some(backend::WS_EX_COMPOSITED),
+            "contexthelp" => Some(backend::WS_EX_CONTEXTHELP),
+            "controlparent" => Some(backend::WS_EX_CONTROLPARENT),
+            "dlgmodalframe" => Some(backend::WS_EX_DLGMODALFRAME),
+            "layered" => Some(backend::WS_EX_LAYERED),
+            "layoutrtl" => Some(backend::WS_EX_LAYOUTRTL),
+            "leftscrollbar" => Some(backend::WS_EX_LEFTSCROLLBAR),
+            "mdichild" => Some(backend::WS_EX_MDICHILD),
+            "noactivate" => Some(backend::WS_EX_NOACTIVATE),
+            "noinheritlayout" => Some(backend::WS_EX_NOINHERITLAYOUT),
+            "noparentnotify" => Some(backend::WS_EX_NOPARENTNOTIFY),
+            "noredirectionbitmap" => Some(backend::WS_EX_NOREDIRECTIONBITMAP),
+            "right" => Some(backend::WS_EX_RIGHT),
+            "rtlreading" => Some(backend::WS_EX_RTLREADING),
+            "staticedge" => Some(backend::WS_EX_STATICEDGE),
+            "toolwindow" => Some(backend::WS_EX_TOOLWINDOW),
+            "topmost" => Some(backend::WS_EX_TOPMOST),
+            "transparent" => Some(backend::WS_EX_TRANSPARENT),
+            "windowedge" => Some(backend::WS_EX_WINDOWEDGE),
+            _ => None,
+        }
+    }
+}
+
+impl rlua::UserData for WindowStyle {
+    fn add_methods<'lua, M: rlua::UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_meta_method(rlua::MetaMethod::Index, |_, this, key: String| {
+            Ok(this.get_style_flag(key)?)
+        });
+
+        methods.add_meta_method_mut(
+            rlua::MetaMethod::NewIndex,
+            |_, this, args: (String, bool)| {
+                let key = args.0;
+                let val = args.1;
+                Ok(this.set_style_flag(key, val)?)
+            },
+        );
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+/// The geometry below normalizes inverted rects before working on them, and
+/// treats rects as half-open: the right and bottom edges are just outside.
+impl Rect {
+    pub fn new(left: i32, top: i32, right: i32, bottom: i32) -> Self {
+        Rect {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    /// Negative sizes extend the rect left or up from `x` and `y`. `None`
+    /// if the far edges don't fit in an `i32`.
+    pub fn from_xywh(x: i32, y: i32, width: i32, height: i32) -> Option<Self> {
+        Some(Rect::new(x, y, x.checked_add(width)?, y.checked_add(height)?).normalized())
+    }
+
+    /// Saturates for rects wider than an `i32` can count.
+    pub fn width(&self) -> i32 {
+        self.right.saturating_sub(self.left)
+    }
+
+    /// Saturates for rects taller than an `i32` can count.
+    pub fn height(&self) -> i32 {
+        self.bottom.saturating_sub(self.top)
+    }
+
+    /// The same rect with its left edge left of its right and its top above
+    /// its bottom.
+    pub fn normalized(&self) -> Rect {
+        Rect::new(
+            cmp::min(self.left, self.right),
+            cmp::min(self.top, self.bottom),
+            cmp::max(self.left, self.right),
+            cmp::max(self.top, self.bottom),
+        )
+    }
+
+    pub fn contains_point(&self, x: i32, y: i32) -> bool {
+        let this = self.normalized();
+        this.left <= x && x < this.right && this.top <= y && y < this.bottom
+    }
+
+    pub fn contains(&self, other: Rect) -> bool {
+        let (this, other) = (self.normalized(), other.normalized());
+        this.left <= other.left
+            && other.right <= this.right
+            && this.top <= other.top
+            && other.bottom <= this.bottom
+    }
+
+    /// Whether the rects share any area. Touching edges don't count.
+    pub fn intersects(&self, other: Rect) -> bool {
+        self.intersection(other).is_some()
+    }
+
+    pub fn intersection(&self, other: Rect) -> Option<Rect> {
+        let (this, other) = (self.normalized(), other.normalized());
+        let intersection = Rect::new(
+            cmp::max(this.left, other.left),
+            cmp::max(this.top, other.top),
+            cmp::min(this.right, other.right),
+            cmp::min(this.bottom, other.bottom),
+        );
+        if intersection.width() > 0 && intersection.height() > 0 {
+            Some(intersection)
+        } else {
+            None
+        }
+    }
+
+    /// The smallest rect containing both.
+    pub fn union(&self, other: Rect) -> Rect {
+        let (this, other) = (self.normalized(), other.normalized());
+        Rect::new(
+            cmp::min(this.left, other.left),
+            cmp::min(this.top, other.top),
+            cmp::max(this.right, other.right),
+            cmp::max(this.bottom, other.bottom),
+        )
+    }
+
+    /// Moves every edge inwards, or outwards for negative amounts. A rect
+    /// inset past its size shrinks to nothing at its center. `None` if the
+    /// edges move past what an `i32` holds.
+    pub fn inset(&self, dx: i32, dy: i32) -> Option<Rect> {
+        let this = self.normalized();
+        let (center_x, center_y) = this.center();
+        let inset_edges = |start: i32, end: i32, d: i32, center: i32| {
+            if 2 * i64::from(d) > span(start, end) {
+                Some((center, center))
+            } else {
+                Some((start.checked_add(d)?, end.checked_sub(d)?))
+            }
+        };
+        let (left, right) = inset_edges(this.left, this.right, dx, center_x)?;
+        let (top, bottom) = inset_edges(this.top, this.bottom, dy, center_y)?;
+        Some(Rect::new(left, top, right, bottom))
+    }
+
+    /// `None` if the edges move past what an `i32` holds.
+    pub fn translate(&self, dx: i32, dy: i32) -> Option<Rect> {
+        let this = self.normalized();
+        Some(Rect::new(
+            this.left.checked_add(dx)?,
+            this.top.checked_add(dy)?,
+            this.right.checked_add(dx)?,
+            this.bottom.checked_add(dy)?,
+        ))
+    }
+
+    /// Rounded towards the top left.
+    pub fn center(&self) -> (i32, i32) {
+        let middle =
+            |start: i32, end: i32| (i64::from(start) + i64::from(end)).div_euclid(2) as i32;
+        (middle(self.left, self.right), middle(self.top, self.bottom))
+    }
+
+    /// Splits into a left and a right part, the left one `ratio` of the
+    /// width, which must be from 0 to 1.
+    pub fn split_h(&self, ratio: f64) -> Option<(Rect, Rect)> {
+        let this = self.normalized();
+        let split =
+            (i64::from(this.left) + split_offset(span(this.left, this.right), ratio)?) as i32;
+        Some((
+            Rect::new(this.left, this.top, split, this.bottom),
+            Rect::new(split, this.top, this.right, this.bottom),
+        ))
+    }
+
+    /// Splits into a top and a bottom part, the top one `ratio` of the
+    /// height, which must be from 0 to 1.
+    pub fn split_v(&self, ratio: f64) -> Option<(Rect, Rect)> {
+        let this = self.normalized();
+        let split =
+            (i64::from(this.top) + split_offset(span(this.top, this.bottom), ratio)?) as i32;
+        Some((
+            Rect::new(this.left, this.top, this.right, split),
+            Rect::new(this.left, split, this.right, this.bottom),
+        ))
+    }
+
+    /// Cuts the rect into `columns` by `rows` cells, row by row, which tile it
+    /// exactly even when it doesn't divide evenly. Fails for no cells or more
+    /// than `MAX_GRID_CELLS`.
+    fn grid(&self, columns: u32, rows: u32) -> Result<Vec<Rect>> {
+        if columns == 0 || rows == 0 {
+            return Err(Error::EmptyGrid);
+        }
+        if u64::from(columns) * u64::from(rows) > MAX_GRID_CELLS {
+            return Err(Error::GridTooLarge(columns, rows));
+        }
+        let this = self.normalized();
+        let edge = |start: i32, end: i32, count: u32, i: u32| {
+            (i64::from(start) + span(start, end) * i64::from(i) / i64::from(count)) as i32
+        };
+        let mut cells = Vec::with_capacity((columns * rows) as usize);
+        for row in 0..rows {
+            for column in 0..columns {
+                cells.push(Rect::new(
+                    edge(this.left, this.right, columns, column),
+                    edge(this.top, this.bottom, rows, row),
+                    edge(this.left, this.right, columns, column + 1),
+                    edge(this.top, this.bottom, rows, row + 1),
+                ));
+            }
+        }
+        Ok(cells)
+    }
+}
+
+/// The distance from `start` to `end`, which may not fit in an `i32`.
+fn span(start: i32, end: i32) -> i64 {
+    i64::from(end) - i64::from(start)
+}
+
+fn split_offset(length: i64, ratio: f64) -> Option<i64> {
+    if (0.0..=1.0).contains(&ratio) {
+        Some((length as f64 * ratio).round() as i64)
+    } else {
+        None
+    }
+}
+
+/// `wlw.rect(x, y, width, height)`.
+pub fn rect_function(lua_ctx: rlua::Context) -> rlua::Result<rlua::Function> {
+    lua_ctx.create_function(|_, (x, y, width, height): (i32, i32, i32, i32)| {
+        Ok(Rect::from_xywh(x, y, width, height).ok_or(Error::RectOverflow)?)
+    })
+}
+
+#[cfg(windows)]
+impl From<windows::RECT> for Rect {
+    fn from(rect: windows::RECT) -> Self {
+        Rect {
+            left: rect.left,
+            top: rect.top,
+            right: rect.right,
+            bottom: rect.bottom,
+        }
+    }
+}
+
+#[cfg(windows)]
+impl From<Rect> for windows::RECT {
+    fn from(rect: Rect) -> Self {
+        windows::RECT {
+            left: rect.left,
+            top: rect.top,
+            right: rect.right,
+            bottom: rect.bottom,
+        }
+    }
+}
+
+/// How a `Rect` lives in Lua. Rects go in and out by value, which leaves
+/// `Rect` free to take plain tables as well.
+struct RectUserData(Rect);
+
+impl Deref for RectUserData {
+    type Target = Rect;
+
+    fn deref(&self) -> &Rect {
+        &self.0
+    }
+}
+
+impl DerefMut for RectUserData {
+    fn deref_mut(&mut self) -> &mut Rect {
+        &mut self.0
+    }
+}
+
+impl<'lua> ToLua<'lua> for Rect {
+    fn to_lua(self, lua_ctx: rlua::Context<'lua>) -> rlua::Result<rlua::Value<'lua>> {
+        lua_ctx
+            .create_userdata(RectUserData(self))
+            .map(rlua::Value::UserData)
+    }
+}
+
+/// Takes a rect the way scripts write one: a rect, a table with `x`, `y`,
+/// `width` and `height`, or an array of those four. Where a script may leave
+/// a rect unchanged, take an `Option<Rect>`, which reads nil as `None`.
+impl<'lua> FromLua<'lua> for Rect {
+    fn from_lua(value: rlua::Value<'lua>, _: rlua::Context<'lua>) -> rlua::Result<Self> {
+        let not_a_rect = |from, message: &str| rlua::Error::FromLuaConversionError {
+            from,
+            to: "Rect",
+            message: Some(message.to_owned()),
+        };
+        let table = match value {
+            rlua::Value::UserData(userdata) => {
+                return match userdata.borrow::<RectUserData>() {
+                    Ok(rect) => Ok(**rect),
+                    Err(_) => Err(not_a_rect("userdata", "not a rect")),
+                };
+            }
+            rlua::Value::Table(table) => table,
+            value => {
+                return Err(not_a_rect(
+                    lua_type_name(&value),
+                    "expected a rect, {x=, y=, width=, height=} or {x, y, width, height}",
+                ));
+            }
+        };
+        let (x, y, width, height) = if table.contains_key("x")? {
+            (
+                rect_table_field(&table, "x")?,
+                rect_table_field(&table, "y")?,
+                rect_table_field(&table, "width")?,
+                rect_table_field(&table, "height")?,
+            )
+        } else if table.raw_len() == 4 {
+            (
+                rect_table_field(&table, 1)?,
+                rect_table_field(&table, 2)?,
+                rect_table_field(&table, 3)?,
+                rect_table_field(&table, 4)?,
+            )
+        } else {
+            return Err(not_a_rect(
+                "table",
+                "expected {x=, y=, width=, height=} or {x, y, width, height}",
+            ));
+        };
+        Rect::from_xywh(x, y, width, height)
+            .ok_or_else(|| not_a_rect("table", "does not fit in 32-bit coordinates"))
+    }
+}
+
+fn rect_table_field<'lua>(
+    table: &rlua::Table<'lua>,
+    key: impl ToLua<'lua> + fmt::Display + Copy,
+) -> rlua::Result<i32> {
+    match table.get::<_, Option<i32>>(key) {
+        Ok(Some(value)) => Ok(value),
+        Ok(None) => Err(rlua::Error::FromLuaConversionError {
+            from: "table",
+            to: "Rect",
+            message: Some(format!("missing {}", key)),
+        }),
+        Err(_) => Err(rlua::Error::FromLuaConversionError {
+            from: "table",
+            to: "Rect",
+            message: Some(format!("{} is not an integer", key)),
+        }),
+    }
+}
+
+/// `rlua::Value::type_name` is private to rlua.
+fn lua_type_name(value: &rlua::Value) -> &'static str {
+    match value {
+        rlua::Value::Nil => "nil",
+        rlua::Value::Boolean(_) => "boolean",
+        rlua::Value::LightUserData(_) => "lightuserdata",
+        rlua::Value::Integer(_) => "integer",
+        rlua::Value::Number(_) => "number",
+        rlua::Value::String(_) => "string",
+        rlua::Value::Table(_) => "table",
+        rlua::Value::Function(_) => "function",
+        rlua::Value::Thread(_) => "thread",
+        rlua::Value::UserData(_) => "userdata",
+        rlua::Value::Error(_) => "error",
+    }
+}
+
+impl rlua::UserData for RectUserData {
+    fn add_methods<'lua, M: rlua::UserDataMethods<'lua, Self>>(methods: &mut M) {
+        // Rects passed in are copied, and every method returns new ones
+        methods.add_method(
+            "contains",
+            |lua_ctx, this, (rect_or_x, y): (rlua::Value, Option<i32>)| match y {
+                Some(y) => Ok(this.contains_point(i32::from_lua(rect_or_x, lua_ctx)?, y)),
+                None => Ok(this.contains(Rect::from_lua(rect_or_x, lua_ctx)?)),
+            },
+        );
+        methods.add_method("intersects", |_, this, other: Rect| {
+            Ok(this.intersects(other))
+        });
+        methods.add_method("intersection", |_, this, other: Rect| {
+            Ok(this.intersection(other))
+        });
+        methods.add_method("union", |_, this, other: Rect| Ok(this.union(other)));
+        methods.add_method("inset", |_, this, (dx, dy): (i32, Option<i32>)| {
+            Ok(this
+                .inset(dx, dy.unwrap_or(dx))
+                .ok_or(Error::RectOverflow)?)
+        });
+        methods.add_method("translate", |_, this, (dx, dy): (i32, i32)| {
+            Ok(this.translate(dx, dy).ok_or(Error::RectOverflow)?)
+        });
+        methods.add_method("center", |_, this, ()| Ok(this.center()));
+        methods.add_method("normalize", |_, this, ()| Ok(this.normalized()));
+        methods.add_method("split_h", |_, this, ratio: f64| {
+            Ok(this.split_h(ratio).ok_or(Error::RatioOutOfRange(ratio))?)
+        });
+        methods.add_method("split_v", |_, this, ratio: f64| {
+            Ok(this.split_v(ratio).ok_or(Error::RatioOutOfRange(ratio))?)
+        });
+        methods.add_method("grid", |_, this, (columns, rows): (u32, u32)| {
+            Ok(this.grid(columns, rows)?)
+        });
+
+        methods.add_meta_method(rlua::MetaMethod::Eq, |_, this, other: Rect| {
+            Ok(this.normalized() == other.normalized())
+        });
+        methods.add_meta_method(rlua::MetaMethod::ToString, |_, this, ()| {
+            Ok(format!(
+                "Rect(x={}, y={}, width={}, height={})",
+                this.left,
+                this.top,
+                this.width(),
+                this.height()
+            ))
+        });
+
+        methods.add_meta_method(rlua::MetaMethod::Index, |_, this, key: String| {
+            match key.as_ref() {
+                "left" => Ok(this.left),
+                "top" => Ok(this.top),
+                "right" => Ok(this.right),
+                "bottom" => Ok(this.bottom),
+                "x" => Ok(this.left),
+                "y" => Ok(this.top),
+                "width" => Ok(this.width()),
+                "height" => Ok(this.height()),
+                _ => Err(Error::KeyDoesNotExist(key).into()),
+            }
+        });
+
+        methods.add_meta_method_mut(
+            rlua::MetaMethod::NewIndex,
+            |_, this, args: (String, i32)| {
+                let key = args.0;
+                let val = args.1;
+                match key.as_ref() {
+                    "left" => {
+                        this.left = val;
+                        Ok(val)
+                    }
+                    "top" => {
+                        this.top = val;
+                        Ok(val)
+                    }
+                    "right" => {
+                        this.right = val;
+                        Ok(val)
+                    }
+                    "bottom" => {
+                        this.bottom = val;
+                        Ok(val)
+                    }
+                    "x" => {
+                        this.left = val;
+                        Ok(val)
+                    }
+                    "y" => {
+                        this.top = val;
+                        Ok(val)
+                    }
+                    "width" => {
+                        this.right = this.left.checked_add(val).ok_or(Error::RectOverflow)?;
+                        Ok(val)
+                    }
+                    "height" => {
+                        this.bottom = this.top.checked_add(val).ok_or(Error::RectOverflow)?;
+                        Ok(val)
+                    }
+                    _ => Err(Error::KeyDoesNotExist(key).into()),
+                }
+            },
+        );
+    }
+}
+
+pub fn show_command_to_str(cmd: i32) -> Option<&'static str> {
+    match cmd {
+        backend::SW_FORCEMINIMIZE => Some("forceminimize"),
+        backend::SW_HIDE => Some("hide"),
+        backend::SW_MAXIMIZE => Some("maximize"),
+        backend::SW_MINIMIZE => Some("minimize"),
+        backend::SW_RESTORE => Some("restore"),
+        backend::SW_SHOW => Some("show"),
+        backend::SW_SHOWDEFAULT => Some("showdefault"),
+        backend::SW_SHOWMINIMIZED => Some("showminimized"),
+        backend::SW_SHOWMINNOACTIVE => Some("showminnoactive"),
+        backend::SW_SHOWNA => Some("showna"),
+        backend::SW_SHOWNOACTIVATE => Some("shownoactivate"),
+        backend::SW_SHOWNORMAL => Some("shownormal"),
+        _ => None,
+    }
+}
+
+/// Names the `SC_*` commands Windows defines, whose four low bits are its
+/// own business.
+pub fn syscommand_to_str(cmd: i32) -> Option<&'static str> {
+    match cmd & !0xF {
+        backend::SC_SIZE => Some("size"),
+        backend::SC_MOVE => Some("move"),
+        backend::SC_MINIMIZE => Some("minimize"),
+        backend::SC_MAXIMIZE => Some("maximize"),
+        backend::SC_NEXTWINDOW => Some("nextwindow"),
+        backend::SC_PREVWINDOW => Some("prevwindow"),
+        backend::SC_CLOSE => Some("close"),
+        backend::SC_VSCROLL => Some("vscroll"),
+        backend::SC_HSCROLL => Some("hscroll"),
+        backend::SC_MOUSEMENU => Some("mousemenu"),
+        backend::SC_KEYMENU => Some("keymenu"),
+        backend::SC_ARRANGE => Some("arrange"),
+        backend::SC_RESTORE => Some("restore"),
+        backend::SC_TASKLIST => Some("tasklist"),
+        backend::SC_SCREENSAVE => Some("screensave"),
+        backend::SC_HOTKEY => Some("hotkey"),
+        backend::SC_DEFAULT => Some("default"),
+        backend::SC_MONITORPOWER => Some("monitorpower"),
+        backend::SC_CONTEXTHELP => Some("contexthelp"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::simulated::{SimulatedBackend, SimulatedWindow};
+    use crate::backend::Monitor;
+
+    fn with_window(script: &str) -> Arc<SimulatedBackend> {
+        let backend = Arc::new(SimulatedBackend::new());
+        let id =
+            backend.create_window(SimulatedWindow::new("Notepad", Rect::new(10, 20, 110, 220)));
+        let lua = rlua::Lua::new();
+        lua.context(|lua_ctx| {
+            lua_ctx
+                .globals()
+                .set("w", WindowHandle::new(backend.clone(), id))?;
+            lua_ctx.load(script).exec()
+        })
+        .unwrap();
+        backend
+    }
+
+    #[test]
+    fn window_handle_reads_through_backend() {
+        with_window(
+            r#"
+            assert(w.title == "Notepad")
+            local rect = w:get_window_rect()
+            assert(rect.x == 10 and rect.y == 20)
+            assert(rect.width == 100 and rect.height == 200)
+            assert(w.style.caption)
+            assert(not w.style.toolwindow)
+            "#,
+        );
+    }
+
+    #[test]
+    fn window_handle_writes_through_backend() {
+        let backend = with_window(
+            r#"
+            w:set_window_rect(5, 6, 300, 400)
+            local style = w.style
+            style.thickframe = false
+            style.toolwindow = true
+            "#,
+        );
+        let window = backend.window(1).unwrap();
+        assert_eq!(window.rect, Rect::new(5, 6, 305, 406));
+        assert_eq!(window.style & backend::WS_THICKFRAME, 0);
+        assert_ne!(window.style & backend::WS_CAPTION, 0);
+        assert_ne!(window.ex_style & backend::WS_EX_TOOLWINDOW, 0);
+    }
+
+    #[test]
+    fn window_handle_refuses_rects_that_overflow() {
+        let backend = with_window(
+            r#"
+            local ok, e = pcall(w.set_window_rect, w, 2147483000, 0, 1000, 10)
+            assert(not ok and tostring(e):find("32-bit", 1, true))
+            "#,
+        );
+        assert_eq!(backend.window(1).unwrap().rect, Rect::new(10, 20, 110, 220));
+    }
+
+    #[test]
+    fn window_handle_acts_on_windows() {
+        let backend = Arc::new(SimulatedBackend::new());
+        let a = backend.create_window(SimulatedWindow::new("a", Rect::new(0, 0, 100, 100)));
+        let b = backend.create_window(SimulatedWindow::new("b", Rect::new(0, 0, 100, 100)));
+        let c = backend.create_window(SimulatedWindow::new("c", Rect::new(0, 0, 100, 100)));
+        let lua = rlua::Lua::new();
+        let run = |script: &str| {
+            lua.context(|lua_ctx| {
+                let globals = lua_ctx.globals();
+                globals.set("a", WindowHandle::new(backend.clone(), a))?;
+                globals.set("b", WindowHandle::new(backend.clone(), b))?;
+                globals.set("c", WindowHandle::new(backend.clone(), c))?;
+                lua_ctx.load(script).exec()
+            })
+        };
+
+        run("a:focus() c:lower() b:set_topmost(true)").unwrap();
+        assert_eq!(backend.focused(), Some(a));
+        assert_eq!(backend.z_order(), vec![b, a, c]);
+        run("b:set_topmost(false) b:lower() c:raise()").unwrap();
+        assert_eq!(backend.z_order(), vec![c, a, b]);
+
+        let show_state = |id| backend.get_show_state(id).unwrap();
+        run("a:maximize() b:minimize()").unwrap();
+        assert_eq!(show_state(a), ShowState::Maximized);
+        assert_eq!(show_state(b), ShowState::Minimized);
+        run("a:restore() b:hide()").unwrap();
+        assert_eq!(show_state(a), ShowState::Normal);
+        assert_eq!(show_state(b), ShowState::Hidden);
+        run("b:show()").unwrap();
+        assert_eq!(show_state(b), ShowState::Minimized);
+
+        run("a:set_opacity(0.5)").unwrap();
+        assert_eq!(backend.window(a).unwrap().opacity, 0.5);
+        assert!(run("a:set_opacity(1.5)").is_err());
+        assert_eq!(backend.window(a).unwrap().opacity, 0.5);
+
+        run("c:close()").unwrap();
+        assert!(backend.window(c).is_err());
+        assert!(run("c:focus()").is_err());
+    }
+
+    #[test]
+    fn window_handle_reports_its_owner() {
+        let backend = Arc::new(SimulatedBackend::new());
+        let mut window = SimulatedWindow::new("Notepad", Rect::new(0, 0, 1, 1));
+        window.class = "Notepad".to_owned();
+        window.pid = 42;
+        window.exe = "notepad.exe".to_owned();
+        let id = backend.create_window(window);
+        let mut hooked = WindowHandle::new(backend.clone(), id);
+        hooked.set_info(WindowInfo {
+            pid: 7,
+            thread_id: 8,
+            class_name: "Edit".to_owned(),
+            exe: "hooked.exe".to_owned(),
+        });
+        let lua = rlua::Lua::new();
+        lua.context(|lua_ctx| {
+            let globals = lua_ctx.globals();
+            globals.set("w", WindowHandle::new(backend.clone(), id))?;
+            globals.set("hooked", hooked)?;
+            lua_ctx
+                .load(
+                    r#"
+                    assert(w.pid == 42 and w.thread_id == 0)
+                    assert(w.class == "Notepad" and w.exe == "notepad.exe")
+                    assert(hooked.pid == 7 and hooked.thread_id == 8)
+                    assert(hooked.class == "Edit" and hooked.exe == "hooked.exe")
+                    assert(w.class_name == nil)
+                    "#,
+                )
+                .exec()
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn window_handle_reports_its_state_and_relatives() {
+        let left = Monitor::new(Rect::new(0, 0, 1920, 1080), Rect::new(0, 0, 1920, 1040));
+        let right = Monitor::new(
+            Rect::new(1920, 0, 3840, 1080),
+            Rect::new(1920, 0, 3840, 1080),
+        );
+        let mut main = SimulatedWindow::new("Main", Rect::new(2000, 100, 2400, 400));
+        main.class = "Main".to_owned();
+        main.style |= backend::WS_MAXIMIZE;
+        let simulated = SimulatedBackend::with_monitors(vec![left, right]);
+        let main = simulated.create_window(main);
+        let mut dialog = SimulatedWindow::new("Dialog", Rect::new(100, 100, 300, 200));
+        dialog.style = backend::WS_CAPTION | backend::WS_VISIBLE;
+        dialog.owner = Some(main);
+        let dialog = simulated.create_window(dialog);
+        let backend: Arc<dyn WindowBackend> = Arc::new(simulated);
+        let lua = rlua::Lua::new();
+        lua.context(|lua_ctx| {
+            lua_ctx.set_named_registry_value(WINDOW_TABLE, lua_ctx.create_table()?)?;
+            let globals = lua_ctx.globals();
+            globals.set("main", get_window_handle(lua_ctx, &backend, main)?)?;
+            globals.set("dialog", get_window_handle(lua_ctx, &backend, dialog)?)?;
+            globals.set("main_id", main)?;
+            lua_ctx
+                .load(
+                    r#"
+                    assert(main.id == main_id and main.class == "Main")
+                    assert(main.visible and main.maximized and not main.minimized)
+                    assert(main.owner == nil and main.parent == nil)
+                    assert(main.monitor.rect.x == 1920)
+                    assert(dialog.owner == main and dialog.parent == nil)
+                    assert(dialog.monitor.work_area.height == 1040)
+                    local client = dialog.client_rect
+                    assert(client.x == 100 and client.y == 123)
+                    assert(client.width == 200 and client.height == 77)
+                    "#,
+                )
+                .exec()
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn window_handle_keeps_script_fields() {
+        with_window(
+            r#"
+            assert(w.workspace == nil)
+            w.workspace = 3
+            w.tags = {"editor"}
+            assert(w.workspace == 3 and w.tags[1] == "editor")
+            w.workspace = nil
+            assert(w.workspace == nil)
+            assert(not pcall(function() w.title = "Renamed" end))
+            assert(w.title == "Notepad")
+            "#,
+        );
+    }
+
+    #[test]
+    fn window_handle_methods_cannot_be_overwritten() {
+        let backend = Arc::new(SimulatedBackend::new());
+        let id = backend.create_window(SimulatedWindow::new("", Rect::new(0, 0, 1, 1)));
+        let lua = rlua::Lua::new();
+        lua.context(|lua_ctx| {
+            lua_ctx
+                .globals()
+                .set("w", WindowHandle::new(backend.clone(), id))
+                .unwrap();
+            for method in &WindowHandle::METHODS {
+                let script = format!(
+                    r#"
+                    local ok, e = pcall(function() w.{0} = print end)
+                    assert(not ok and tostring(e):find("read-only: {0}", 1, true))
+                    assert(type(w.{0}) == "function" and w.{0} ~= print)
+                    "#,
+                    method
+                );
+                lua_ctx.load(&script).exec().unwrap();
+            }
+        });
+    }
+
+    #[test]
+    fn rects_split_and_grid_without_gaps() {
+        let rect = Rect::from_xywh(0, 0, 100, 51).unwrap();
+        assert_eq!(
+            rect.split_h(0.25),
+            Some((Rect::new(0, 0, 25, 51), Rect::new(25, 0, 100, 51)))
+        );
+        assert_eq!(rect.split_v(1.5), None);
+        let cells = rect.grid(3, 2).unwrap();
+        assert_eq!(cells.len(), 6);
+        assert_eq!(cells[0], Rect::new(0, 0, 33, 25));
+        assert_eq!(cells[4], Rect::new(33, 25, 66, 51));
+        assert_eq!(cells[5], Rect::new(66, 25, 100, 51));
+        assert!(matches!(rect.grid(0, 2), Err(Error::EmptyGrid)));
+    }
+
+    #[test]
+    fn rects_normalize_before_geometry() {
+        let inverted = Rect::new(100, 100, 0, 0);
+        assert_eq!(
+            Rect::from_xywh(100, 100, -100, -100),
+            Some(Rect::new(0, 0, 100, 100))
+        );
+        assert!(inverted.contains_point(0, 0) && !inverted.contains_point(100, 50));
+        assert_eq!(
+            inverted.intersection(Rect::new(50, 50, 150, 150)),
+            Some(Rect::new(50, 50, 100, 100))
+        );
+        assert!(!inverted.intersects(Rect::new(100, 0, 200, 100)));
+        assert_eq!(inverted.inset(10, 60), Some(Rect::new(10, 50, 90, 50)));
+        assert_eq!(inverted.center(), (50, 50));
+    }
+
+    #[test]
+    fn rects_refuse_to_overflow() {
+        let max = i32::MAX;
+        assert_eq!(Rect::from_xywh(max, 0, 1, 1), None);
+        assert_eq!(Rect::from_xywh(0, i32::MIN, 1, -1), None);
+        assert_eq!(
+            Rect::from_xywh(max - 1, 0, 1, 1),
+            Some(Rect::new(max - 1, 0, max, 1))
+        );
+
+        let everything = Rect::new(i32::MIN, i32::MIN, max, max);
+        assert_eq!(everything.width(), max);
+        assert_eq!(everything.center(), (-1, -1));
+        assert_eq!(everything.inset(-1, 0), None);
+        assert_eq!(everything.inset(max, max), Some(Rect::new(-1, -1, 0, 0)));
+        assert_eq!(everything.translate(1, 0), None);
+        assert_eq!(Rect::new(0, 0, 10, 10).translate(0, max), None);
+        assert_eq!(Rect::new(0, 0, 10, 10).inset(i32::MIN, 0), None);
+        assert_eq!(
+            everything.split_h(0.5).unwrap().0,
+            Rect::new(i32::MIN, i32::MIN, 0, max)
+        );
+        let cells = everything.grid(2, 1).unwrap();
+        assert_eq!(cells[1], Rect::new(-1, i32::MIN, max, max));
+
+        assert!(matches!(
+            everything.grid(u32::MAX, u32::MAX),
+            Err(Error::GridTooLarge(..))
+        ));
+        assert!(matches!(
+            everything.grid(65536, 65536),
+            Err(Error::GridTooLarge(..))
+        ));
+        assert_eq!(everything.grid(100, 100).unwrap().len(), 10_000);
+
+        let lua = rlua::Lua::new();
+        lua.context(|lua_ctx| {
+            lua_ctx
+                .globals()
+                .set("r", Rect::new(0, 0, max, max))
+                .unwrap();
+            let error = |source: &str| -> String {
+                lua_ctx
+                    .load(&format!(
+                        "return tostring(select(2, pcall(function() {} end)))",
+                        source
+                    ))
+                    .eval()
+                    .unwrap()
+            };
+            assert!(error("r:translate(1, 0)").contains("32-bit"));
+            assert!(error("r:inset(-1)").contains("32-bit"));
+            assert!(error("r.x = 1; r.width = 2147483647").contains("32-bit"));
+            assert!(error("r:grid(1000000, 1000000)").contains("at most 10000 cells"));
+            let from_table: rlua::Result<Rect> = lua_ctx
+                .load("{x = 2147483647, y = 0, width = 1, height = 1}")
+                .eval();
+            assert!(from_table.unwrap_err().to_string().contains("32-bit"));
+        });
+    }
+
+    #[test]
+    fn rects_come_from_tables_and_arrays() {
+        let lua = rlua::Lua::new();
+        lua.context(|lua_ctx| {
+            let rect = |source: &str| -> rlua::Result<Rect> { lua_ctx.load(source).eval() };
+            let expected = Rect::new(10, 20, 110, 220);
+            assert_eq!(
+                rect("{x = 10, y = 20, width = 100, height = 200}").unwrap(),
+                expected
+            );
+            assert_eq!(rect("{10, 20, 100, 200}").unwrap(), expected);
+            assert_eq!(rect("{110, 220, -100, -200}").unwrap(), expected);
+            let error = |source: &str| rect(source).unwrap_err().to_string();
+            assert!(error("{x = 1, y = 2, width = 'wide', height = 4}")
+                .contains("width is not an integer"));
+            assert!(error("{1, 2, 3}").contains("{x, y, width, height}"));
+            assert!(error("'rect'").contains("string"));
+            assert!(error("nil").contains("nil"));
+            let optional: Option<Rect> = lua_ctx.load("nil").eval().unwrap();
+            assert_eq!(optional, None);
+        });
+    }
+
+    #[test]
+    fn unknown_keys_read_as_nil() {
+        let backend = Arc::new(SimulatedBackend::new());
+        let id = backend.create_window(SimulatedWindow::new("", Rect::new(0, 0, 1, 1)));
+        let lua = rlua::Lua::new();
+        lua.context(|lua_ctx| {
+            lua_ctx
+                .globals()
+                .set("w", WindowHandle::new(backend.clone(), id))
+                .unwrap();
+            assert!(lua_ctx.load("assert(w.nonsense == nil)").exec().is_ok());
+            assert!(lua_ctx.load("return w.style.nonsense").exec().is_err());
+        });
+    }
+}
@@ -0,0 +1,869 @@
+#[cfg(windows)]
+mod namedpipe;
+#[cfg(unix)]
+mod unixsocket;
+
+#[cfg(unix)]
+pub use self::unixsocket::socket_path;
+
+use crate::clock::Clock;
+use crate::codec::{self, Message};
+#[cfg(windows)]
+use crate::windows;
+use crossbeam_channel as xchan;
+use std::cell::RefCell;
+use std::cmp;
+use std::collections::{HashMap, VecDeque};
+use std::error;
+use std::fmt;
+use std::io;
+use std::marker::PhantomData;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+#[cfg(windows)]
+type PlatformTransport = namedpipe::NamedPipeTransport;
+#[cfg(unix)]
+type PlatformTransport = unixsocket::UnixSocketTransport;
+
+#[derive(Debug)]
+pub enum Error {
+    #[cfg(windows)]
+    Windows(windows::Error),
+    Io(io::Error),
+    Codec(codec::Error),
+    ShortWrite {
+        expected: usize,
+        actual: usize,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            #[cfg(windows)]
+            Error::Windows(e) => write!(f, "{}", e),
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Codec(e) => write!(f, "{}", e),
+            Error::ShortWrite { expected, actual } => {
+                write!(f, "Wrote {} bytes of a {} byte frame", actual, expected)
+            }
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+#[cfg(windows)]
+impl From<windows::Error> for Error {
+    fn from(error: windows::Error) -> Self {
+        Error::Windows(error)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<codec::Error> for Error {
+    fn from(error: codec::Error) -> Self {
+        Error::Codec(error)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+enum IoState {
+    Pending,
+    Finished(usize),
+}
+
+/// Wakes the poll thread from any other thread.
+trait Signal: Send + Sync {
+    fn set(&self) -> Result<()>;
+}
+
+/// A pipe can have one operation pending each way at once: connecting or
+/// reading in, and writing out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Direction {
+    In,
+    Out,
+}
+
+enum Wakeup {
+    Stop,
+    ResponseReady,
+    Pipe(usize),
+    Timeout,
+}
+
+/// Rounds up, so that waits end no earlier than asked, and stops short of the
+/// values which mean waiting forever.
+fn whole_millis(timeout: Duration) -> u32 {
+    cmp::min(timeout.as_micros().div_ceil(1000), i32::MAX as u128) as u32
+}
+
+/// The OS half of a pipe server: a growable set of pipes, each of which
+/// serves one client at a time. Operations either finish immediately or stay
+/// pending until `wait` reports their pipe, after which `finish` completes
+/// them, or reports them still pending if it was the other direction which
+/// woke the pipe.
+trait Transport: Sized {
+    type Signal: Signal + 'static;
+
+    fn new_signal() -> Result<Self::Signal>;
+
+    fn new(
+        name: &str,
+        input_size: usize,
+        output_size: usize,
+        stop: Arc<Self::Signal>,
+        response_ready: Arc<Self::Signal>,
+    ) -> Result<Self>;
+
+    fn add_pipe(&mut self) -> Result<usize>;
+
+    fn connect(&mut self, index: usize) -> Result<IoState>;
+
+    /// `data` must stay valid until the read finishes.
+    unsafe fn read(&mut self, index: usize, data: *mut u8, size: usize) -> Result<IoState>;
+
+    /// `data` must stay valid until the write finishes.
+    unsafe fn write(&mut self, index: usize, data: *const u8, size: usize) -> Result<IoState>;
+
+    fn finish(&mut self, index: usize, direction: Direction) -> Result<IoState>;
+
+    fn disconnect(&mut self, index: usize) -> Result<()>;
+
+    /// Waits forever without a `timeout`.
+    fn wait(&mut self, timeout: Option<Duration>) -> Result<Wakeup>;
+}
+
+/// Gives the answer to a request in place of the one which did not come in
+/// time.
+pub type Fallback<ReqType, ResType> = Box<dyn Fn(&ReqType) -> Option<ResType> + Send>;
+
+/// Has the server answer requests itself once they go unanswered for too
+/// long, so that clients are never held up for longer than `timeout`.
+pub struct Deadline<ReqType, ResType> {
+    pub timeout: Duration,
+    pub fallback: Fallback<ReqType, ResType>,
+    pub clock: Arc<dyn Clock>,
+}
+
+pub struct Request<ReqType: Message, ResType: Message> {
+    pub message: ReqType,
+    index: usize,
+    generation: usize,
+    message_id: u32,
+    /// Nothing goes back for notifications.
+    wants_reply: bool,
+    /// Dropping a request which has not been answered acknowledges it.
+    answered: bool,
+    signal: Arc<dyn Signal>,
+    channel: xchan::Sender<Response<ResType>>,
+}
+
+struct Response<ResType: Message> {
+    message: Option<ResType>,
+    index: usize,
+    generation: usize,
+    message_id: u32,
+}
+
+impl<ReqType: Message, ResType: Message> Request<ReqType, ResType> {
+    /// A request from no client at all, for testing whatever handles
+    /// requests without any pipes.
+    #[cfg(test)]
+    pub fn fake(message: ReqType) -> (Self, FakeClient<ResType>) {
+        let (channel, responses) = xchan::unbounded();
+        let request = Request {
+            message,
+            index: 0,
+            generation: 0,
+            message_id: 0,
+            wants_reply: true,
+            answered: false,
+            signal: Arc::new(NoSignal),
+            channel,
+        };
+        (request, FakeClient { responses })
+    }
+
+    /// A request from no client at all which waits for no answer.
+    #[cfg(test)]
+    pub fn fake_notification(message: ReqType) -> Self {
+        let (mut request, _) = Request::fake(message);
+        request.wants_reply = false;
+        request
+    }
+
+    /// Whether the client waits for the answer, rather than having sent a
+    /// notification.
+    pub fn wants_reply(&self) -> bool {
+        self.wants_reply
+    }
+
+    pub fn respond(mut self, message: ResType) {
+        self.send(Some(message));
+    }
+
+    pub fn acknowledge(mut self) {
+        self.send(None);
+    }
+
+    fn send(&mut self, message: Option<ResType>) {
+        self.answered = true;
+        if !self.wants_reply {
+            return;
+        }
+        // Queue the response before waking the poll thread so that it is
+        // there to be drained. A server which has stopped has nobody left to
+        // answer.
+        let response = Response {
+            message,
+            index: self.index,
+            generation: self.generation,
+            message_id: self.message_id,
+        };
+        if self.channel.send(response).is_ok() {
+            self.signal.set().unwrap();
+        }
+    }
+}
+
+/// Whatever path a request takes, its client is not left waiting on it.
+impl<ReqType: Message, ResType: Message> Drop for Request<ReqType, ResType> {
+    fn drop(&mut self) {
+        if !self.answered {
+            self.send(None);
+        }
+    }
+}
+
+#[cfg(test)]
+struct NoSignal;
+
+#[cfg(test)]
+impl Signal for NoSignal {
+    fn set(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Where the answer to a fake request goes.
+#[cfg(test)]
+pub struct FakeClient<ResType: Message> {
+    responses: xchan::Receiver<Response<ResType>>,
+}
+
+#[cfg(test)]
+impl<ResType: Message> FakeClient<ResType> {
+    /// `None` until the request is answered, then `Some` of the response,
+    /// which is itself `None` for an acknowledgement.
+    pub fn answer(&self) -> Option<Option<ResType>> {
+        self.responses
+            .try_recv()
+            .ok()
+            .map(|response| response.message)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum ConnectionState {
+    Disconnected,
+    Connecting,
+    Greeting,
+    /// Reading requests, and writing answers as they come.
+    Connected,
+}
+
+enum PollAction<HelloType: Message, ReqType: Message> {
+    DoNothing,
+    CheckHello(HelloType),
+    DispatchRequest(codec::Header, ReqType),
+    ReadNext,
+}
+
+/// A request whose client waits for the answer.
+struct Outstanding<ResType> {
+    /// When the server stops waiting on the request, and what it answers
+    /// then.
+    deadline: Option<Instant>,
+    fallback: Option<ResType>,
+}
+
+/// Between polls, a connection other than a disconnected one always has an
+/// operation pending in: the connect, the read of the hello, or the read of
+/// the next request.
+struct Connection<HelloType: Message, ReqType: Message, ResType: Message> {
+    index: usize,
+    /// Bumped on every disconnect, so that answers meant for an earlier
+    /// client are dropped.
+    generation: usize,
+    num_free_connections: Rc<RefCell<usize>>,
+    state: ConnectionState,
+    /// Requests which are still to be answered, by message id.
+    outstanding: HashMap<u32, Outstanding<ResType>>,
+    /// Whether `write_buffer` is being written.
+    writing: bool,
+    /// Frames waiting for the write in progress to finish.
+    queued_writes: VecDeque<Vec<u8>>,
+    // On the heap so that pending I/O survives the connection list growing
+    read_buffer: Vec<u8>,
+    write_buffer: Vec<u8>,
+    types: PhantomData<(HelloType, ReqType, ResType)>,
+}
+
+impl<HelloType: Message, ReqType: Message, ResType: Message>
+    Connection<HelloType, ReqType, ResType>
+{
+    fn new(index: usize, num_free_connections: Rc<RefCell<usize>>) -> Self {
+        Connection {
+            index,
+            generation: 0,
+            num_free_connections,
+            state: ConnectionState::Disconnected,
+            outstanding: HashMap::new(),
+            writing: false,
+            queued_writes: VecDeque::new(),
+            read_buffer: vec![0; codec::MAX_FRAME_SIZE],
+            write_buffer: Vec::new(),
+            types: PhantomData,
+        }
+    }
+
+    /// Answers the request with id `message_id`, with an empty frame when
+    /// there is nothing to say.
+    fn respond(
+        &mut self,
+        transport: &mut impl Transport,
+        message_id: u32,
+        response: Option<ResType>,
+    ) -> Result<()> {
+        assert_eq!(self.state, ConnectionState::Connected);
+        let frame = match response {
+            Some(response) => codec::encode_with_id(&response, 0, message_id)?,
+            None => codec::empty_frame(message_id),
+        };
+        self.queued_writes.push_back(frame);
+        self.flush(transport)
+    }
+
+    /// Writes queued frames until one is left pending or none are left.
+    fn flush(&mut self, transport: &mut impl Transport) -> Result<()> {
+        while !self.writing {
+            match self.queued_writes.pop_front() {
+                Some(frame) => self.write_buffer = frame,
+                None => break,
+            }
+            match unsafe {
+                transport.write(
+                    self.index,
+                    self.write_buffer.as_ptr(),
+                    self.write_buffer.len(),
+                )
+            }? {
+                IoState::Finished(num_transferred) => self.check_write(num_transferred)?,
+                IoState::Pending => self.writing = true,
+            }
+        }
+        Ok(())
+    }
+
+    fn check_write(&self, num_transferred: usize) -> Result<()> {
+        if num_transferred != self.write_buffer.len() {
+            return Err(Error::ShortWrite {
+                expected: self.write_buffer.len(),
+                actual: num_transferred,
+            });
+        }
+        Ok(())
+    }
+
+    fn connect(
+        &mut self,
+        transport: &mut impl Transport,
+    ) -> Result<PollAction<HelloType, ReqType>> {
+        if self.state != ConnectionState::Disconnected {
+            panic!("Tried to connect an already-active pipe");
+        }
+        match transport.connect(self.index)? {
+            IoState::Finished(_) => self.on_new_connection(transport),
+            IoState::Pending => {
+                self.state = ConnectionState::Connecting;
+                Ok(PollAction::DoNothing)
+            }
+        }
+    }
+
+    fn read_hello(
+        &mut self,
+        transport: &mut impl Transport,
+    ) -> Result<PollAction<HelloType, ReqType>> {
+        match unsafe {
+            transport.read(
+                self.index,
+                self.read_buffer.as_mut_ptr(),
+                self.read_buffer.len(),
+            )
+        }? {
+            IoState::Finished(num_transferred) => self.on_hello_complete(num_transferred),
+            IoState::Pending => Ok(PollAction::DoNothing),
+        }
+    }
+
+    fn read(&mut self, transport: &mut impl Transport) -> Result<PollAction<HelloType, ReqType>> {
+        match unsafe {
+            transport.read(
+                self.index,
+                self.read_buffer.as_mut_ptr(),
+                self.read_buffer.len(),
+            )
+        }? {
+            IoState::Finished(num_transferred) => self.on_read_complete(transport, num_transferred),
+            IoState::Pending => Ok(PollAction::DoNothing),
+        }
+    }
+
+    fn on_signalled(
+        &mut self,
+        transport: &mut impl Transport,
+    ) -> Result<PollAction<HelloType, ReqType>> {
+        match self.state {
+            ConnectionState::Connecting => match transport.finish(self.index, Direction::In)? {
+                IoState::Finished(_) => self.on_new_connection(transport),
+                // Another pipe took the client
+                IoState::Pending => Ok(PollAction::DoNothing),
+            },
+            ConnectionState::Greeting => match transport.finish(self.index, Direction::In)? {
+                IoState::Finished(num_transferred) => self.on_hello_complete(num_transferred),
+                IoState::Pending => Ok(PollAction::DoNothing),
+            },
+            ConnectionState::Connected => {
+                // One wakeup may be for both directions
+                if self.writing {
+                    if let IoState::Finished(num_transferred) =
+                        transport.finish(self.index, Direction::Out)?
+                    {
+                        self.writing = false;
+                        self.check_write(num_transferred)?;
+                        self.flush(transport)?;
+                    }
+                }
+                match transport.finish(self.index, Direction::In)? {
+                    IoState::Finished(num_transferred) => {
+                        self.on_read_complete(transport, num_transferred)
+                    }
+                    IoState::Pending => Ok(PollAction::DoNothing),
+                }
+            }
+            ConnectionState::Disconnected => panic!("Disconnected state somehow signalled"),
+        }
+    }
+
+    fn on_new_connection(
+        &mut self,
+        transport: &mut impl Transport,
+    ) -> Result<PollAction<HelloType, ReqType>> {
+        *self.num_free_connections.borrow_mut() -= 1;
+        // Connected from here on, even if the reads below finish or fail
+        // straight away
+        self.state = ConnectionState::Greeting;
+        // Clients introduce themselves before anything else
+        self.read_hello(transport)
+    }
+
+    fn on_hello_complete(
+        &mut self,
+        num_transferred: usize,
+    ) -> Result<PollAction<HelloType, ReqType>> {
+        let hello = codec::decode(&self.read_buffer[..num_transferred])?;
+        Ok(PollAction::CheckHello(hello))
+    }
+
+    fn on_read_complete(
+        &mut self,
+        transport: &mut impl Transport,
+        num_transferred: usize,
+    ) -> Result<PollAction<HelloType, ReqType>> {
+        let frame = &self.read_buffer[..num_transferred];
+        let header = match codec::decode_header(frame) {
+            Ok(header) => header,
+            // A client waiting on a frame too mangled to say so waits in vain
+            Err(e) => {
+                warn!("Dropping malformed message: {}", e);
+                return Ok(PollAction::ReadNext);
+            }
+        };
+        match codec::decode(frame) {
+            Ok(request) => return Ok(PollAction::DispatchRequest(header, request)),
+            // Most likely from a newer client, which can carry on without us
+            Err(codec::Error::UnknownKind(kind)) => {
+                debug!("Skipping message of unknown kind {}", kind)
+            }
+            // One bad message is no reason to give up on the client
+            Err(e) => warn!("Dropping malformed message: {}", e),
+        }
+        if header.flags & codec::FLAG_WANTS_REPLY != 0 {
+            self.respond(transport, header.id, None)?;
+        }
+        Ok(PollAction::ReadNext)
+    }
+
+    fn disconnect(&mut self, transport: &mut impl Transport) -> Result<()> {
+        if self.state == ConnectionState::Disconnected {
+            panic!("Tried to disconnect an already-inactive pipe");
+        }
+        if self.state != ConnectionState::Connecting {
+            *self.num_free_connections.borrow_mut() += 1;
+        }
+        self.generation += 1;
+        self.outstanding.clear();
+        self.writing = false;
+        self.queued_writes.clear();
+        self.state = ConnectionState::Disconnected;
+        transport.disconnect(self.index)
+    }
+
+    fn reconnect(
+        &mut self,
+        transport: &mut impl Transport,
+    ) -> Result<PollAction<HelloType, ReqType>> {
+        if self.state != ConnectionState::Disconnected {
+            self.disconnect(transport)?;
+        }
+        self.connect(transport)
+    }
+}
+
+struct ConnectionList<T: Transport, HelloType: Message, ReqType: Message, ResType: Message> {
+    // Free connections before the transport
+    connections: Vec<Connection<HelloType, ReqType, ResType>>,
+    transport: T,
+    response_ready_signal: Arc<T::Signal>,
+    num_free_connections: Rc<RefCell<usize>>,
+    on_hello: Box<dyn Fn(HelloType) -> bool + Send>,
+    on_new_request: Box<dyn Fn(Request<ReqType, ResType>) + Send>,
+    incoming_response_channel: xchan::Receiver<Response<ResType>>,
+    outgoing_response_channel: xchan::Sender<Response<ResType>>,
+    deadline: Option<Deadline<ReqType, ResType>>,
+    num_timeouts: Arc<AtomicUsize>,
+}
+
+impl<T: Transport, HelloType: Message, ReqType: Message, ResType: Message>
+    ConnectionList<T, HelloType, ReqType, ResType>
+{
+    fn new(
+        pipe_name: &str,
+        stop_signal: Arc<T::Signal>,
+        num_free_connections: Rc<RefCell<usize>>,
+        on_hello: Box<dyn Fn(HelloType) -> bool + Send>,
+        on_new_request: Box<dyn Fn(Request<ReqType, ResType>) + Send>,
+        deadline: Option<Deadline<ReqType, ResType>>,
+        num_timeouts: Arc<AtomicUsize>,
+    ) -> Result<Self> {
+        let (outgoing_response_channel, incoming_response_channel) = xchan::unbounded();
+        let response_ready_signal = Arc::new(T::new_signal()?);
+        let transport = T::new(
+            pipe_name,
+            codec::MAX_FRAME_SIZE,
+            codec::MAX_FRAME_SIZE,
+            stop_signal,
+            response_ready_signal.clone(),
+        )?;
+        Ok(ConnectionList {
+            connections: Vec::new(),
+            transport,
+            response_ready_signal,
+            num_free_connections,
+            on_hello,
+            on_new_request,
+            incoming_response_channel,
+            outgoing_response_channel,
+            deadline,
+            num_timeouts,
+        })
+    }
+
+    fn grow(&mut self, amount: usize) -> Result<()> {
+        *self.num_free_connections.borrow_mut() += amount;
+        self.connections.reserve(amount);
+        for _ in 0..amount {
+            let index = self.transport.add_pipe()?;
+            self.connections
+                .push(Connection::new(index, self.num_free_connections.clone()));
+            let result = self.connections[index].connect(&mut self.transport);
+            self.process(index, result);
+        }
+        Ok(())
+    }
+
+    fn poll(&mut self) -> Result<bool> {
+        let timeout = self.time_to_next_deadline();
+        match self.transport.wait(timeout)? {
+            Wakeup::Stop => return Ok(true),
+            Wakeup::ResponseReady => {
+                // Several responses may share one wakeup
+                while let Ok(response) = self.incoming_response_channel.try_recv() {
+                    let index = response.index;
+                    let conn = &mut self.connections[index];
+                    let is_outstanding = conn.generation == response.generation
+                        && conn.outstanding.remove(&response.message_id).is_some();
+                    let result = if is_outstanding {
+                        conn.respond(&mut self.transport, response.message_id, response.message)
+                            .map(|()| PollAction::DoNothing)
+                    } else {
+                        debug!("Dropping response to a request already answered or abandoned");
+                        Ok(PollAction::DoNothing)
+                    };
+                    self.process(index, result);
+                }
+            }
+            Wakeup::Pipe(index) => {
+                let result = self.connections[index].on_signalled(&mut self.transport);
+                self.process(index, result);
+            }
+            Wakeup::Timeout => {}
+        }
+        // Whatever woke the thread, requests past their deadline are answered
+        // before it sleeps again
+        self.answer_overdue_requests();
+        Ok(false)
+    }
+
+    fn time_to_next_deadline(&self) -> Option<Duration> {
+        let now = self.deadline.as_ref()?.clock.now();
+        self.connections
+            .iter()
+            .flat_map(|conn| conn.outstanding.values())
+            .filter_map(|outstanding| outstanding.deadline)
+            .min()
+            .map(|deadline| deadline.saturating_duration_since(now))
+    }
+
+    fn answer_overdue_requests(&mut self) {
+        let (now, timeout) = match &self.deadline {
+            Some(deadline) => (deadline.clock.now(), deadline.timeout),
+            None => return,
+        };
+        for index in 0..self.connections.len() {
+            let conn = &mut self.connections[index];
+            let overdue: Vec<u32> = conn
+                .outstanding
+                .iter()
+                .filter(|(_, outstanding)| outstanding.deadline.is_some_and(|d| d <= now))
+                .map(|(&message_id, _)| message_id)
+                .collect();
+            let mut result = Ok(());
+            for message_id in overdue {
+                // No longer outstanding, so the response still to come is
+                // dropped
+                let outstanding = conn.outstanding.remove(&message_id).unwrap();
+                let num_timeouts = self.num_timeouts.fetch_add(1, Ordering::Relaxed) + 1;
+                warn!(
+                    "Request unanswered after {:?}, answering it without waiting further ({} so far)",
+                    timeout, num_timeouts
+                );
+                result = conn.respond(&mut self.transport, message_id, outstanding.fallback);
+                if result.is_err() {
+                    break;
+                }
+            }
+            self.process(index, result.map(|()| PollAction::DoNothing));
+        }
+    }
+
+    fn process(&mut self, index: usize, mut result: Result<PollAction<HelloType, ReqType>>) {
+        loop {
+            let conn = &mut self.connections[index];
+            result = match result {
+                Ok(PollAction::DoNothing) => break,
+                Ok(PollAction::CheckHello(hello)) => {
+                    if (self.on_hello)(hello) {
+                        conn.state = ConnectionState::Connected;
+                        conn.read(&mut self.transport)
+                    } else {
+                        conn.reconnect(&mut self.transport)
+                    }
+                }
+                Ok(PollAction::DispatchRequest(header, message)) => {
+                    let wants_reply = header.flags & codec::FLAG_WANTS_REPLY != 0;
+                    if wants_reply {
+                        let outstanding = match &self.deadline {
+                            Some(deadline) => Outstanding {
+                                deadline: Some(deadline.clock.now() + deadline.timeout),
+                                fallback: (deadline.fallback)(&message),
+                            },
+                            None => Outstanding {
+                                deadline: None,
+                                fallback: None,
+                            },
+                        };
+                        if conn.outstanding.insert(header.id, outstanding).is_some() {
+                            warn!(
+                                "Client reused message id {} before it was answered",
+                                header.id
+                            );
+                        }
+                    }
+                    let request = Request {
+                        index,
+                        generation: conn.generation,
+                        message_id: header.id,
+                        wants_reply,
+                        answered: false,
+                        message,
+                        signal: self.response_ready_signal.clone(),
+                        channel: self.outgoing_response_channel.clone(),
+                    };
+                    (self.on_new_request)(request);
+                    // Carry on reading; the answer goes out whenever it comes
+                    conn.read(&mut self.transport)
+                }
+                Ok(PollAction::ReadNext) => conn.read(&mut self.transport),
+                Err(e) => {
+                    error!("Pipe connection problem: {}", e);
+                    conn.reconnect(&mut self.transport)
+                }
+            };
+        }
+    }
+}
+
+/// Serves requests from clients which each open with a hello message, which
+/// decides whether the server talks to them at all.
+pub struct PipeServer<HelloType: Message, ReqType: Message, ResType: Message> {
+    poll_thread: Option<JoinHandle<()>>,
+    poll_thread_stop_signal: Arc<dyn Signal>,
+    num_timeouts: Arc<AtomicUsize>,
+    hellotype: PhantomData<HelloType>,
+    reqtype: PhantomData<ReqType>,
+    restype: PhantomData<ResType>,
+}
+
+impl<
+        HelloType: Message + Send + 'static,
+        ReqType: Message + Send + 'static,
+        ResType: Message + Send + 'static,
+    > PipeServer<HelloType, ReqType, ResType>
+{
+    /// Clients whose hello `on_hello` turns down are disconnected.
+    pub fn new(
+        pipe_name: impl AsRef<str>,
+        on_hello: impl Fn(HelloType) -> bool + Send + 'static,
+        on_new_request: impl Fn(Request<ReqType, ResType>) + Send + 'static,
+        on_fail: impl FnOnce(Error) + Send + 'static,
+    ) -> Result<Self> {
+        Self::with_transport::<PlatformTransport>(
+            pipe_name,
+            None,
+            on_hello,
+            on_new_request,
+            on_fail,
+        )
+    }
+
+    /// Like `new`, but clients waiting on a request get `deadline`'s
+    /// fallback once it goes unanswered for too long, each request timed on
+    /// its own. The late response is then dropped.
+    pub fn with_deadline(
+        pipe_name: impl AsRef<str>,
+        deadline: Deadline<ReqType, ResType>,
+        on_hello: impl Fn(HelloType) -> bool + Send + 'static,
+        on_new_request: impl Fn(Request<ReqType, ResType>) + Send + 'static,
+        on_fail: impl FnOnce(Error) + Send + 'static,
+    ) -> Result<Self> {
+        Self::with_transport::<PlatformTransport>(
+            pipe_name,
+            Some(deadline),
+            on_hello,
+            on_new_request,
+            on_fail,
+        )
+    }
+
+    fn with_transport<T: Transport>(
+        pipe_name: impl AsRef<str>,
+        deadline: Option<Deadline<ReqType, ResType>>,
+        on_hello: impl Fn(HelloType) -> bool + Send + 'static,
+        on_new_request: impl Fn(Request<ReqType, ResType>) + Send + 'static,
+        on_fail: impl FnOnce(Error) + Send + 'static,
+    ) -> Result<Self> {
+        trace!("Creating pipe server named \"{}\"", pipe_name.as_ref());
+        let pipe_name = pipe_name.as_ref().to_owned();
+        let poll_thread_stop_signal = Arc::new(T::new_signal()?);
+        let stop_signal = poll_thread_stop_signal.clone();
+        let num_timeouts = Arc::new(AtomicUsize::new(0));
+        let poll_thread_num_timeouts = num_timeouts.clone();
+        let (init_sender, init_receiver) = xchan::bounded(1);
+        let poll_thread = thread::spawn(move || {
+            let num_free_connections = Rc::new(RefCell::new(0));
+            let mut conn_list = match ConnectionList::<T, _, _, _>::new(
+                &pipe_name,
+                stop_signal,
+                num_free_connections.clone(),
+                Box::new(on_hello),
+                Box::new(on_new_request),
+                deadline,
+                poll_thread_num_timeouts,
+            ) {
+                Ok(conn_list) => {
+                    init_sender.send(Ok(())).unwrap();
+                    conn_list
+                }
+                Err(e) => {
+                    init_sender.send(Err(e)).unwrap();
+                    return;
+                }
+            };
+
+            let mut run = move || -> Result<()> {
+                loop {
+                    if *num_free_connections.borrow() == 0 {
+                        conn_list.grow(16)?;
+                    }
+                    if conn_list.poll()? {
+                        break;
+                    }
+                }
+                Ok(())
+            };
+            if let Err(e) = run() {
+                on_fail(e);
+            }
+        });
+        if let Err(e) = init_receiver.recv().unwrap() {
+            poll_thread.join().unwrap();
+            return Err(e);
+        }
+        Ok(PipeServer {
+            poll_thread: Some(poll_thread),
+            poll_thread_stop_signal,
+            num_timeouts,
+            hellotype: PhantomData,
+            reqtype: PhantomData,
+            restype: PhantomData,
+        })
+    }
+
+    /// How many requests have been answered with the deadline's fallback.
+    pub fn num_timeouts(&self) -> usize {
+        self.num_timeouts.load(Ordering::Relaxed)
+    }
+}
+
+impl<HelloType: Message, ReqType: Message, ResType: Message> Drop
+    for PipeServer<HelloType, ReqType, ResType>
+{
+    fn drop(&mut self) {
+        self.poll_thread_stop_signal.set().unwrap();
+        self.poll_thread.take().unwrap().join().unwrap();
+    }
+}
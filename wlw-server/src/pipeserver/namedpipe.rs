@@ -0,0 +1,396 @@
+use super::{whole_millis, Direction, IoState, Result, Signal, Transport, Wakeup};
+use crate::windows;
+use std::ffi::{OsStr, OsString};
+use std::sync::Arc;
+use std::time::Duration;
+use std::{mem, ptr};
+
+pub struct Event {
+    handle: windows::HANDLE,
+}
+
+impl Event {
+    fn new(manual_reset: bool, initial_state: bool) -> windows::Result<Self> {
+        let handle = unsafe { windows::CreateEvent(ptr::null_mut(), manual_reset, initial_state) }?;
+        Ok(Event { handle })
+    }
+
+    fn reset(&self) -> windows::Result<()> {
+        unsafe { windows::ResetEvent(self.handle) }
+    }
+}
+
+impl Signal for Event {
+    fn set(&self) -> Result<()> {
+        Ok(unsafe { windows::SetEvent(self.handle) }?)
+    }
+}
+
+impl Drop for Event {
+    fn drop(&mut self) {
+        unsafe { windows::CloseHandle(self.handle) }.unwrap()
+    }
+}
+
+unsafe impl Sync for Event {}
+unsafe impl Send for Event {}
+
+struct Pipe {
+    handle: windows::HANDLE,
+}
+
+impl Pipe {
+    fn new(
+        pipe_name: impl AsRef<OsStr>,
+        output_size: usize,
+        input_size: usize,
+    ) -> windows::Result<Self> {
+        let handle = unsafe {
+            windows::CreateNamedPipe(
+                pipe_name,
+                windows::PIPE_ACCESS_DUPLEX | windows::FILE_FLAG_OVERLAPPED,
+                windows::PIPE_TYPE_MESSAGE | windows::PIPE_READMODE_MESSAGE | windows::PIPE_WAIT,
+                windows::PIPE_UNLIMITED_INSTANCES,
+                output_size as windows::DWORD,
+                input_size as windows::DWORD,
+                1000,
+                ptr::null_mut(),
+            )?
+        };
+        Ok(Pipe { handle })
+    }
+
+    unsafe fn connect(
+        &mut self,
+        overlap: &mut windows::OVERLAPPED,
+    ) -> windows::Result<windows::IoState> {
+        windows::ConnectNamedPipe(self.handle, overlap as *mut _)
+    }
+
+    fn disconnect(&mut self) -> windows::Result<()> {
+        unsafe { windows::DisconnectNamedPipe(self.handle) }
+    }
+
+    fn get_overlapped_result(
+        &mut self,
+        overlap: &mut windows::OVERLAPPED,
+    ) -> windows::Result<usize> {
+        unsafe {
+            windows::GetOverlappedResult(self.handle, overlap as *mut _, false).map(|s| s as usize)
+        }
+    }
+
+    unsafe fn write(
+        &mut self,
+        data: *const u8,
+        size: usize,
+        overlap: &mut windows::OVERLAPPED,
+    ) -> windows::Result<windows::IoState> {
+        windows::WriteFile(
+            self.handle,
+            data as windows::LPCVOID,
+            size as windows::DWORD,
+            ptr::null_mut(),
+            overlap as *mut _,
+        )
+    }
+
+    unsafe fn read(
+        &mut self,
+        data: *mut u8,
+        size: usize,
+        overlap: &mut windows::OVERLAPPED,
+    ) -> windows::Result<windows::IoState> {
+        windows::ReadFile(
+            self.handle,
+            data as windows::LPVOID,
+            size as windows::DWORD,
+            ptr::null_mut(),
+            overlap as *mut _,
+        )
+    }
+}
+
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        self.disconnect().unwrap();
+        unsafe { windows::CloseHandle(self.handle) }.unwrap();
+    }
+}
+
+/// Both directions signal the one manual-reset event, so that a pipe takes
+/// up a single handle in the wait. `wait` resets it before the operations are
+/// checked rather than when one finishes, lest that hide the other finishing.
+/// Operations that finish immediately still signal it, which at worst wakes
+/// the poll thread to find nothing done.
+struct Instance {
+    incoming: Box<windows::OVERLAPPED>,
+    outgoing: Box<windows::OVERLAPPED>,
+    pipe: Pipe,
+    event: Event,
+}
+
+impl Instance {
+    fn on_io(&mut self, direction: Direction, state: windows::IoState) -> Result<IoState> {
+        match state {
+            windows::IoState::Finished => self.finish(direction),
+            windows::IoState::Pending => Ok(IoState::Pending),
+        }
+    }
+
+    fn finish(&mut self, direction: Direction) -> Result<IoState> {
+        let overlap = match direction {
+            Direction::In => &mut *self.incoming,
+            Direction::Out => &mut *self.outgoing,
+        };
+        match self.pipe.get_overlapped_result(overlap) {
+            Ok(num_transferred) => Ok(IoState::Finished(num_transferred)),
+            Err(ref e) if e.code == windows::ERROR_IO_INCOMPLETE => Ok(IoState::Pending),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Overlapped Win32 named pipes, all waited on at once with
+/// `WaitForMultipleObjects`.
+pub struct NamedPipeTransport {
+    pipe_name: OsString,
+    input_size: usize,
+    output_size: usize,
+    // Free instances before the events they reference
+    instances: Vec<Instance>,
+    handles: Vec<windows::HANDLE>,
+    _stop: Arc<Event>,
+    _response_ready: Arc<Event>,
+}
+
+impl Transport for NamedPipeTransport {
+    type Signal = Event;
+
+    fn new_signal() -> Result<Event> {
+        Ok(Event::new(false, false)?)
+    }
+
+    fn new(
+        name: &str,
+        input_size: usize,
+        output_size: usize,
+        stop: Arc<Event>,
+        response_ready: Arc<Event>,
+    ) -> Result<Self> {
+        Ok(NamedPipeTransport {
+            pipe_name: OsString::from(format!("\\\\.\\pipe\\{}", name)),
+            input_size,
+            output_size,
+            instances: Vec::new(),
+            handles: vec![stop.handle, response_ready.handle],
+            _stop: stop,
+            _response_ready: response_ready,
+        })
+    }
+
+    fn add_pipe(&mut self) -> Result<usize> {
+        let event = Event::new(true, false)?;
+        let mut incoming: Box<windows::OVERLAPPED> = Box::new(unsafe { mem::zeroed() });
+        incoming.hEvent = event.handle;
+        let mut outgoing: Box<windows::OVERLAPPED> = Box::new(unsafe { mem::zeroed() });
+        outgoing.hEvent = event.handle;
+        let pipe = Pipe::new(&self.pipe_name, self.output_size, self.input_size)?;
+        self.handles.push(event.handle);
+        self.instances.push(Instance {
+            incoming,
+            outgoing,
+            pipe,
+            event,
+        });
+        Ok(self.instances.len() - 1)
+    }
+
+    fn connect(&mut self, index: usize) -> Result<IoState> {
+        let instance = &mut self.instances[index];
+        let state = unsafe { instance.pipe.connect(&mut *instance.incoming) }?;
+        match state {
+            // A client which connected before ConnectNamedPipe leaves no
+            // transfer to query
+            windows::IoState::Finished => Ok(IoState::Finished(0)),
+            windows::IoState::Pending => Ok(IoState::Pending),
+        }
+    }
+
+    unsafe fn read(&mut self, index: usize, data: *mut u8, size: usize) -> Result<IoState> {
+        let instance = &mut self.instances[index];
+        let state = instance.pipe.read(data, size, &mut *instance.incoming)?;
+        instance.on_io(Direction::In, state)
+    }
+
+    unsafe fn write(&mut self, index: usize, data: *const u8, size: usize) -> Result<IoState> {
+        let instance = &mut self.instances[index];
+        let state = instance.pipe.write(data, size, &mut *instance.outgoing)?;
+        instance.on_io(Direction::Out, state)
+    }
+
+    fn finish(&mut self, index: usize, direction: Direction) -> Result<IoState> {
+        self.instances[index].finish(direction)
+    }
+
+    fn disconnect(&mut self, index: usize) -> Result<()> {
+        Ok(self.instances[index].pipe.disconnect()?)
+    }
+
+    fn wait(&mut self, timeout: Option<Duration>) -> Result<Wakeup> {
+        let wait_result = unsafe {
+            windows::WaitForMultipleObjects(
+                self.handles.len() as windows::DWORD,
+                self.handles.as_ptr(),
+                false,
+                timeout.map_or(windows::INFINITE, whole_millis),
+            )
+        }?;
+        match wait_result {
+            windows::WaitResult::Timeout => Ok(Wakeup::Timeout),
+            windows::WaitResult::Abandoned(_) => panic!("Pipe wait abandoned somehow"),
+            windows::WaitResult::Object(0) => Ok(Wakeup::Stop),
+            windows::WaitResult::Object(1) => Ok(Wakeup::ResponseReady),
+            windows::WaitResult::Object(object) => {
+                let index = object as usize - 2;
+                self.instances[index].event.reset()?;
+                Ok(Wakeup::Pipe(index))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{PipeServer, Request};
+    use crate::codec::{self, Message, FLAG_WANTS_REPLY};
+    use crate::windows;
+    use flexi_logger::Logger;
+    use std::marker::PhantomData;
+    use std::ptr;
+    use std::{thread, time};
+    use winapi::um::fileapi::OPEN_EXISTING;
+    use winapi::um::winnt::GENERIC_READ;
+    use winapi::um::winnt::GENERIC_WRITE;
+
+    struct TestClient<ReqType: Message, ResType: Message> {
+        handle: windows::HANDLE,
+        reqtype: PhantomData<ReqType>,
+        restype: PhantomData<ResType>,
+    }
+
+    impl<ReqType: Message, ResType: Message> TestClient<ReqType, ResType> {
+        fn new(pipe_name: impl AsRef<str>) -> windows::Result<Self> {
+            let handle = unsafe {
+                windows::CreateFile(
+                    format!("\\\\.\\pipe\\{}", pipe_name.as_ref()),
+                    GENERIC_READ | GENERIC_WRITE,
+                    0,
+                    ptr::null_mut(),
+                    OPEN_EXISTING,
+                    0,
+                    ptr::null_mut(),
+                )
+            }?;
+            let mut mode: windows::DWORD = windows::PIPE_READMODE_MESSAGE;
+            let result = unsafe {
+                windows::SetNamedPipeHandleState(
+                    handle,
+                    &mut mode as *mut windows::DWORD,
+                    ptr::null_mut(),
+                    ptr::null_mut(),
+                )
+            };
+            match result {
+                Ok(_) => Ok(TestClient {
+                    handle,
+                    restype: PhantomData,
+                    reqtype: PhantomData,
+                }),
+                Err(e) => {
+                    unsafe { windows::CloseHandle(handle) }.ok();
+                    Err(e)
+                }
+            }
+        }
+
+        unsafe fn write(&mut self, data: *const u8, size: usize) -> windows::Result<()> {
+            let mut nbw: windows::DWORD = 0;
+            windows::WriteFile(
+                self.handle,
+                data as windows::LPCVOID,
+                size as windows::DWORD,
+                &mut nbw as *mut windows::DWORD,
+                ptr::null_mut(),
+            )
+            .map(|_| ())
+        }
+
+        unsafe fn read(&mut self, data: *mut u8, size: usize) -> windows::Result<usize> {
+            let mut nbr: windows::DWORD = 0;
+            windows::ReadFile(
+                self.handle,
+                data as windows::LPVOID,
+                size as windows::DWORD,
+                &mut nbr as *mut windows::DWORD,
+                ptr::null_mut(),
+            )
+            .map(|_| nbr as usize)
+        }
+
+        fn send(&mut self, frame: &[u8]) {
+            unsafe { self.write(frame.as_ptr(), frame.len()).unwrap() };
+        }
+
+        fn request(&mut self, req: ReqType) -> ResType {
+            self.send(&codec::encode(&req, FLAG_WANTS_REPLY).unwrap());
+            let mut frame = vec![0u8; codec::MAX_FRAME_SIZE];
+            let size = unsafe { self.read(frame.as_mut_ptr(), frame.len()).unwrap() };
+            codec::decode(&frame[..size]).unwrap()
+        }
+    }
+
+    impl<ReqType: Message, ResType: Message> Drop for TestClient<ReqType, ResType> {
+        fn drop(&mut self) {
+            unsafe { windows::CloseHandle(self.handle) }.unwrap();
+        }
+    }
+
+    #[test]
+    fn create_and_stop() {
+        let _ps = PipeServer::new(
+            "wlw_test_create_and_stop",
+            |_: u32| true,
+            |_: Request<u32, u32>| {},
+            |_| {},
+        )
+        .unwrap();
+        thread::sleep(time::Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn trivial_reqres() {
+        Logger::with_str("trace").start().unwrap();
+        let _ps = PipeServer::new(
+            "wlw_test_trivial_reqres",
+            |hello: u32| hello == 1,
+            |request: Request<[u8; 4], [u8; 4]>| {
+                trace!("GOT REQUEST: {:?}", request.message);
+                let response = [0, 1, 2, 3];
+                request.respond(response);
+            },
+            |_| {
+                error!("Server broke :(");
+            },
+        )
+        .unwrap();
+        thread::sleep(time::Duration::from_millis(1000));
+
+        // Test sending/receiving message
+        let mut client: TestClient<[u8; 4], [u8; 4]> =
+            TestClient::new("wlw_test_trivial_reqres").unwrap();
+        client.send(&codec::encode(&1u32, 0).unwrap());
+        trace!("GOT RESPONSE: {:?}", client.request([3, 2, 1, 0]));
+        thread::sleep(time::Duration::from_millis(1000));
+    }
+}
@@ -0,0 +1,778 @@
+use super::{whole_millis, Direction, IoState, Result, Signal, Transport, Wakeup};
+use std::env;
+use std::fs;
+use std::io;
+use std::mem;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::RawFd;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const SEND_FLAGS: libc::c_int = libc::MSG_NOSIGNAL;
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+const SEND_FLAGS: libc::c_int = 0;
+
+/// Where the server named `name` listens.
+pub fn socket_path(name: impl AsRef<str>) -> PathBuf {
+    env::temp_dir().join(format!("{}.sock", name.as_ref()))
+}
+
+fn cvt(result: libc::c_int) -> io::Result<libc::c_int> {
+    if result == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(result)
+    }
+}
+
+fn cvt_size(result: libc::ssize_t) -> io::Result<usize> {
+    if result == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(result as usize)
+    }
+}
+
+fn would_block(error: &io::Error) -> bool {
+    error.kind() == io::ErrorKind::WouldBlock
+}
+
+fn socket_addr(path: &Path) -> io::Result<libc::sockaddr_un> {
+    let mut addr: libc::sockaddr_un = unsafe { mem::zeroed() };
+    addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+    let bytes = path.as_os_str().as_bytes();
+    // Leave room for the terminator
+    if bytes.len() >= addr.sun_path.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Socket path is too long",
+        ));
+    }
+    for (dst, src) in addr.sun_path.iter_mut().zip(bytes) {
+        *dst = *src as libc::c_char;
+    }
+    Ok(addr)
+}
+
+struct Fd(RawFd);
+
+impl Fd {
+    fn socket() -> io::Result<Self> {
+        Ok(Fd(cvt(unsafe {
+            libc::socket(libc::AF_UNIX, libc::SOCK_SEQPACKET, 0)
+        })?))
+    }
+
+    fn set_nonblocking(&self) -> io::Result<()> {
+        let flags = cvt(unsafe { libc::fcntl(self.0, libc::F_GETFL) })?;
+        cvt(unsafe { libc::fcntl(self.0, libc::F_SETFL, flags | libc::O_NONBLOCK) })?;
+        Ok(())
+    }
+}
+
+impl Drop for Fd {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.0) };
+    }
+}
+
+/// A self-pipe which stays readable until drained.
+pub struct PipeSignal {
+    read: Fd,
+    write: Fd,
+}
+
+impl PipeSignal {
+    fn new() -> io::Result<Self> {
+        let mut fds = [0; 2];
+        cvt(unsafe { libc::pipe(fds.as_mut_ptr()) })?;
+        let signal = PipeSignal {
+            read: Fd(fds[0]),
+            write: Fd(fds[1]),
+        };
+        signal.read.set_nonblocking()?;
+        signal.write.set_nonblocking()?;
+        Ok(signal)
+    }
+
+    fn drain(&self) {
+        let mut buffer = [0u8; 64];
+        while unsafe {
+            libc::read(
+                self.read.0,
+                buffer.as_mut_ptr() as *mut libc::c_void,
+                buffer.len(),
+            )
+        } > 0
+        {}
+    }
+}
+
+impl Signal for PipeSignal {
+    fn set(&self) -> Result<()> {
+        let byte = 1u8;
+        match cvt_size(unsafe { libc::write(self.write.0, &byte as *const u8 as *const _, 1) }) {
+            Ok(_) => Ok(()),
+            // Full means it is already set
+            Err(ref e) if would_block(e) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+enum Incoming {
+    Nothing,
+    Accept,
+    Read(*mut u8, usize),
+}
+
+struct Slot {
+    socket: Option<Fd>,
+    incoming: Incoming,
+    /// The write in progress, if any.
+    outgoing: Option<(*const u8, usize)>,
+}
+
+/// `SOCK_SEQPACKET` Unix domain sockets, which keep message boundaries like
+/// message-mode named pipes do. Every free slot accepts on the one listening
+/// socket.
+pub struct UnixSocketTransport {
+    path: PathBuf,
+    listener: Fd,
+    stop: Arc<PipeSignal>,
+    response_ready: Arc<PipeSignal>,
+    slots: Vec<Slot>,
+}
+
+impl UnixSocketTransport {
+    fn accept(&mut self, index: usize) -> Result<IoState> {
+        let slot = &mut self.slots[index];
+        match cvt(unsafe { libc::accept(self.listener.0, ptr::null_mut(), ptr::null_mut()) }) {
+            Ok(fd) => {
+                let socket = Fd(fd);
+                socket.set_nonblocking()?;
+                slot.socket = Some(socket);
+                slot.incoming = Incoming::Nothing;
+                Ok(IoState::Finished(0))
+            }
+            Err(ref e) if would_block(e) => {
+                slot.incoming = Incoming::Accept;
+                Ok(IoState::Pending)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    unsafe fn recv(&mut self, index: usize, data: *mut u8, size: usize) -> Result<IoState> {
+        let slot = &mut self.slots[index];
+        let fd = slot
+            .socket
+            .as_ref()
+            .expect("Read from an unconnected slot")
+            .0;
+        let mut iov = libc::iovec {
+            iov_base: data as *mut libc::c_void,
+            iov_len: size,
+        };
+        let mut msg: libc::msghdr = mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        match cvt_size(libc::recvmsg(fd, &mut msg, 0)) {
+            Ok(0) => {
+                Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Client disconnected").into())
+            }
+            Ok(_) if msg.msg_flags & libc::MSG_TRUNC != 0 => {
+                Err(io::Error::new(io::ErrorKind::InvalidData, "Message too long").into())
+            }
+            Ok(num_transferred) => {
+                slot.incoming = Incoming::Nothing;
+                Ok(IoState::Finished(num_transferred))
+            }
+            Err(ref e) if would_block(e) => {
+                slot.incoming = Incoming::Read(data, size);
+                Ok(IoState::Pending)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    unsafe fn send(&mut self, index: usize, data: *const u8, size: usize) -> Result<IoState> {
+        let slot = &mut self.slots[index];
+        let fd = slot
+            .socket
+            .as_ref()
+            .expect("Wrote to an unconnected slot")
+            .0;
+        match cvt_size(libc::send(
+            fd,
+            data as *const libc::c_void,
+            size,
+            SEND_FLAGS,
+        )) {
+            Ok(num_transferred) => {
+                slot.outgoing = None;
+                Ok(IoState::Finished(num_transferred))
+            }
+            Err(ref e) if would_block(e) => {
+                slot.outgoing = Some((data, size));
+                Ok(IoState::Pending)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl Transport for UnixSocketTransport {
+    type Signal = PipeSignal;
+
+    fn new_signal() -> Result<PipeSignal> {
+        Ok(PipeSignal::new()?)
+    }
+
+    fn new(
+        name: &str,
+        _input_size: usize,
+        _output_size: usize,
+        stop: Arc<PipeSignal>,
+        response_ready: Arc<PipeSignal>,
+    ) -> Result<Self> {
+        let path = socket_path(name);
+        let addr = socket_addr(&path)?;
+        // Clear out a socket left behind by a server which did not exit
+        // cleanly
+        if let Err(e) = fs::remove_file(&path) {
+            if e.kind() != io::ErrorKind::NotFound {
+                return Err(e.into());
+            }
+        }
+        let listener = Fd::socket()?;
+        cvt(unsafe {
+            libc::bind(
+                listener.0,
+                &addr as *const _ as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_un>() as libc::socklen_t,
+            )
+        })?;
+        cvt(unsafe { libc::listen(listener.0, libc::SOMAXCONN) })?;
+        listener.set_nonblocking()?;
+        Ok(UnixSocketTransport {
+            path,
+            listener,
+            stop,
+            response_ready,
+            slots: Vec::new(),
+        })
+    }
+
+    fn add_pipe(&mut self) -> Result<usize> {
+        self.slots.push(Slot {
+            socket: None,
+            incoming: Incoming::Nothing,
+            outgoing: None,
+        });
+        Ok(self.slots.len() - 1)
+    }
+
+    fn connect(&mut self, index: usize) -> Result<IoState> {
+        self.accept(index)
+    }
+
+    unsafe fn read(&mut self, index: usize, data: *mut u8, size: usize) -> Result<IoState> {
+        self.recv(index, data, size)
+    }
+
+    unsafe fn write(&mut self, index: usize, data: *const u8, size: usize) -> Result<IoState> {
+        self.send(index, data, size)
+    }
+
+    fn finish(&mut self, index: usize, direction: Direction) -> Result<IoState> {
+        let slot = &self.slots[index];
+        match direction {
+            Direction::In => match slot.incoming {
+                Incoming::Nothing => panic!("Finished a slot with nothing pending in"),
+                Incoming::Accept => self.accept(index),
+                Incoming::Read(data, size) => unsafe { self.recv(index, data, size) },
+            },
+            Direction::Out => match slot.outgoing {
+                None => panic!("Finished a slot with nothing pending out"),
+                Some((data, size)) => unsafe { self.send(index, data, size) },
+            },
+        }
+    }
+
+    fn disconnect(&mut self, index: usize) -> Result<()> {
+        let slot = &mut self.slots[index];
+        slot.socket = None;
+        slot.incoming = Incoming::Nothing;
+        slot.outgoing = None;
+        Ok(())
+    }
+
+    fn wait(&mut self, timeout: Option<Duration>) -> Result<Wakeup> {
+        let mut fds = Vec::with_capacity(self.slots.len() + 2);
+        let pollfd = |fd, events| libc::pollfd {
+            fd,
+            events,
+            revents: 0,
+        };
+        fds.push(pollfd(self.stop.read.0, libc::POLLIN));
+        fds.push(pollfd(self.response_ready.read.0, libc::POLLIN));
+        for slot in &self.slots {
+            let socket = slot.socket.as_ref().map_or(-1, |socket| socket.0);
+            let mut events = 0;
+            if let Incoming::Read(..) = slot.incoming {
+                events |= libc::POLLIN;
+            }
+            if slot.outgoing.is_some() {
+                events |= libc::POLLOUT;
+            }
+            fds.push(match slot.incoming {
+                Incoming::Accept => pollfd(self.listener.0, libc::POLLIN),
+                // Negative descriptors are ignored
+                _ if events == 0 => pollfd(-1, 0),
+                _ => pollfd(socket, events),
+            });
+        }
+        let timeout = timeout.map_or(-1, |timeout| whole_millis(timeout) as libc::c_int);
+        let num_ready = loop {
+            match cvt(unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout) }) {
+                Ok(num_ready) => break num_ready,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        };
+        if num_ready == 0 {
+            Ok(Wakeup::Timeout)
+        } else if fds[0].revents != 0 {
+            self.stop.drain();
+            Ok(Wakeup::Stop)
+        } else if fds[1].revents != 0 {
+            self.response_ready.drain();
+            Ok(Wakeup::ResponseReady)
+        } else {
+            let index = fds[2..]
+                .iter()
+                .position(|fd| fd.revents != 0)
+                .expect("Poll returned with nothing ready");
+            Ok(Wakeup::Pipe(index))
+        }
+    }
+}
+
+impl Drop for UnixSocketTransport {
+    fn drop(&mut self) {
+        fs::remove_file(&self.path).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Deadline, PipeServer, Request};
+    use super::*;
+    use crate::clock::{Clock, ManualClock, SystemClock};
+    use crate::codec::{self, Message, FLAG_WANTS_REPLY};
+    use crossbeam_channel as xchan;
+    use std::marker::PhantomData;
+    use std::time::Duration;
+
+    struct TestClient<ReqType: Message, ResType: Message> {
+        socket: Fd,
+        reqtype: PhantomData<ReqType>,
+        restype: PhantomData<ResType>,
+    }
+
+    const HELLO: u32 = 0x6865_6c6f;
+
+    fn accept_hello(hello: u32) -> bool {
+        hello == HELLO
+    }
+
+    impl<ReqType: Message, ResType: Message> TestClient<ReqType, ResType> {
+        /// Connects and introduces itself.
+        fn new(name: &str) -> io::Result<Self> {
+            let mut client = TestClient::connect(name)?;
+            client.send(&codec::encode(&HELLO, 0).unwrap());
+            Ok(client)
+        }
+
+        fn connect(name: &str) -> io::Result<Self> {
+            let addr = socket_addr(&socket_path(name))?;
+            let socket = Fd::socket()?;
+            cvt(unsafe {
+                libc::connect(
+                    socket.0,
+                    &addr as *const _ as *const libc::sockaddr,
+                    mem::size_of::<libc::sockaddr_un>() as libc::socklen_t,
+                )
+            })?;
+            Ok(TestClient {
+                socket,
+                reqtype: PhantomData,
+                restype: PhantomData,
+            })
+        }
+
+        fn send(&mut self, frame: &[u8]) {
+            let sent = cvt_size(unsafe {
+                libc::send(
+                    self.socket.0,
+                    frame.as_ptr() as *const libc::c_void,
+                    frame.len(),
+                    SEND_FLAGS,
+                )
+            })
+            .unwrap();
+            assert_eq!(sent, frame.len());
+        }
+
+        fn receive(&mut self) -> Vec<u8> {
+            let mut frame = vec![0u8; codec::MAX_FRAME_SIZE];
+            let received = cvt_size(unsafe {
+                libc::recv(
+                    self.socket.0,
+                    frame.as_mut_ptr() as *mut libc::c_void,
+                    frame.len(),
+                    0,
+                )
+            })
+            .unwrap();
+            frame.truncate(received);
+            frame
+        }
+
+        fn notify(&mut self, req: ReqType) {
+            self.send(&codec::encode(&req, 0).unwrap());
+        }
+
+        /// Whether the server hung up.
+        fn is_disconnected(&mut self) -> bool {
+            self.receive().is_empty()
+        }
+
+        /// Gives `None` for an empty answer.
+        fn request(&mut self, req: ReqType) -> Option<ResType> {
+            self.send(&codec::encode(&req, FLAG_WANTS_REPLY).unwrap());
+            let frame = self.receive();
+            if codec::decode_header(&frame).unwrap().kind == codec::EMPTY_KIND {
+                None
+            } else {
+                Some(codec::decode(&frame).unwrap())
+            }
+        }
+    }
+
+    fn reversing_server(name: &str) -> PipeServer<u32, [u8; 4], [u8; 4]> {
+        PipeServer::new(
+            name,
+            accept_hello,
+            |request: Request<[u8; 4], [u8; 4]>| {
+                let mut response = request.message;
+                response.reverse();
+                request.respond(response);
+            },
+            |e| panic!("Server broke: {}", e),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn create_and_stop() {
+        let name = "wlw_test_unix_create_and_stop";
+        let ps = reversing_server(name);
+        assert!(socket_path(name).exists());
+        drop(ps);
+        assert!(!socket_path(name).exists());
+    }
+
+    #[test]
+    fn bad_path_fails_init() {
+        assert!(PipeServer::<u32, u32, u32>::new(
+            "no/such/directory",
+            accept_hello,
+            |_| {},
+            |_| {}
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn trivial_reqres() {
+        let name = "wlw_test_unix_trivial_reqres";
+        let _ps = reversing_server(name);
+        let mut client: TestClient<[u8; 4], [u8; 4]> = TestClient::new(name).unwrap();
+        assert_eq!(client.request([3, 2, 1, 0]), Some([0, 1, 2, 3]));
+        assert_eq!(client.request([4, 5, 6, 7]), Some([7, 6, 5, 4]));
+    }
+
+    #[test]
+    fn acknowledge_resumes_reading() {
+        let name = "wlw_test_unix_acknowledge";
+        let _ps = PipeServer::new(
+            name,
+            accept_hello,
+            |request: Request<u32, u32>| {
+                // Small requests get nothing to say
+                if request.message < 100 {
+                    request.acknowledge();
+                } else {
+                    let message = request.message;
+                    request.respond(message * 10);
+                }
+            },
+            |e| panic!("Server broke: {}", e),
+        )
+        .unwrap();
+        let mut client: TestClient<u32, u32> = TestClient::new(name).unwrap();
+        client.notify(2);
+        client.notify(4);
+        assert_eq!(client.request(100), Some(1000));
+        assert_eq!(client.request(6), None);
+        assert_eq!(client.request(200), Some(2000));
+    }
+
+    #[test]
+    fn dropped_requests_are_acknowledged() {
+        let name = "wlw_test_unix_dropped";
+        let _ps = PipeServer::new(
+            name,
+            accept_hello,
+            |request: Request<u32, u32>| {
+                // Small requests are forgotten about
+                if request.message >= 100 {
+                    let message = request.message;
+                    request.respond(message * 10);
+                }
+            },
+            |e| panic!("Server broke: {}", e),
+        )
+        .unwrap();
+        let mut client: TestClient<u32, u32> = TestClient::new(name).unwrap();
+        assert_eq!(client.request(1), None);
+        client.notify(2);
+        assert_eq!(client.request(100), Some(1000));
+        assert_eq!(client.request(3), None);
+    }
+
+    #[test]
+    fn grows_past_initial_connections() {
+        let name = "wlw_test_unix_grows";
+        let _ps = reversing_server(name);
+        let mut clients: Vec<TestClient<[u8; 4], [u8; 4]>> =
+            (0..40).map(|_| TestClient::new(name).unwrap()).collect();
+        for (i, client) in clients.iter_mut().enumerate() {
+            let i = i as u8;
+            assert_eq!(client.request([i, 0, 0, 1]), Some([1, 0, 0, i]));
+        }
+    }
+
+    #[test]
+    fn late_response_to_departed_client_is_dropped() {
+        let name = "wlw_test_unix_departed";
+        let (request_sender, request_receiver) = xchan::unbounded();
+        let _ps = PipeServer::new(
+            name,
+            accept_hello,
+            move |request: Request<u32, u32>| request_sender.send(request).unwrap(),
+            |e| panic!("Server broke: {}", e),
+        )
+        .unwrap();
+
+        let mut departed: TestClient<u32, u32> = TestClient::new(name).unwrap();
+        departed.send(&codec::encode(&1u32, FLAG_WANTS_REPLY).unwrap());
+        let stale = request_receiver
+            .recv_timeout(Duration::from_secs(5))
+            .unwrap();
+        drop(departed);
+        let mut client: TestClient<u32, u32> = TestClient::new(name).unwrap();
+        client.send(&codec::encode(&2u32, FLAG_WANTS_REPLY).unwrap());
+        let fresh = request_receiver
+            .recv_timeout(Duration::from_secs(5))
+            .unwrap();
+        // Writing to the departed client fails and recycles its connection
+        stale.respond(10);
+        fresh.respond(20);
+        assert_eq!(codec::decode(&client.receive()), Ok(20u32));
+    }
+
+    #[test]
+    fn rejected_hello_disconnects() {
+        let name = "wlw_test_unix_rejected_hello";
+        let _ps = reversing_server(name);
+        let mut stranger: TestClient<[u8; 4], [u8; 4]> = TestClient::connect(name).unwrap();
+        stranger.send(&codec::encode(&(HELLO + 1), 0).unwrap());
+        assert!(stranger.is_disconnected());
+        let mut client: TestClient<[u8; 4], [u8; 4]> = TestClient::new(name).unwrap();
+        assert_eq!(client.request([1, 2, 3, 4]), Some([4, 3, 2, 1]));
+    }
+
+    #[test]
+    fn clients_must_say_hello() {
+        let name = "wlw_test_unix_no_hello";
+        let _ps = reversing_server(name);
+        let mut rude: TestClient<[u8; 4], [u8; 4]> = TestClient::connect(name).unwrap();
+        rude.notify([1, 2, 3, 4]);
+        assert!(rude.is_disconnected());
+        let mut garbled: TestClient<[u8; 4], [u8; 4]> = TestClient::connect(name).unwrap();
+        garbled.send(&[7, 0]);
+        assert!(garbled.is_disconnected());
+    }
+
+    #[test]
+    fn malformed_frames_are_dropped() {
+        let name = "wlw_test_unix_malformed";
+        let _ps = reversing_server(name);
+        let mut client: TestClient<[u8; 4], [u8; 4]> = TestClient::new(name).unwrap();
+        client.send(&[7, 0]);
+        let mut frame = codec::encode(&[1u8, 2, 3, 4], 0).unwrap();
+        frame[0] += 1;
+        client.send(&frame);
+        // Cut short, but still intact enough to be answered
+        let mut frame = codec::encode(&[1u8, 2, 3, 4], FLAG_WANTS_REPLY).unwrap();
+        frame.truncate(frame.len() - 1);
+        frame[0] -= 1;
+        client.send(&frame);
+        assert_eq!(client.receive(), codec::empty_frame(0));
+        assert_eq!(client.request([5, 6, 7, 8]), Some([8, 7, 6, 5]));
+    }
+
+    #[test]
+    fn unknown_kinds_are_skipped() {
+        let name = "wlw_test_unix_unknown_kinds";
+        let _ps = reversing_server(name);
+        let mut client: TestClient<[u8; 4], [u8; 4]> = TestClient::new(name).unwrap();
+        // `u32`s are news to a server of `[u8; 4]`s
+        client.send(&codec::encode(&5u32, 0).unwrap());
+        client.send(&codec::encode(&6u32, FLAG_WANTS_REPLY).unwrap());
+        assert_eq!(client.receive(), codec::empty_frame(0));
+        assert_eq!(client.request([1, 2, 3, 4]), Some([4, 3, 2, 1]));
+    }
+
+    #[test]
+    fn pipelined_requests_are_answered_in_any_order() {
+        let name = "wlw_test_unix_pipelined";
+        let (request_sender, request_receiver) = xchan::unbounded();
+        let _ps = PipeServer::new(
+            name,
+            accept_hello,
+            move |request: Request<u32, u32>| request_sender.send(request).unwrap(),
+            |e| panic!("Server broke: {}", e),
+        )
+        .unwrap();
+        let mut client: TestClient<u32, u32> = TestClient::new(name).unwrap();
+        // Nothing waits on the answers to the ones before
+        client.send(&codec::encode_with_id(&1u32, FLAG_WANTS_REPLY, 11).unwrap());
+        client.notify(2);
+        client.send(&codec::encode_with_id(&3u32, FLAG_WANTS_REPLY, 13).unwrap());
+        client.send(&codec::encode_with_id(&4u32, FLAG_WANTS_REPLY, 14).unwrap());
+        let next = || {
+            request_receiver
+                .recv_timeout(Duration::from_secs(5))
+                .unwrap()
+        };
+        let (first, notification, third, fourth) = (next(), next(), next(), next());
+        fourth.respond(40);
+        notification.acknowledge();
+        first.respond(10);
+        third.acknowledge();
+
+        let frame = client.receive();
+        assert_eq!(codec::decode_header(&frame).unwrap().id, 14);
+        assert_eq!(codec::decode(&frame), Ok(40u32));
+        let frame = client.receive();
+        assert_eq!(codec::decode_header(&frame).unwrap().id, 11);
+        assert_eq!(codec::decode(&frame), Ok(10u32));
+        assert_eq!(client.receive(), codec::empty_frame(13));
+    }
+
+    /// A server whose requests are left to the test to answer, or not.
+    fn slow_server(
+        name: &str,
+        timeout: Duration,
+        clock: Arc<dyn Clock>,
+    ) -> (
+        PipeServer<u32, u32, u32>,
+        xchan::Receiver<Request<u32, u32>>,
+    ) {
+        let (request_sender, request_receiver) = xchan::unbounded();
+        let ps = PipeServer::with_deadline(
+            name,
+            Deadline {
+                timeout,
+                fallback: Box::new(|message: &u32| Some(message + 1)),
+                clock,
+            },
+            accept_hello,
+            move |request: Request<u32, u32>| request_sender.send(request).unwrap(),
+            |e| panic!("Server broke: {}", e),
+        )
+        .unwrap();
+        (ps, request_receiver)
+    }
+
+    #[test]
+    fn overdue_requests_get_the_fallback() {
+        let name = "wlw_test_unix_overdue";
+        let clock = Arc::new(ManualClock::new());
+        let (ps, requests) = slow_server(name, Duration::from_secs(1), clock.clone());
+        let mut client: TestClient<u32, u32> = TestClient::new(name).unwrap();
+        client.send(&codec::encode(&1u32, FLAG_WANTS_REPLY).unwrap());
+        let late = requests.recv_timeout(Duration::from_secs(5)).unwrap();
+        clock.advance(Duration::from_secs(2));
+        // Any wakeup has the server look at its deadlines, and a new client
+        // makes several
+        let _bystander: TestClient<u32, u32> = TestClient::new(name).unwrap();
+        assert_eq!(codec::decode(&client.receive()), Ok(2u32));
+        assert_eq!(ps.num_timeouts(), 1);
+
+        late.respond(10);
+        client.send(&codec::encode(&3u32, FLAG_WANTS_REPLY).unwrap());
+        let prompt = requests.recv_timeout(Duration::from_secs(5)).unwrap();
+        prompt.respond(30);
+        assert_eq!(codec::decode(&client.receive()), Ok(30u32));
+        assert_eq!(ps.num_timeouts(), 1);
+    }
+
+    #[test]
+    fn deadlines_are_per_request() {
+        let name = "wlw_test_unix_deadline_per_request";
+        let clock = Arc::new(ManualClock::new());
+        let (ps, requests) = slow_server(name, Duration::from_secs(1), clock.clone());
+        let mut client: TestClient<u32, u32> = TestClient::new(name).unwrap();
+        client.send(&codec::encode_with_id(&1u32, FLAG_WANTS_REPLY, 1).unwrap());
+        let first = requests.recv_timeout(Duration::from_secs(5)).unwrap();
+        clock.advance(Duration::from_millis(600));
+        client.send(&codec::encode_with_id(&3u32, FLAG_WANTS_REPLY, 2).unwrap());
+        let second = requests.recv_timeout(Duration::from_secs(5)).unwrap();
+        clock.advance(Duration::from_millis(600));
+        // Only the first is overdue
+        let _bystander: TestClient<u32, u32> = TestClient::new(name).unwrap();
+        let frame = client.receive();
+        assert_eq!(codec::decode_header(&frame).unwrap().id, 1);
+        assert_eq!(codec::decode(&frame), Ok(2u32));
+
+        second.respond(30);
+        first.respond(10);
+        let frame = client.receive();
+        assert_eq!(codec::decode_header(&frame).unwrap().id, 2);
+        assert_eq!(codec::decode(&frame), Ok(30u32));
+        assert_eq!(ps.num_timeouts(), 1);
+    }
+
+    #[test]
+    fn deadlines_pass_without_wakeups() {
+        let name = "wlw_test_unix_deadline_passes";
+        let (ps, requests) = slow_server(name, Duration::from_millis(10), Arc::new(SystemClock));
+        let mut client: TestClient<u32, u32> = TestClient::new(name).unwrap();
+        // Notifications have nobody waiting on them, so no deadline
+        client.notify(1);
+        requests
+            .recv_timeout(Duration::from_secs(5))
+            .unwrap()
+            .acknowledge();
+        assert_eq!(client.request(5), Some(6));
+        assert_eq!(ps.num_timeouts(), 1);
+    }
+}
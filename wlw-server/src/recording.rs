@@ -0,0 +1,594 @@
+use crate::backend::simulated::{SimulatedBackend, SimulatedWindow};
+use crate::backend::{WindowBackend, WindowId, WS_OVERLAPPEDWINDOW};
+use crate::context::{self, ContextBuilder};
+use crate::hookevent::{self, HookEvent, HookResponse, WindowEvent, WindowInfo};
+use crate::luauserdata::Rect;
+use std::error;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const HEADER: &str = "# wlw recording v2";
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+    Context(context::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "Error reading recording: {}", e),
+            Error::Parse { line, message } => write!(f, "Recording line {}: {}", line, message),
+            Error::Context(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// One hook event as the server saw it, with who the window belonged to and
+/// its title at the time, and what Lua answered.
+///
+/// Entries are written one per line as tab-separated fields: milliseconds
+/// since recording started, the event, the response (a rect, `allow`, `deny`
+/// or `-` for none), the window's pid and thread id, then its escaped class
+/// name, executable and title. Title changes keep the title being set there
+/// rather than in the event.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub time: Duration,
+    pub event: HookEvent,
+    pub window: WindowInfo,
+    pub title: String,
+    pub response: Option<HookResponse>,
+}
+
+fn write_rect(f: &mut fmt::Formatter, rect: Rect) -> fmt::Result {
+    write!(
+        f,
+        "{} {} {} {}",
+        rect.left, rect.top, rect.right, rect.bottom
+    )
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(escaped: &str) -> Result<String, String> {
+    let mut text = String::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => text.push('\\'),
+            Some('t') => text.push('\t'),
+            Some('n') => text.push('\n'),
+            Some('r') => text.push('\r'),
+            _ => return Err(format!("Bad escape in \"{}\"", escaped)),
+        }
+    }
+    Ok(text)
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let millis = self.time.as_secs() * 1000 + u64::from(self.time.subsec_millis());
+        write!(f, "{}\t", millis)?;
+        match &self.event {
+            &HookEvent::CwpShowWindow { hwnd, shown } => {
+                write!(f, "show {} {}", hwnd, shown as u8)?
+            }
+            &HookEvent::CbtActivate {
+                hwnd,
+                caused_by_mouse,
+            } => write!(f, "activate {} {}", hwnd, caused_by_mouse as u8)?,
+            &HookEvent::CbtCreateWindow { hwnd, rect } => {
+                write!(f, "create {} ", hwnd)?;
+                write_rect(f, rect)?;
+            }
+            &HookEvent::CbtDestroyWindow { hwnd } => write!(f, "destroy {}", hwnd)?,
+            &HookEvent::CbtMinMax { hwnd, show_command } => {
+                write!(f, "minmax {} {}", hwnd, show_command)?
+            }
+            &HookEvent::CbtMoveSize { hwnd, rect } => {
+                write!(f, "movesize {} ", hwnd)?;
+                write_rect(f, rect)?;
+            }
+            HookEvent::CwpSetText { hwnd, .. } => write!(f, "settext {}", hwnd)?,
+            &HookEvent::CbtSetFocus { hwnd } => write!(f, "focus {}", hwnd)?,
+            &HookEvent::CbtSysCommand { hwnd, command } => {
+                write!(f, "syscommand {} {}", hwnd, command)?
+            }
+            &HookEvent::CwpWindowPosChanged { hwnd, rect } => {
+                write!(f, "moved {} ", hwnd)?;
+                write_rect(f, rect)?;
+            }
+        }
+        f.write_str("\t")?;
+        match self.response {
+            Some(HookResponse::PosAndSize { rect }) => write_rect(f, rect)?,
+            Some(HookResponse::Verdict { allow: true }) => f.write_str("allow")?,
+            Some(HookResponse::Verdict { allow: false }) => f.write_str("deny")?,
+            None => f.write_str("-")?,
+        }
+        write!(
+            f,
+            "\t{} {}\t{}\t{}\t{}",
+            self.window.pid,
+            self.window.thread_id,
+            escape(&self.window.class_name),
+            escape(&self.window.exe),
+            escape(&self.title)
+        )
+    }
+}
+
+struct Fields<'a>(std::str::SplitWhitespace<'a>);
+
+impl<'a> Fields<'a> {
+    fn next<T: FromStr>(&mut self, what: &str) -> Result<T, String> {
+        let field = self.0.next().ok_or_else(|| format!("Missing {}", what))?;
+        field
+            .parse()
+            .map_err(|_| format!("Bad {} \"{}\"", what, field))
+    }
+
+    fn next_bool(&mut self, what: &str) -> Result<bool, String> {
+        Ok(self.next::<u8>(what)? != 0)
+    }
+
+    fn next_rect(&mut self) -> Result<Rect, String> {
+        Ok(Rect::new(
+            self.next("left")?,
+            self.next("top")?,
+            self.next("right")?,
+            self.next("bottom")?,
+        ))
+    }
+
+    fn end(mut self) -> Result<(), String> {
+        match self.0.next() {
+            Some(field) => Err(format!("Unexpected \"{}\"", field)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Parses an entry's event. Title changes take `title`, the entry's title.
+fn parse_event(text: &str, title: &str) -> Result<HookEvent, String> {
+    let mut fields = Fields(text.split_whitespace());
+    let kind: String = fields.next("event")?;
+    let hwnd: WindowId = fields.next("window")?;
+    let event = match kind.as_str() {
+        "show" => HookEvent::CwpShowWindow {
+            hwnd,
+            shown: fields.next_bool("shown")?,
+        },
+        "activate" => HookEvent::CbtActivate {
+            hwnd,
+            caused_by_mouse: fields.next_bool("caused_by_mouse")?,
+        },
+        "create" => HookEvent::CbtCreateWindow {
+            hwnd,
+            rect: fields.next_rect()?,
+        },
+        "destroy" => HookEvent::CbtDestroyWindow { hwnd },
+        "minmax" => HookEvent::CbtMinMax {
+            hwnd,
+            show_command: fields.next("show_command")?,
+        },
+        "movesize" => HookEvent::CbtMoveSize {
+            hwnd,
+            rect: fields.next_rect()?,
+        },
+        "settext" => HookEvent::CwpSetText {
+            hwnd,
+            title: title.to_owned(),
+        },
+        "focus" => HookEvent::CbtSetFocus { hwnd },
+        "syscommand" => HookEvent::CbtSysCommand {
+            hwnd,
+            command: fields.next("command")?,
+        },
+        "moved" => HookEvent::CwpWindowPosChanged {
+            hwnd,
+            rect: fields.next_rect()?,
+        },
+        _ => return Err(format!("Unknown event \"{}\"", kind)),
+    };
+    fields.end()?;
+    Ok(event)
+}
+
+impl FromStr for Entry {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, String> {
+        let columns: Vec<&str> = line.split('\t').collect();
+        if columns.len() != 7 {
+            return Err("Expected seven tab-separated fields".to_owned());
+        }
+        let millis: u64 = columns[0]
+            .parse()
+            .map_err(|_| format!("Bad time \"{}\"", columns[0]))?;
+        let response = match columns[2] {
+            "-" => None,
+            "allow" => Some(HookResponse::Verdict { allow: true }),
+            "deny" => Some(HookResponse::deny()),
+            rect => {
+                let mut fields = Fields(rect.split_whitespace());
+                let rect = fields.next_rect()?;
+                fields.end()?;
+                Some(HookResponse::from_rect(rect))
+            }
+        };
+        let mut ids = Fields(columns[3].split_whitespace());
+        let window = WindowInfo {
+            pid: ids.next("pid")?,
+            thread_id: ids.next("thread_id")?,
+            class_name: unescape(columns[4])?,
+            exe: unescape(columns[5])?,
+        };
+        ids.end()?;
+        let title = unescape(columns[6])?;
+        Ok(Entry {
+            time: Duration::from_millis(millis),
+            event: parse_event(columns[1], &title)?,
+            window,
+            title,
+            response,
+        })
+    }
+}
+
+/// Appends every event the server handles to a recording.
+pub struct Recorder {
+    start: Instant,
+    writer: Box<dyn Write>,
+}
+
+impl Recorder {
+    pub fn new(mut writer: impl Write + 'static) -> io::Result<Self> {
+        writeln!(writer, "{}", HEADER)?;
+        writer.flush()?;
+        Ok(Recorder {
+            start: Instant::now(),
+            writer: Box::new(writer),
+        })
+    }
+
+    pub fn record(
+        &mut self,
+        event: HookEvent,
+        window: WindowInfo,
+        title: String,
+        response: Option<HookResponse>,
+    ) -> io::Result<()> {
+        let entry = Entry {
+            time: self.start.elapsed(),
+            event,
+            window,
+            title,
+            response,
+        };
+        writeln!(self.writer, "{}", entry)?;
+        // Keep what has been recorded so far if the server goes down
+        self.writer.flush()
+    }
+}
+
+/// Reads a recording written by `Recorder`. Blank lines and lines starting
+/// with `#` are skipped.
+pub fn read(reader: impl BufRead) -> Result<Vec<Entry>, Error> {
+    let mut entries = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.map_err(Error::Io)?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        entries.push(line.parse().map_err(|message| Error::Parse {
+            line: index + 1,
+            message,
+        })?);
+    }
+    Ok(entries)
+}
+
+/// A recorded event which the script answered differently on replay.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mismatch {
+    pub entry: Entry,
+    pub replayed: Option<HookResponse>,
+}
+
+/// Feeds `entries` through `script` in order and returns every event whose
+/// response differs from the recorded one.
+///
+/// The windows live on a simulated desktop which follows the recording:
+/// each window takes its recorded title and owner, and is created, shown, activated,
+/// moved and destroyed as the events say, using the recorded responses
+/// rather than the replayed ones so that one divergence does not cascade.
+/// Events the recorded response denied leave the desktop alone.
+pub fn replay(script: &str, entries: &[Entry]) -> Result<Vec<Mismatch>, Error> {
+    let backend = Arc::new(SimulatedBackend::new());
+    let mut context = ContextBuilder::new(backend.clone())
+        .script_source(script)
+        .build()
+        .map_err(Error::Context)?;
+    let mut ignore = |_| None;
+    let mut mismatches = Vec::new();
+    for entry in entries {
+        let hwnd = entry.event.hwnd();
+        match backend.window(hwnd) {
+            Ok(_) => backend.set_title(hwnd, &entry.title).unwrap(),
+            Err(_) => {
+                let rect = match entry.event {
+                    HookEvent::CbtCreateWindow { rect, .. } => rect,
+                    _ => Rect::new(0, 0, 0, 0),
+                };
+                let mut window = SimulatedWindow::new(entry.title.clone(), rect);
+                window.class = entry.window.class_name.clone();
+                window.pid = entry.window.pid;
+                window.exe = entry.window.exe.clone();
+                if let HookEvent::CbtCreateWindow { .. } = entry.event {
+                    window.style = WS_OVERLAPPEDWINDOW;
+                }
+                backend.insert_window(hwnd, window);
+            }
+        }
+
+        let replayed = context
+            .handle_window_event(&WindowEvent {
+                event: entry.event.clone(),
+                window: entry.window.clone(),
+            })
+            .map_err(Error::Context)?;
+        if replayed != entry.response {
+            mismatches.push(Mismatch {
+                entry: entry.clone(),
+                replayed,
+            });
+        }
+
+        if !hookevent::allows(entry.response) {
+            if let HookEvent::CbtCreateWindow { .. } = entry.event {
+                backend.destroy_window(hwnd).unwrap();
+            }
+            continue;
+        }
+        match entry.event {
+            HookEvent::CwpShowWindow { shown, .. } => {
+                backend.set_window_shown(hwnd, shown, &mut ignore).unwrap()
+            }
+            HookEvent::CbtActivate {
+                caused_by_mouse, ..
+            } => backend
+                .activate_window(hwnd, caused_by_mouse, &mut ignore)
+                .unwrap(),
+            HookEvent::CbtCreateWindow { .. } | HookEvent::CbtMoveSize { .. } => {
+                if let Some(rect) = entry.response.and_then(|response| response.rect()) {
+                    backend.set_window_rect(hwnd, rect).unwrap();
+                }
+            }
+            HookEvent::CbtDestroyWindow { .. } => backend.destroy_window(hwnd).unwrap(),
+            HookEvent::CbtMinMax { show_command, .. } => backend
+                .min_max_window(hwnd, show_command, &mut ignore)
+                .unwrap(),
+            HookEvent::CwpWindowPosChanged { rect, .. } => {
+                backend.set_window_rect(hwnd, rect).unwrap()
+            }
+            // Titles were set above, and the simulated desktop has nothing
+            // for the window menu or per-control focus to act on
+            HookEvent::CwpSetText { .. }
+            | HookEvent::CbtSetFocus { .. }
+            | HookEvent::CbtSysCommand { .. } => {}
+        }
+    }
+    Ok(mismatches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{SC_CLOSE, SW_MAXIMIZE};
+    use std::fs::{self, File};
+    use std::io::BufReader;
+
+    const SCRIPT: &str = r#"
+        function wlw.on_window_create(w, rect)
+            if w.exe == "firefox.exe" then
+                rect.x = 0
+                rect.y = 0
+                rect.width = 400
+                rect.height = 300
+            end
+            return rect
+        end
+    "#;
+
+    fn entry(millis: u64, event: HookEvent, title: &str, response: Option<HookResponse>) -> Entry {
+        Entry {
+            time: Duration::from_millis(millis),
+            event,
+            window: WindowInfo::default(),
+            title: title.to_owned(),
+            response,
+        }
+    }
+
+    #[test]
+    fn entries_round_trip_through_text() {
+        let mut entries = vec![
+            entry(
+                0,
+                HookEvent::CbtCreateWindow {
+                    hwnd: 7,
+                    rect: Rect::new(-10, 20, 300, 400),
+                },
+                "Tab\there \\ and\nthere",
+                Some(HookResponse::from_rect(Rect::new(0, 0, 310, 380))),
+            ),
+            entry(
+                20,
+                HookEvent::CbtActivate {
+                    hwnd: 7,
+                    caused_by_mouse: false,
+                },
+                "",
+                Some(HookResponse::deny()),
+            ),
+            entry(
+                1500,
+                HookEvent::CbtMinMax {
+                    hwnd: 7,
+                    show_command: SW_MAXIMIZE,
+                },
+                "",
+                None,
+            ),
+            entry(
+                1600,
+                HookEvent::CwpSetText {
+                    hwnd: 7,
+                    title: "New\ttitle".to_owned(),
+                },
+                "New\ttitle",
+                None,
+            ),
+            entry(1700, HookEvent::CbtSetFocus { hwnd: 7 }, "", None),
+            entry(
+                1800,
+                HookEvent::CbtSysCommand {
+                    hwnd: 7,
+                    command: SC_CLOSE,
+                },
+                "",
+                Some(HookResponse::deny()),
+            ),
+            entry(
+                1900,
+                HookEvent::CwpWindowPosChanged {
+                    hwnd: 7,
+                    rect: Rect::new(1, 2, 3, 4),
+                },
+                "",
+                None,
+            ),
+        ];
+        entries[0].window = WindowInfo {
+            pid: 1234,
+            thread_id: 5678,
+            class_name: "Mozilla\tWindow\\Class".to_owned(),
+            exe: "fire fox.exe".to_owned(),
+        };
+        let mut text = format!("{}\n\n", HEADER);
+        for entry in &entries {
+            text.push_str(&format!("{}\n", entry));
+        }
+        assert_eq!(read(text.as_bytes()).unwrap(), entries);
+    }
+
+    #[test]
+    fn recorder_writes_a_readable_recording() {
+        let path = std::env::temp_dir().join("wlw_test_recorder.txt");
+        let mut recorder = Recorder::new(File::create(&path).unwrap()).unwrap();
+        let event = HookEvent::CbtMoveSize {
+            hwnd: 3,
+            rect: Rect::new(1, 2, 3, 4),
+        };
+        let response = HookResponse::from_rect(Rect::new(5, 6, 7, 8));
+        let window = WindowInfo {
+            pid: 9,
+            thread_id: 10,
+            class_name: "Notepad".to_owned(),
+            exe: "notepad.exe".to_owned(),
+        };
+        recorder
+            .record(
+                event.clone(),
+                window.clone(),
+                "Notepad".to_owned(),
+                Some(response),
+            )
+            .unwrap();
+        recorder
+            .record(
+                HookEvent::CbtDestroyWindow { hwnd: 3 },
+                window.clone(),
+                "Notepad".to_owned(),
+                None,
+            )
+            .unwrap();
+        drop(recorder);
+
+        let entries = read(BufReader::new(File::open(&path).unwrap())).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].event, event);
+        assert_eq!(entries[0].window, window);
+        assert_eq!(entries[0].response, Some(response));
+        assert_eq!(entries[1].response, None);
+    }
+
+    #[test]
+    fn bad_lines_are_reported_by_number() {
+        let text = format!(
+            "{}\n0\tdestroy 1\t-\t0 0\t\t\t\n0\texplode 1\t-\t0 0\t\t\t\n",
+            HEADER
+        );
+        match read(text.as_bytes()) {
+            Err(Error::Parse { line: 3, .. }) => {}
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn replay_reports_changed_responses() {
+        let create = |hwnd, title: &str| {
+            let mut entry = entry(
+                0,
+                HookEvent::CbtCreateWindow {
+                    hwnd,
+                    rect: Rect::new(100, 100, 500, 400),
+                },
+                title,
+                // Recorded against a script which placed every window at 0, 0
+                Some(HookResponse::from_rect(Rect::new(0, 0, 400, 300))),
+            );
+            entry.window.exe = format!("{}.exe", title.to_lowercase());
+            entry
+        };
+        let entries = vec![
+            create(1, "Firefox"),
+            create(2, "Notepad"),
+            entry(10, HookEvent::CbtDestroyWindow { hwnd: 1 }, "Firefox", None),
+        ];
+        let mismatches = replay(SCRIPT, &entries).unwrap();
+        assert_eq!(
+            mismatches,
+            vec![Mismatch {
+                entry: entries[1].clone(),
+                replayed: Some(HookResponse::from_rect(Rect::new(100, 100, 500, 400))),
+            }]
+        );
+    }
+}
@@ -0,0 +1,600 @@
+use crate::backend::simulated::{SimulatedBackend, SimulatedWindow};
+use crate::backend::{
+    ShowState, WindowBackend, WindowId, SW_MAXIMIZE, SW_MINIMIZE, SW_RESTORE, WS_OVERLAPPEDWINDOW,
+};
+use crate::context::{self, Context, ContextBuilder};
+use crate::hookevent::HookEvent;
+use crate::luauserdata::Rect;
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub enum Error {
+    Parse {
+        line: usize,
+        message: String,
+    },
+    Context(context::Error),
+    Failed {
+        line: usize,
+        time: Duration,
+        message: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Parse { line, message } => write!(f, "Scenario line {}: {}", line, message),
+            Error::Context(e) => write!(f, "{}", e),
+            Error::Failed {
+                line,
+                time,
+                message,
+            } => write!(
+                f,
+                "Scenario line {} at {}ms: {}",
+                line,
+                time.as_millis(),
+                message
+            ),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Step {
+    Create {
+        name: String,
+        window: SimulatedWindow,
+    },
+    Move {
+        name: String,
+        rect: Rect,
+    },
+    MinMax {
+        name: String,
+        show_command: i32,
+    },
+    Show {
+        name: String,
+        shown: bool,
+    },
+    Activate {
+        name: String,
+        caused_by_mouse: bool,
+    },
+    Destroy {
+        name: String,
+    },
+    Retitle {
+        name: String,
+        title: String,
+    },
+    Advance(Duration),
+    ExpectRect {
+        name: String,
+        rect: Rect,
+    },
+    ExpectResponse {
+        name: String,
+        response: Option<Rect>,
+    },
+    ExpectState {
+        name: String,
+        state: ShowState,
+    },
+    ExpectFocus(Option<String>),
+}
+
+/// A script of desktop activity and expectations about how a wlw.lua reacts
+/// to it, one step per line. Blank lines and lines starting with `#` are
+/// skipped, and rects are written `left,top,right,bottom`.
+///
+/// ```text
+/// create NAME [title="TITLE"] [class="CLASS"] [exe="EXE"] [rect=RECT] [hidden]
+/// move NAME RECT              # the user drags the window
+/// minimize|maximize|restore NAME
+/// show|hide NAME
+/// activate NAME [mouse]
+/// retitle NAME "TITLE"
+/// destroy NAME
+/// advance MILLISECONDS
+/// expect rect NAME RECT
+/// expect response NAME RECT|none  # the last rect the script answered with
+/// expect state NAME hidden|normal|minimized|maximized
+/// expect focus NAME|none
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scenario {
+    steps: Vec<(usize, Step)>,
+}
+
+fn tokenize(line: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        match chars.peek() {
+            None => return Ok(tokens),
+            Some(c) if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            Some(_) => {}
+        }
+        let mut token = String::new();
+        let mut quoted = false;
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => quoted = !quoted,
+                '\\' if quoted => match chars.next() {
+                    Some(c) => token.push(c),
+                    None => return Err("Line ends in an escape".to_owned()),
+                },
+                c if c.is_whitespace() && !quoted => break,
+                c => token.push(c),
+            }
+        }
+        if quoted {
+            return Err("Unterminated quote".to_owned());
+        }
+        tokens.push(token);
+    }
+}
+
+fn parse_rect(text: &str) -> Result<Rect, String> {
+    let values = text
+        .split(',')
+        .map(|value| value.trim().parse::<i32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| format!("Bad rect \"{}\"", text))?;
+    match values[..] {
+        [left, top, right, bottom] => Ok(Rect::new(left, top, right, bottom)),
+        _ => Err(format!("Bad rect \"{}\"", text)),
+    }
+}
+
+fn parse_create(name: String, options: &[String]) -> Result<Step, String> {
+    let mut window = SimulatedWindow::new("", Rect::new(0, 0, 800, 600));
+    for option in options {
+        let mut split = option.splitn(2, '=');
+        match (split.next().unwrap(), split.next()) {
+            ("title", Some(title)) => window.title = title.to_owned(),
+            ("class", Some(class)) => window.class = class.to_owned(),
+            ("exe", Some(exe)) => window.exe = exe.to_owned(),
+            ("rect", Some(rect)) => {
+                window.rect = parse_rect(rect)?;
+                window.normal_rect = window.rect;
+            }
+            ("hidden", None) => window.style = WS_OVERLAPPEDWINDOW,
+            _ => return Err(format!("Unknown window option \"{}\"", option)),
+        }
+    }
+    Ok(Step::Create { name, window })
+}
+
+fn parse_expect(tokens: &[String]) -> Result<Step, String> {
+    let arg = |index: usize| -> Result<&str, String> {
+        tokens
+            .get(index)
+            .map(|token| token.as_str())
+            .ok_or_else(|| "Missing argument".to_owned())
+    };
+    let (step, num_args) = match arg(0)? {
+        "rect" => (
+            Step::ExpectRect {
+                name: arg(1)?.to_owned(),
+                rect: parse_rect(arg(2)?)?,
+            },
+            3,
+        ),
+        "response" => (
+            Step::ExpectResponse {
+                name: arg(1)?.to_owned(),
+                response: match arg(2)? {
+                    "none" => None,
+                    rect => Some(parse_rect(rect)?),
+                },
+            },
+            3,
+        ),
+        "state" => (
+            Step::ExpectState {
+                name: arg(1)?.to_owned(),
+                state: match arg(2)? {
+                    "hidden" => ShowState::Hidden,
+                    "normal" => ShowState::Normal,
+                    "minimized" => ShowState::Minimized,
+                    "maximized" => ShowState::Maximized,
+                    state => return Err(format!("Unknown state \"{}\"", state)),
+                },
+            },
+            3,
+        ),
+        "focus" => (
+            Step::ExpectFocus(match arg(1)? {
+                "none" => None,
+                name => Some(name.to_owned()),
+            }),
+            2,
+        ),
+        what => return Err(format!("Unknown expectation \"{}\"", what)),
+    };
+    if tokens.len() > num_args {
+        return Err(format!("Unexpected \"{}\"", tokens[num_args]));
+    }
+    Ok(step)
+}
+
+fn parse_step(tokens: &[String]) -> Result<Step, String> {
+    let command = tokens[0].as_str();
+    if command == "expect" {
+        return parse_expect(&tokens[1..]);
+    }
+    let arg = tokens
+        .get(1)
+        .cloned()
+        .ok_or_else(|| format!("{} needs an argument", command))?;
+    let rest = &tokens[2..];
+    let step = match command {
+        "create" => return parse_create(arg, rest),
+        "move" => {
+            return match rest {
+                [rect] => Ok(Step::Move {
+                    name: arg,
+                    rect: parse_rect(rect)?,
+                }),
+                [] => Err("move needs a rect".to_owned()),
+                _ => Err(format!("Unexpected \"{}\"", rest[1])),
+            };
+        }
+        "retitle" => {
+            return match rest {
+                [title] => Ok(Step::Retitle {
+                    name: arg,
+                    title: title.clone(),
+                }),
+                [] => Err("retitle needs a title".to_owned()),
+                _ => Err(format!("Unexpected \"{}\"", rest[1])),
+            };
+        }
+        "activate" => {
+            return match rest {
+                [] => Ok(Step::Activate {
+                    name: arg,
+                    caused_by_mouse: false,
+                }),
+                [mouse] if mouse == "mouse" => Ok(Step::Activate {
+                    name: arg,
+                    caused_by_mouse: true,
+                }),
+                _ => Err(format!("Unexpected \"{}\"", rest[0])),
+            };
+        }
+        "minimize" => Step::MinMax {
+            name: arg,
+            show_command: SW_MINIMIZE,
+        },
+        "maximize" => Step::MinMax {
+            name: arg,
+            show_command: SW_MAXIMIZE,
+        },
+        "restore" => Step::MinMax {
+            name: arg,
+            show_command: SW_RESTORE,
+        },
+        "show" => Step::Show {
+            name: arg,
+            shown: true,
+        },
+        "hide" => Step::Show {
+            name: arg,
+            shown: false,
+        },
+        "destroy" => Step::Destroy { name: arg },
+        "advance" => Step::Advance(Duration::from_millis(
+            arg.parse()
+                .map_err(|_| format!("Bad duration \"{}\"", arg))?,
+        )),
+        _ => return Err(format!("Unknown command \"{}\"", command)),
+    };
+    match rest.first() {
+        Some(extra) => Err(format!("Unexpected \"{}\"", extra)),
+        None => Ok(step),
+    }
+}
+
+impl Scenario {
+    pub fn parse(text: &str) -> Result<Scenario, Error> {
+        let mut steps = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let parse_error = |message| Error::Parse {
+                line: line_number,
+                message,
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let tokens = tokenize(line).map_err(parse_error)?;
+            steps.push((line_number, parse_step(&tokens).map_err(parse_error)?));
+        }
+        Ok(Scenario { steps })
+    }
+
+    /// Runs the scenario against a fresh simulated desktop, stopping at the
+    /// first expectation which does not hold.
+    pub fn run(&self, script: &str) -> Result<(), Error> {
+        let backend = Arc::new(SimulatedBackend::new());
+        let mut context = ContextBuilder::new(backend.clone())
+            .script_source(script)
+            .build()
+            .map_err(Error::Context)?;
+        let mut runner = Runner {
+            backend,
+            names: HashMap::new(),
+            responses: HashMap::new(),
+            time: Duration::from_millis(0),
+        };
+        for (line, step) in &self.steps {
+            runner
+                .run_step(&mut context, step)
+                .map_err(|message| Error::Failed {
+                    line: *line,
+                    time: runner.time,
+                    message,
+                })?;
+        }
+        Ok(())
+    }
+}
+
+struct Runner {
+    backend: Arc<SimulatedBackend>,
+    names: HashMap<String, WindowId>,
+    responses: HashMap<WindowId, Rect>,
+    time: Duration,
+}
+
+impl Runner {
+    fn id(&self, name: &str) -> Result<WindowId, String> {
+        self.names
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("No window named \"{}\"", name))
+    }
+
+    fn name(&self, id: WindowId) -> &str {
+        self.names
+            .iter()
+            .find(|&(_, &other)| other == id)
+            .map_or("<unnamed>", |(name, _)| name.as_str())
+    }
+
+    fn run_step(&mut self, context: &mut Context, step: &Step) -> Result<(), String> {
+        let backend = self.backend.clone();
+        let mut responses = Vec::new();
+        let mut lua_error = None;
+        let mut hook = |event: HookEvent| {
+            let hwnd = event.hwnd();
+            match context.handle_hook_event(event) {
+                Ok(response) => {
+                    if let Some(rect) = response.and_then(|response| response.rect()) {
+                        responses.push((hwnd, rect));
+                    }
+                    response
+                }
+                Err(e) => {
+                    lua_error.get_or_insert(e.to_string());
+                    None
+                }
+            }
+        };
+        let result = match step {
+            Step::Create { name, window } => {
+                if self.names.contains_key(name) {
+                    return Err(format!("A window named \"{}\" already exists", name));
+                }
+                let id = backend.open_window(window.clone(), &mut hook);
+                self.names.insert(name.clone(), id);
+                Ok(())
+            }
+            Step::Move { name, rect } => backend
+                .move_size_window(self.id(name)?, *rect, &mut hook)
+                .map_err(|e| e.to_string()),
+            Step::MinMax { name, show_command } => backend
+                .min_max_window(self.id(name)?, *show_command, &mut hook)
+                .map_err(|e| e.to_string()),
+            Step::Show { name, shown } => backend
+                .set_window_shown(self.id(name)?, *shown, &mut hook)
+                .map_err(|e| e.to_string()),
+            Step::Activate {
+                name,
+                caused_by_mouse,
+            } => backend
+                .activate_window(self.id(name)?, *caused_by_mouse, &mut hook)
+                .map_err(|e| e.to_string()),
+            Step::Retitle { name, title } => backend
+                .retitle_window(self.id(name)?, title, &mut hook)
+                .map_err(|e| e.to_string()),
+            Step::Destroy { name } => {
+                let id = self.id(name)?;
+                let result = backend.close_window(id, &mut hook);
+                self.names.remove(name);
+                result.map_err(|e| e.to_string())
+            }
+            Step::Advance(duration) => {
+                self.time += *duration;
+                Ok(())
+            }
+            Step::ExpectRect { name, rect } => {
+                let actual = backend
+                    .get_window_rect(self.id(name)?)
+                    .map_err(|e| e.to_string())?;
+                expect(name, "rect", *rect, actual)
+            }
+            Step::ExpectResponse { name, response } => {
+                let actual = self.responses.get(&self.id(name)?).cloned();
+                expect(name, "response", *response, actual)
+            }
+            Step::ExpectState { name, state } => {
+                let actual = backend
+                    .get_show_state(self.id(name)?)
+                    .map_err(|e| e.to_string())?;
+                expect(name, "state", *state, actual)
+            }
+            Step::ExpectFocus(name) => {
+                let actual = backend.focused().map(|id| self.name(id).to_owned());
+                if *name == actual {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "Expected focus on {:?}, but it is on {:?}",
+                        name, actual
+                    ))
+                }
+            }
+        };
+        self.responses.extend(responses);
+        match lua_error {
+            Some(e) => Err(e),
+            None => result,
+        }
+    }
+}
+
+fn expect<T: fmt::Debug + PartialEq>(
+    name: &str,
+    what: &str,
+    expected: T,
+    actual: T,
+) -> Result<(), String> {
+    if expected == actual {
+        Ok(())
+    } else {
+        Err(format!(
+            "Expected {} of \"{}\" to be {:?}, but it is {:?}",
+            what, name, expected, actual
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCRIPT: &str = r#"
+        function wlw.on_window_create(w, rect)
+            if w.title == "Terminal" then
+                rect.x = 0
+                rect.y = 0
+                rect.width = 960
+                rect.height = 1040
+            end
+            return rect
+        end
+        function wlw.on_window_move_resize(w, rect)
+            rect.width = math.min(rect.width, 800)
+            return rect
+        end
+    "#;
+
+    #[test]
+    fn tokens_respect_quotes() {
+        assert_eq!(
+            tokenize(r#"create term title="My \"Terminal\"" rect=1,2,3,4"#).unwrap(),
+            vec!["create", "term", "title=My \"Terminal\"", "rect=1,2,3,4"]
+        );
+        assert!(tokenize(r#"create term title="oops"#).is_err());
+    }
+
+    #[test]
+    fn passing_scenario() {
+        let scenario = Scenario::parse(
+            r#"
+            # Terminals snap to the left half
+            create term title="Terminal" class="ConsoleWindowClass" rect=100,100,500,400
+            expect response term 0,0,960,1040
+            expect rect term 0,0,960,1040
+            expect focus term
+
+            create notes title="Notes" rect=200,200,400,400
+            expect rect notes 200,200,400,400
+            move notes 0,0,1000,500
+            expect rect notes 0,0,800,500
+
+            minimize notes
+            expect state notes minimized
+            expect focus term
+            advance 250
+            destroy term
+            expect focus none
+            "#,
+        )
+        .unwrap();
+        scenario.run(SCRIPT).unwrap();
+    }
+
+    #[test]
+    fn title_changes_reach_the_script() {
+        let scenario = Scenario::parse(
+            r#"
+            create mail title="Inbox" rect=100,100,500,400
+            retitle mail "Inbox (1 unread)"
+            expect rect mail 0,0,10,10
+            "#,
+        )
+        .unwrap();
+        let script = r#"
+            function wlw.on_window_title_change(w, title)
+                if title:find("unread") and w.title == "Inbox" then
+                    w:set_window_rect(0, 0, 10, 10)
+                end
+            end
+        "#;
+        scenario.run(script).unwrap();
+        assert!(Scenario::parse("retitle mail").is_err());
+    }
+
+    #[test]
+    fn failed_expectation_reports_line_and_time() {
+        let scenario = Scenario::parse(
+            "create notes title=Notes rect=0,0,100,100\nadvance 1500\nexpect state notes maximized\n",
+        )
+        .unwrap();
+        match scenario.run(SCRIPT) {
+            Err(Error::Failed { line: 3, time, .. }) => {
+                assert_eq!(time, Duration::from_millis(1500))
+            }
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_errors_report_line() {
+        match Scenario::parse("create a\nexplode a\n") {
+            Err(Error::Parse { line: 2, .. }) => {}
+            other => panic!("Unexpected result {:?}", other),
+        }
+        assert!(Scenario::parse("move a 1,2,3").is_err());
+        assert!(Scenario::parse("expect focus a b").is_err());
+    }
+
+    #[test]
+    fn lua_errors_fail_the_step() {
+        let scenario = Scenario::parse("create notes title=Notes").unwrap();
+        let script = "function wlw.on_window_create(w, rect) error('boom') end";
+        match scenario.run(script) {
+            Err(Error::Failed {
+                line: 1, message, ..
+            }) => assert!(message.contains("boom")),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+}
@@ -1,24 +1,123 @@
 #[macro_use]
 extern crate log;
+use std::env;
+use std::error::Error;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::PathBuf;
+#[cfg(any(windows, unix))]
+use std::sync::Arc;
+#[cfg(unix)]
+use std::thread;
+#[cfg(windows)]
 use wintrap::{self, Signal};
-pub mod context;
-#[cfg(debug_assertions)]
-pub mod debug;
-pub mod hookevent;
-pub mod hookmanager;
-pub mod luauserdata;
-pub mod pipeserver;
-use crate::context::Context;
-use crossbeam_channel as xchan;
+#[cfg(unix)]
+use wlw_server::backend;
+#[cfg(windows)]
+use wlw_server::backend::win32::Win32Backend;
+#[cfg(unix)]
+use wlw_server::backend::x11::X11Backend;
+use wlw_server::context;
+#[cfg(any(windows, unix))]
+use wlw_server::context::ContextBuilder;
+use wlw_server::recording;
+#[cfg(any(windows, unix))]
+use wlw_server::recording::Recorder;
 
 use flexi_logger::Logger;
 
-fn run() -> Result<(), context::Error> {
-    let (event_sender, event_receiver) = xchan::unbounded::<context::Event>();
-    let interrupt_event_sender = event_sender.clone();
-    let mut context = Context::new(event_sender, event_receiver)?;
+const USAGE: &str = "Usage: wlw-server [--record RECORDING | --replay RECORDING [SCRIPT]]";
+
+#[derive(Debug)]
+enum MainError {
+    Usage(String),
+    #[cfg(not(any(windows, unix)))]
+    ServeUnsupported,
+    #[cfg(unix)]
+    Backend(backend::Error),
+    Context(context::Error),
+    RecordingOpen(io::Error),
+    Recording(recording::Error),
+    ReplayMismatch(usize),
+}
+
+impl fmt::Display for MainError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MainError::Usage(message) => write!(f, "{}\n{}", message, USAGE),
+            #[cfg(not(any(windows, unix)))]
+            MainError::ServeUnsupported => write!(f, "wlw-server requires Windows or X11"),
+            #[cfg(unix)]
+            MainError::Backend(e) => write!(f, "{}", e),
+            MainError::Context(e) => write!(f, "{}", e),
+            MainError::RecordingOpen(e) => write!(f, "Error opening recording: {}", e),
+            MainError::Recording(e) => write!(f, "{}", e),
+            MainError::ReplayMismatch(count) => {
+                write!(f, "{} responses differ from the recording", count)
+            }
+        }
+    }
+}
+
+impl Error for MainError {}
+
+enum Mode {
+    Serve {
+        record: Option<PathBuf>,
+    },
+    Replay {
+        recording: PathBuf,
+        script: Option<PathBuf>,
+    },
+}
+
+fn parse_args(mut args: impl Iterator<Item = OsString>) -> Result<Mode, MainError> {
+    let mode = match args.next() {
+        None => Mode::Serve { record: None },
+        Some(ref flag) if flag == "--record" => Mode::Serve {
+            record: Some(
+                args.next()
+                    .ok_or_else(|| MainError::Usage("--record needs a file".to_owned()))?
+                    .into(),
+            ),
+        },
+        Some(ref flag) if flag == "--replay" => Mode::Replay {
+            recording: args
+                .next()
+                .ok_or_else(|| MainError::Usage("--replay needs a file".to_owned()))?
+                .into(),
+            script: args.next().map(PathBuf::from),
+        },
+        Some(arg) => {
+            return Err(MainError::Usage(format!(
+                "Unknown argument \"{}\"",
+                arg.to_string_lossy()
+            )))
+        }
+    };
+    match args.next() {
+        Some(arg) => Err(MainError::Usage(format!(
+            "Unexpected argument \"{}\"",
+            arg.to_string_lossy()
+        ))),
+        None => Ok(mode),
+    }
+}
+
+#[cfg(windows)]
+fn serve(record: Option<PathBuf>) -> Result<(), MainError> {
+    let mut builder = ContextBuilder::new(Arc::new(Win32Backend::new())).supervise_hooks();
+    if let Some(path) = record {
+        let file = File::create(path).map_err(MainError::RecordingOpen)?;
+        builder = builder.recorder(Recorder::new(file).map_err(MainError::RecordingOpen)?);
+    }
+    let mut context = builder.build().map_err(MainError::Context)?;
+    context.adopt_windows().map_err(MainError::Context)?;
+    let interrupt_event_sender = context.event_sender();
     wintrap::trap(
-        vec![Signal::CtrlC, Signal::CloseWindow, Signal::CloseConsole],
+        &[Signal::CtrlC, Signal::CloseWindow, Signal::CloseConsole],
         move |_| {
             interrupt_event_sender
                 .send(context::Event::Interrupt)
@@ -26,8 +125,64 @@ fn run() -> Result<(), context::Error> {
         },
         move || context.run(),
     )
-    .unwrap()?;
-    Ok(())
+    .unwrap()
+    .map_err(MainError::Context)
+}
+
+#[cfg(unix)]
+fn serve(record: Option<PathBuf>) -> Result<(), MainError> {
+    let backend = Arc::new(X11Backend::connect(None).map_err(MainError::Backend)?);
+    let mut builder = ContextBuilder::new(backend.clone());
+    if let Some(path) = record {
+        let file = File::create(path).map_err(MainError::RecordingOpen)?;
+        builder = builder.recorder(Recorder::new(file).map_err(MainError::RecordingOpen)?);
+    }
+    let mut context = builder.build().map_err(MainError::Context)?;
+    context.adopt_windows().map_err(MainError::Context)?;
+    let event_sender = context.event_sender();
+    thread::spawn(move || {
+        let result = backend.manage(&mut |event| context::forward_hook_event(&event_sender, event));
+        if let Err(e) = result {
+            event_sender.send(context::Event::BackendFail(e)).ok();
+        }
+    });
+    context.run().map_err(MainError::Context)
+}
+
+#[cfg(not(any(windows, unix)))]
+fn serve(_record: Option<PathBuf>) -> Result<(), MainError> {
+    Err(MainError::ServeUnsupported)
+}
+
+fn replay(recording: PathBuf, script: Option<PathBuf>) -> Result<(), MainError> {
+    let script = context::read_script(script.unwrap_or_else(context::default_script_path))
+        .map_err(MainError::Context)?;
+    let file = File::open(recording).map_err(MainError::RecordingOpen)?;
+    let entries = recording::read(BufReader::new(file)).map_err(MainError::Recording)?;
+    let mismatches = recording::replay(&script, &entries).map_err(MainError::Recording)?;
+    for mismatch in &mismatches {
+        warn!(
+            "At {}ms, \"{}\" got {:?} instead of {:?} for {:?}",
+            mismatch.entry.time.as_millis(),
+            mismatch.entry.title,
+            mismatch.replayed,
+            mismatch.entry.response,
+            mismatch.entry.event
+        );
+    }
+    info!("Replayed {} events", entries.len());
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(MainError::ReplayMismatch(mismatches.len()))
+    }
+}
+
+fn run() -> Result<(), MainError> {
+    match parse_args(env::args_os().skip(1))? {
+        Mode::Serve { record } => serve(record),
+        Mode::Replay { recording, script } => replay(recording, script),
+    }
 }
 
 fn main() {
@@ -0,0 +1,48 @@
+use flexi_logger::Logger;
+use std::env;
+use std::fs;
+use std::process;
+use wlw_server::scenario::Scenario;
+
+const USAGE: &str = "Usage: wlw-test SCRIPT SCENARIO...";
+
+fn run_scenario(script: &str, path: &str) -> Result<(), String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Error reading scenario: {}", e))?;
+    let scenario = Scenario::parse(&text).map_err(|e| e.to_string())?;
+    scenario.run(script).map_err(|e| e.to_string())
+}
+
+fn main() {
+    Logger::with_env_or_str("warn").start().unwrap();
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() < 2 {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    }
+    let script = match fs::read_to_string(&args[0]) {
+        Ok(script) => script,
+        Err(e) => {
+            eprintln!("Error reading Lua script: {}", e);
+            process::exit(2);
+        }
+    };
+
+    let mut num_failed = 0;
+    for path in &args[1..] {
+        match run_scenario(&script, path) {
+            Ok(()) => println!("PASS {}", path),
+            Err(e) => {
+                println!("FAIL {}: {}", path, e);
+                num_failed += 1;
+            }
+        }
+    }
+    println!(
+        "{} passed, {} failed",
+        args.len() - 1 - num_failed,
+        num_failed
+    );
+    if num_failed > 0 {
+        process::exit(1);
+    }
+}
@@ -1,14 +1,14 @@
 pub use winapi::ctypes::c_int;
 pub use winapi::shared::minwindef::{
     BOOL, DWORD, FALSE, FARPROC, HINSTANCE, HLOCAL, HMODULE, LPARAM, LPCVOID, LPDWORD, LPVOID,
-    LRESULT, TRUE, UINT, WPARAM,
+    LRESULT, MAX_PATH, TRUE, UINT, WPARAM,
 };
 pub use winapi::shared::ntdef::{
     HANDLE, LANG_NEUTRAL, LONG, LPCWSTR, LPWSTR, MAKELANGID, SUBLANG_DEFAULT,
 };
-pub use winapi::shared::windef::{HHOOK, HWND, RECT};
+pub use winapi::shared::windef::{HHOOK, HWND, POINT, RECT};
 pub use winapi::shared::winerror::{
-    ERROR_IO_PENDING, ERROR_PIPE_CONNECTED, ERROR_SUCCESS, WAIT_TIMEOUT,
+    ERROR_IO_INCOMPLETE, ERROR_IO_PENDING, ERROR_PIPE_CONNECTED, ERROR_SUCCESS, WAIT_TIMEOUT,
 };
 pub use winapi::um::handleapi::INVALID_HANDLE_VALUE;
 pub use winapi::um::minwinbase::{LPOVERLAPPED, LPSECURITY_ATTRIBUTES, OVERLAPPED};
@@ -20,17 +20,22 @@ pub use winapi::um::winbase::{
     FILE_FLAG_OVERLAPPED, INFINITE, PIPE_ACCESS_DUPLEX, PIPE_READMODE_MESSAGE, PIPE_TYPE_MESSAGE,
     PIPE_UNLIMITED_INSTANCES, PIPE_WAIT, WAIT_ABANDONED_0, WAIT_FAILED, WAIT_OBJECT_0,
 };
-pub use winapi::um::winnt::SYNCHRONIZE;
+pub use winapi::um::winnt::{PROCESS_QUERY_LIMITED_INFORMATION, SYNCHRONIZE};
 pub use winapi::um::winuser::{
-    GWL_EXSTYLE, GWL_STYLE, HOOKPROC, HWND_TOP, MSG, SWP_NOACTIVATE, SW_FORCEMINIMIZE, SW_HIDE,
-    SW_MAXIMIZE, SW_MINIMIZE, SW_RESTORE, SW_SHOW, SW_SHOWDEFAULT, SW_SHOWMINIMIZED,
-    SW_SHOWMINNOACTIVE, SW_SHOWNA, SW_SHOWNOACTIVATE, SW_SHOWNORMAL, WH_CALLWNDPROC, WH_CBT,
-    WM_QUIT, WS_BORDER, WS_CAPTION, WS_CHILD, WS_CLIPCHILDREN, WS_CLIPSIBLINGS, WS_DISABLED,
-    WS_DLGFRAME, WS_EX_ACCEPTFILES, WS_EX_APPWINDOW, WS_EX_CLIENTEDGE, WS_EX_COMPOSITED,
-    WS_EX_CONTEXTHELP, WS_EX_CONTROLPARENT, WS_EX_DLGMODALFRAME, WS_EX_LAYERED, WS_EX_LAYOUTRTL,
-    WS_EX_LEFTSCROLLBAR, WS_EX_MDICHILD, WS_EX_NOACTIVATE, WS_EX_NOINHERITLAYOUT,
-    WS_EX_NOPARENTNOTIFY, WS_EX_NOREDIRECTIONBITMAP, WS_EX_RIGHT, WS_EX_RTLREADING,
-    WS_EX_STATICEDGE, WS_EX_TOOLWINDOW, WS_EX_TOPMOST, WS_EX_TRANSPARENT, WS_EX_WINDOWEDGE,
-    WS_GROUP, WS_HSCROLL, WS_ICONIC, WS_MAXIMIZE, WS_MAXIMIZEBOX, WS_MINIMIZE, WS_MINIMIZEBOX,
-    WS_POPUP, WS_SYSMENU, WS_TABSTOP, WS_THICKFRAME, WS_VISIBLE, WS_VSCROLL,
+    GWL_EXSTYLE, GWL_STYLE, GW_OWNER, HOOKPROC, HWND_BOTTOM, HWND_NOTOPMOST, HWND_TOP,
+    HWND_TOPMOST, LWA_ALPHA, MONITORINFO, MONITOR_DEFAULTTONEAREST, MSG, SWP_NOACTIVATE,
+    SWP_NOMOVE, SWP_NOSIZE, SW_FORCEMINIMIZE, SW_HIDE, SW_MAXIMIZE, SW_MINIMIZE, SW_RESTORE,
+    SW_SHOW, SW_SHOWDEFAULT, SW_SHOWMINIMIZED, SW_SHOWMINNOACTIVE, SW_SHOWNA, SW_SHOWNOACTIVATE,
+    SW_SHOWNORMAL, WH_CALLWNDPROC, WH_CBT, WM_CLOSE, WM_QUIT, WS_BORDER, WS_CAPTION, WS_CHILD,
+    WS_CLIPCHILDREN, WS_CLIPSIBLINGS, WS_DISABLED, WS_DLGFRAME, WS_EX_ACCEPTFILES, WS_EX_APPWINDOW,
+    WS_EX_CLIENTEDGE, WS_EX_COMPOSITED, WS_EX_CONTEXTHELP, WS_EX_CONTROLPARENT,
+    WS_EX_DLGMODALFRAME, WS_EX_LAYERED, WS_EX_LAYOUTRTL, WS_EX_LEFTSCROLLBAR, WS_EX_MDICHILD,
+    WS_EX_NOACTIVATE, WS_EX_NOINHERITLAYOUT, WS_EX_NOPARENTNOTIFY, WS_EX_NOREDIRECTIONBITMAP,
+    WS_EX_RIGHT, WS_EX_RTLREADING, WS_EX_STATICEDGE, WS_EX_TOOLWINDOW, WS_EX_TOPMOST,
+    WS_EX_TRANSPARENT, WS_EX_WINDOWEDGE, WS_GROUP, WS_HSCROLL, WS_ICONIC, WS_MAXIMIZE,
+    WS_MAXIMIZEBOX, WS_MINIMIZE, WS_MINIMIZEBOX, WS_POPUP, WS_SYSMENU, WS_TABSTOP, WS_THICKFRAME,
+    WS_VISIBLE, WS_VSCROLL,
 };
+
+// Missing from winapi's winuser
+pub const GA_PARENT: UINT = 1;
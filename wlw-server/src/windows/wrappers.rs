@@ -13,9 +13,16 @@ pub enum ErrorOrigin {
     SetWindowText,
     GetWindowTextLength,
     GetWindowText,
+    GetWindowThreadProcessId,
+    GetClassName,
+    EnumWindows,
+    QueryFullProcessImageName,
     GetWindowLong,
     SetWindowLong,
     GetWindowRect,
+    GetClientRect,
+    ClientToScreen,
+    GetMonitorInfo,
     SetWindowPos,
     ReadFile,
     WriteFile,
@@ -39,6 +46,8 @@ pub enum ErrorOrigin {
     OpenProcess,
     GetExitCodeProcess,
     PostThreadMessage,
+    PostMessage,
+    SetLayeredWindowAttributes,
     GetMessage,
 }
 
@@ -115,6 +124,64 @@ pub unsafe fn GetWindowText(hWnd: HWND) -> Result<OsString> {
     }
 }
 
+/// Returns the ids of the thread which created the window and of its process.
+pub unsafe fn GetWindowThreadProcessId(hWnd: HWND) -> Result<(DWORD, DWORD)> {
+    let mut process_id: DWORD = 0;
+    let thread_id = winapi::um::winuser::GetWindowThreadProcessId(hWnd, &mut process_id);
+    if thread_id == 0 {
+        Err(Error::last(ErrorOrigin::GetWindowThreadProcessId))
+    } else {
+        Ok((thread_id, process_id))
+    }
+}
+
+pub unsafe fn GetClassName(hWnd: HWND) -> Result<OsString> {
+    // Class names are at most 256 characters
+    let mut class_buffer = [0u16; 257];
+    let ret = winapi::um::winuser::GetClassNameW(
+        hWnd,
+        class_buffer.as_mut_ptr(),
+        class_buffer.len() as c_int,
+    );
+    if ret == 0 {
+        Err(Error::last(ErrorOrigin::GetClassName))
+    } else {
+        Ok(OsString::from_wide(&class_buffer[..ret as usize]))
+    }
+}
+
+/// The top-level windows, front to back.
+pub unsafe fn EnumWindows() -> Result<Vec<HWND>> {
+    unsafe extern "system" fn push(hWnd: HWND, lParam: LPARAM) -> BOOL {
+        (*(lParam as *mut Vec<HWND>)).push(hWnd);
+        TRUE
+    }
+    let mut windows: Vec<HWND> = Vec::new();
+    let result =
+        winapi::um::winuser::EnumWindows(Some(push), &mut windows as *mut Vec<HWND> as LPARAM);
+    if result == FALSE {
+        Err(Error::last(ErrorOrigin::EnumWindows))
+    } else {
+        Ok(windows)
+    }
+}
+
+pub unsafe fn QueryFullProcessImageName(hProcess: HANDLE) -> Result<OsString> {
+    let mut path_buffer: Vec<u16> = vec![0; MAX_PATH];
+    let mut size = path_buffer.len() as DWORD;
+    let result = winapi::um::winbase::QueryFullProcessImageNameW(
+        hProcess,
+        0,
+        path_buffer.as_mut_ptr(),
+        &mut size,
+    );
+    if result == FALSE {
+        Err(Error::last(ErrorOrigin::QueryFullProcessImageName))
+    } else {
+        Ok(OsString::from_wide(&path_buffer[..size as usize]))
+    }
+}
+
 pub unsafe fn GetWindowLong(hWnd: HWND, nIndex: c_int) -> Result<LONG> {
     let result = winapi::um::winuser::GetWindowLongW(hWnd, nIndex);
     if result == 0 {
@@ -154,6 +221,51 @@ pub unsafe fn GetWindowRect(hWnd: HWND) -> Result<RECT> {
     }
 }
 
+/// The client area, in screen coordinates.
+pub unsafe fn GetClientRect(hWnd: HWND) -> Result<RECT> {
+    let mut rect: RECT = mem::zeroed();
+    if winapi::um::winuser::GetClientRect(hWnd, &mut rect as *mut _) == FALSE {
+        return Err(Error::last(ErrorOrigin::GetClientRect));
+    }
+    let mut top_left = POINT { x: 0, y: 0 };
+    if winapi::um::winuser::ClientToScreen(hWnd, &mut top_left as *mut _) == FALSE {
+        return Err(Error::last(ErrorOrigin::ClientToScreen));
+    }
+    Ok(RECT {
+        left: rect.left + top_left.x,
+        top: rect.top + top_left.y,
+        right: rect.right + top_left.x,
+        bottom: rect.bottom + top_left.y,
+    })
+}
+
+/// Null if the window has no owner.
+pub unsafe fn GetOwner(hWnd: HWND) -> HWND {
+    winapi::um::winuser::GetWindow(hWnd, GW_OWNER)
+}
+
+/// Null for top-level windows, whose parent is the desktop.
+pub unsafe fn GetParent(hWnd: HWND) -> HWND {
+    let parent = winapi::um::winuser::GetAncestor(hWnd, GA_PARENT);
+    if parent == winapi::um::winuser::GetDesktopWindow() {
+        ptr::null_mut()
+    } else {
+        parent
+    }
+}
+
+/// The bounds and work area of the monitor the window overlaps the most.
+pub unsafe fn GetMonitorInfo(hWnd: HWND) -> Result<(RECT, RECT)> {
+    let monitor = winapi::um::winuser::MonitorFromWindow(hWnd, MONITOR_DEFAULTTONEAREST);
+    let mut info: MONITORINFO = mem::zeroed();
+    info.cbSize = mem::size_of::<MONITORINFO>() as DWORD;
+    if winapi::um::winuser::GetMonitorInfoW(monitor, &mut info as *mut _) == FALSE {
+        Err(Error::last(ErrorOrigin::GetMonitorInfo))
+    } else {
+        Ok((info.rcMonitor, info.rcWork))
+    }
+}
+
 pub unsafe fn SetWindowPos(
     hWnd: HWND,
     hWndInsertAfter: HWND,
@@ -171,6 +283,40 @@ pub unsafe fn SetWindowPos(
     }
 }
 
+pub unsafe fn IsWindowVisible(hWnd: HWND) -> bool {
+    winapi::um::winuser::IsWindowVisible(hWnd) != FALSE
+}
+
+pub unsafe fn IsIconic(hWnd: HWND) -> bool {
+    winapi::um::winuser::IsIconic(hWnd) != FALSE
+}
+
+pub unsafe fn IsZoomed(hWnd: HWND) -> bool {
+    winapi::um::winuser::IsZoomed(hWnd) != FALSE
+}
+
+pub unsafe fn ShowWindow(hWnd: HWND, nCmdShow: c_int) -> bool {
+    winapi::um::winuser::ShowWindow(hWnd, nCmdShow) != FALSE
+}
+
+pub unsafe fn SetForegroundWindow(hWnd: HWND) -> bool {
+    winapi::um::winuser::SetForegroundWindow(hWnd) != FALSE
+}
+
+pub unsafe fn SetLayeredWindowAttributes(
+    hWnd: HWND,
+    crKey: DWORD,
+    bAlpha: u8,
+    dwFlags: DWORD,
+) -> Result<()> {
+    let result = winapi::um::winuser::SetLayeredWindowAttributes(hWnd, crKey, bAlpha, dwFlags);
+    if result == FALSE {
+        Err(Error::last(ErrorOrigin::SetLayeredWindowAttributes))
+    } else {
+        Ok(())
+    }
+}
+
 pub enum IoState {
     Pending,
     Finished,
@@ -524,6 +670,15 @@ pub unsafe fn PostThreadMessage(
     }
 }
 
+pub unsafe fn PostMessage(hWnd: HWND, msg: UINT, wParam: WPARAM, lParam: LPARAM) -> Result<()> {
+    let result = winapi::um::winuser::PostMessageW(hWnd, msg, wParam, lParam);
+    if result == FALSE {
+        Err(Error::last(ErrorOrigin::PostMessage))
+    } else {
+        Ok(())
+    }
+}
+
 pub enum GetMessageResult {
     Quit(i32),
     Message(MSG),